@@ -1,4 +1,5 @@
-use super::{AppConfig, FunctionCall, PayType, PayTypeChange};
+use super::{Employee, FunctionCall, PayType, PayTypeChange};
+use crate::{ExecutionOptions, config::ConnectionProfile, progress::ProgressEvent};
 use chrono::NaiveDate;
 use serde::Deserialize;
 
@@ -7,6 +8,7 @@ pub struct ApiPYTMDETResponse {
     pub value: Vec<PYTMDET>,
 }
 
+#[allow(clippy::upper_case_acronyms)] // named after the EBMS table
 #[derive(Debug, Deserialize, Clone)]
 pub struct PYTMDET {
     #[serde(rename = "AUTOID")]
@@ -27,79 +29,207 @@ impl PYTMDET {
     }
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct ApiPREMPLOYResponse {
+    pub value: Vec<PREMPLOY>,
+}
+
+#[allow(clippy::upper_case_acronyms)] // named after the EBMS table
+#[derive(Debug, Deserialize, Clone)]
+pub struct PREMPLOY {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "F_NAME", default)]
+    pub first_name: String,
+    #[serde(rename = "L_NAME", default)]
+    pub last_name: String,
+}
+
+impl From<PREMPLOY> for Employee {
+    fn from(e: PREMPLOY) -> Self {
+        let name = format!("{} {}", e.first_name.trim(), e.last_name.trim())
+            .trim()
+            .to_string();
+        Employee {
+            name: if name.is_empty() { e.id.clone() } else { name },
+            id: e.id.trim().to_string(),
+        }
+    }
+}
+
+// The PREMPLOY column holding the employee ID of an employee's supervisor
+const SUPERVISOR: &str = "SUPERVISOR";
+
+/// Looks up the given employee IDs in the EBMS employee directory.
+pub async fn get_employees(
+    profile: &ConnectionProfile,
+    ids: &[String],
+) -> Result<Vec<Employee>, Box<dyn std::error::Error + Send + Sync>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let id_filters: Vec<String> = ids.iter().map(|id| format!("ID eq '{}'", id)).collect();
+    query_employees(profile, &id_filters.join(" or ")).await
+}
+
+/// The logged-in employee and everyone EBMS lists them as the supervisor of.
+pub async fn get_team(
+    profile: &ConnectionProfile,
+) -> Result<Vec<Employee>, Box<dyn std::error::Error + Send + Sync>> {
+    let id = profile.employee_id.trim();
+    query_employees(
+        profile,
+        &format!("ID eq '{}' or {} eq '{}'", id, SUPERVISOR, id),
+    )
+    .await
+}
+
+async fn query_employees(
+    profile: &ConnectionProfile,
+    filter: &str,
+) -> Result<Vec<Employee>, Box<dyn std::error::Error + Send + Sync>> {
+    let url = format!(
+        "{}/PREMPLOY?$filter={}&$select=ID,F_NAME,L_NAME",
+        profile.ebms_url, filter
+    );
+    let client = client(profile)?;
+    let res = client
+        .get(&url)
+        .basic_auth(
+            profile.ebms_username.clone(),
+            Some(profile.ebms_password.clone()),
+        )
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        let text = res.text().await?;
+        return Err(format!("Error getting employees: {}", text).into());
+    }
+
+    let response: ApiPREMPLOYResponse = res.json().await?;
+    Ok(response.value.into_iter().map(Employee::from).collect())
+}
+
 pub async fn set_pay_type(
-    config: &AppConfig,
-    dates: &Vec<NaiveDate>,
+    profile: &ConnectionProfile,
+    employee: &Employee,
+    dates: &[NaiveDate],
     pay_type: &PayType,
     function_call: &FunctionCall,
-) -> Result<Vec<PayTypeChange>, Box<dyn std::error::Error>> {
-    let pay_code = format_pay_code(pay_type);
-    let pytmdets: Vec<PYTMDET> = get_pytmdets(config, dates).await?;
+    options: &ExecutionOptions,
+) -> Result<Vec<PayTypeChange>, Box<dyn std::error::Error + Send + Sync>> {
+    let pay_code = profile.pay_code(pay_type);
+    options.report(ProgressEvent::EbmsRead {
+        employee: employee.name.clone(),
+        days: dates.len(),
+    });
+    let pytmdets: Vec<PYTMDET> = get_pytmdets(profile, &employee.id, dates).await?;
     if pytmdets.is_empty() {
-        return Err(format!("No time details found for the specified dates").into());
+        return Err("No time details found for the specified dates".into());
     }
 
     let pytmdets_to_change: Vec<&PYTMDET> =
         pytmdets.iter().filter(|d| d.pay_type != pay_code).collect();
+    options.report(ProgressEvent::Planned {
+        changes: pytmdets_to_change.len(),
+    });
 
     if pytmdets_to_change.is_empty() {
-        return Ok(output(dates, pay_type, &pytmdets, None));
+        return Ok(output(
+            employee, dates, pay_type, &pay_code, &pytmdets, None,
+        ));
+    }
+
+    if options.dry_run {
+        return Ok(output(
+            employee,
+            dates,
+            pay_type,
+            &pay_code,
+            &pytmdets,
+            Some(function_call.clone()),
+        ));
     }
 
     let autoids_to_change: Vec<String> = pytmdets_to_change
         .iter()
         .map(|d| d.autoid.clone())
         .collect();
-    let body = get_body(&autoids_to_change, pay_code);
+    let body = get_body(&autoids_to_change, &pay_code);
     let url = format!(
         "{}/TimeDetailManager(c2e90ee5-3e20-473c-9b2c-979a6a2ce6e2)/Model.Entities.ModifyTimeEntries",
-        config.ebms_url
+        profile.ebms_url
     );
-    println!("PATCH {}\n{}", url, body);
+    tracing::debug!(%url, %body, "Modifying time entries");
+    options.report(ProgressEvent::EbmsWrite {
+        rows: autoids_to_change.len(),
+    });
 
-    let client = reqwest::Client::new();
+    let client = client(profile)?;
     let res = client
         .post(&url)
         .basic_auth(
-            config.ebms_username.clone(),
-            Some(config.ebms_password.clone()),
+            profile.ebms_username.clone(),
+            Some(profile.ebms_password.clone()),
         )
         .json(&body)
         .send()
         .await?;
 
-    if res.status().is_success() {
-        Ok(output(
-            &dates,
-            &pay_type,
-            &pytmdets,
-            Some(function_call.clone()),
-        ))
-    } else {
+    if !res.status().is_success() {
         let text = res.text().await?;
-        Err(format!("Error setting pay type: {}", text).into())
+        return Err(format!("Error setting pay type: {}", text).into());
     }
+
+    // Read the changed days back, so a write EBMS accepted but didn't apply isn't reported as done
+    let changed_dates: Vec<NaiveDate> = pytmdets_to_change
+        .iter()
+        .filter_map(|d| d.get_date())
+        .collect();
+    let updated = get_pytmdets(profile, &employee.id, &changed_dates)
+        .await?
+        .iter()
+        .filter(|d| autoids_to_change.contains(&d.autoid) && d.pay_type == pay_code)
+        .count();
+    options.report(ProgressEvent::Verified { rows: updated });
+    if updated < autoids_to_change.len() {
+        return Err(format!(
+            "EBMS accepted the change, but {} of {} entries still don't show {}",
+            autoids_to_change.len() - updated,
+            autoids_to_change.len(),
+            pay_code
+        )
+        .into());
+    }
+    Ok(output(
+        employee,
+        dates,
+        pay_type,
+        &pay_code,
+        &pytmdets,
+        Some(function_call.clone()),
+    ))
 }
 
 fn output(
-    dates: &Vec<NaiveDate>,
+    employee: &Employee,
+    dates: &[NaiveDate],
     pay_type: &PayType,
-    pytmdets: &Vec<PYTMDET>,
+    pay_code: &str,
+    pytmdets: &[PYTMDET],
     function_call: Option<FunctionCall>,
 ) -> Vec<PayTypeChange> {
     let mut changes = Vec::new();
     for date in dates {
-        if let Some(old) = pytmdets.iter().find_map(|d| {
-            if d.get_date() == Some(*date) {
-                Some(d)
-            } else {
-                None
-            }
-        }) {
+        if let Some(old) = pytmdets.iter().find(|d| d.get_date() == Some(*date)) {
             changes.push(PayTypeChange {
+                employee: employee.clone(),
                 date: *date,
                 old_pay_type: old.pay_type.clone(),
                 pay_type: pay_type.clone(),
+                pay_code: pay_code.to_string(),
                 function_call: function_call.clone(),
             });
         }
@@ -108,9 +238,10 @@ fn output(
 }
 
 async fn get_pytmdets(
-    config: &AppConfig,
-    dates: &Vec<NaiveDate>,
-) -> Result<Vec<PYTMDET>, Box<dyn std::error::Error>> {
+    profile: &ConnectionProfile,
+    employee_id: &str,
+    dates: &[NaiveDate],
+) -> Result<Vec<PYTMDET>, Box<dyn std::error::Error + Send + Sync>> {
     if dates.is_empty() {
         return Ok(Vec::new());
     }
@@ -119,7 +250,6 @@ async fn get_pytmdets(
         .iter()
         .map(|d| d.format("%Y-%m-%d").to_string())
         .collect();
-    let client = reqwest::Client::new();
     // Build a filter string for multiple dates using 'or'
     let date_filters: Vec<String> = date_strs
         .iter()
@@ -127,18 +257,52 @@ async fn get_pytmdets(
         .collect();
     let filter = format!(
         "ID eq '{}' and ({})",
-        config.employee_id,
+        employee_id,
         date_filters.join(" or ")
     );
+    let details = fetch_pytmdets(profile, &filter).await?;
+    if details.is_empty() {
+        return Err("No time details found to change".into());
+    }
+
+    tracing::debug!(
+        "Found PYTMDET AUTOIDs: {:?}",
+        details.iter().map(|d| &d.autoid).collect::<Vec<_>>()
+    );
+    Ok(details)
+}
+
+/// All time details for the employee from `start` to `end` inclusive.
+pub async fn get_pytmdets_between(
+    profile: &ConnectionProfile,
+    employee_id: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<Vec<PYTMDET>, Box<dyn std::error::Error + Send + Sync>> {
+    let filter = format!(
+        "ID eq '{}' and DATE ge {}T00:00:00Z and DATE le {}T00:00:00Z",
+        employee_id,
+        start.format("%Y-%m-%d"),
+        end.format("%Y-%m-%d")
+    );
+    fetch_pytmdets(profile, &filter).await
+}
+
+async fn fetch_pytmdets(
+    profile: &ConnectionProfile,
+    filter: &str,
+) -> Result<Vec<PYTMDET>, Box<dyn std::error::Error + Send + Sync>> {
+    let client = client(profile)?;
     let url = format!(
         "{}/PYTMDET?$filter={}&$select=AUTOID,DATE,PAY_LEVEL",
-        config.ebms_url, filter
+        profile.ebms_url, filter
     );
+    tracing::debug!(%url, "Fetching time details");
     let res = client
         .get(&url)
         .basic_auth(
-            config.ebms_username.clone(),
-            Some(config.ebms_password.clone()),
+            profile.ebms_username.clone(),
+            Some(profile.ebms_password.clone()),
         ) // Use OAuth2 or env var
         .send()
         .await?;
@@ -149,18 +313,16 @@ async fn get_pytmdets(
     }
 
     let response: ApiPYTMDETResponse = res.json().await?;
-    let details = response.value;
-    if details.is_empty() {
-        return Err(format!("No time details found to change").into());
-    }
+    Ok(response.value)
+}
 
-    println!(
-        "Found PYTMDET AUTOIDs: {:?}",
-        details.iter().map(|d| &d.autoid).collect::<Vec<_>>()
-    );
-    Ok(details)
+fn client(profile: &ConnectionProfile) -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+        .timeout(profile.timeout())
+        .build()
 }
 
+/// The built-in pay code for a pay type, used unless the profile maps it to another.
 pub fn format_pay_code(pay_type: &PayType) -> &'static str {
     match pay_type {
         PayType::Sick => "Sick-Sal",
@@ -171,7 +333,7 @@ pub fn format_pay_code(pay_type: &PayType) -> &'static str {
     }
 }
 
-fn get_body(autoids: &Vec<String>, pay_type: &str) -> serde_json::Value {
+fn get_body(autoids: &[String], pay_type: &str) -> serde_json::Value {
     serde_json::json!({
         "ModifyEntries": autoids.iter().map(|autoid| {
             serde_json::json!({
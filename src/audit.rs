@@ -0,0 +1,113 @@
+use crate::{
+    PayTypeChange,
+    config::{AppConfig, config_dir},
+    conversation_message::FunctionCall,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// One batch of changes written to EBMS, appended to `audit.jsonl` in the config directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub id: String,
+    pub timestamp: chrono::DateTime<chrono::Local>,
+    pub profile: String,
+    /// The call that was applied; undo records revert several calls and leave this empty
+    pub function_call: Option<FunctionCall>,
+    pub changes: Vec<PayTypeChange>,
+    /// The id of the record this one reverted
+    #[serde(default)]
+    pub undoes: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(
+        profile: &str,
+        function_call: Option<FunctionCall>,
+        changes: Vec<PayTypeChange>,
+        undoes: Option<String>,
+    ) -> Self {
+        let timestamp = chrono::Local::now();
+        AuditRecord {
+            id: timestamp.format("%Y%m%d%H%M%S%6f").to_string(),
+            timestamp,
+            profile: profile.to_string(),
+            function_call,
+            changes,
+            undoes,
+        }
+    }
+}
+
+fn audit_path(config: &AppConfig) -> Option<PathBuf> {
+    config
+        .audit_log_path
+        .clone()
+        .or_else(|| config_dir().map(|dir| dir.join(AUDIT_FILE)))
+}
+
+pub fn append(config: &AppConfig, record: &AuditRecord) {
+    let result = audit_path(config)
+        .ok_or_else(|| "No config directory".into())
+        .and_then(|path| append_json(&path, record));
+    if let Err(e) = result {
+        tracing::error!("Failed to write audit log: {}", e);
+    }
+}
+
+/// Every record in the audit log, oldest first. Unreadable lines are skipped.
+pub fn load(config: &AppConfig) -> Vec<AuditRecord> {
+    audit_path(config)
+        .map(|path| load_json(&path))
+        .unwrap_or_default()
+}
+
+/// Appends the value as one line of a JSON Lines file, creating the file if needed.
+pub(crate) fn append_json<T: Serialize>(
+    path: &Path,
+    value: &T,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(value)?)?;
+    Ok(())
+}
+
+/// Each line of a JSON Lines file that parses, or nothing if it can't be read.
+pub(crate) fn load_json<T: DeserializeOwned>(path: &Path) -> Vec<T> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// The most recent record for the profile that changed something and hasn't been undone.
+pub fn last_undoable(config: &AppConfig, profile: &str) -> Option<AuditRecord> {
+    let records: Vec<AuditRecord> = load(config)
+        .into_iter()
+        .filter(|r| r.profile == profile)
+        .collect();
+    let undone: Vec<&String> = records.iter().filter_map(|r| r.undoes.as_ref()).collect();
+    records
+        .iter()
+        .rev()
+        .find(|r| {
+            r.undoes.is_none()
+                && !undone.contains(&&r.id)
+                && r.changes.iter().any(|c| c.get_function_call().is_some())
+        })
+        .cloned()
+}
@@ -0,0 +1,475 @@
+use agent::{
+    ExecutionError, ExecutionOptions, ExecutionResult, PayTypeChange, audit,
+    clock::Clock,
+    config::{AppConfig, load_config},
+    conversation_message::ConversationMessage,
+    export,
+    import::{self, CsvRow, IcsImportOptions, ImportPlan, ImportPreview},
+    progress::{ProgressEvent, ProgressSink},
+    usage,
+};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+/// Set EBMS pay types from the command line
+#[derive(Parser)]
+#[command(name = "agent-cli")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Prompt to run once, e.g. "vacation next Friday". Starts an interactive session if omitted
+    prompt: Option<String>,
+
+    /// Apply changes without asking for confirmation
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Show what would change without writing anything to EBMS
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Print results as JSON, one object per prompt
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Connection profile to use instead of the one last logged in with
+    #[arg(short, long, global = true)]
+    profile: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Set pay types from the events in a calendar file, e.g. an exported PTO calendar
+    ImportIcs {
+        /// iCalendar (.ics) file; event titles are matched against the configured import rules
+        file: PathBuf,
+
+        /// Name or employee ID to import for instead of yourself
+        #[arg(short, long)]
+        employee: Option<String>,
+
+        /// Skip days before this date (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<NaiveDate>,
+
+        /// Skip days after this date (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+
+    /// Set pay types from a spreadsheet, without the model
+    ImportCsv {
+        /// CSV file with employee, date and pay_type columns and an optional hours column;
+        /// rows under a full day are refused
+        file: PathBuf,
+    },
+
+    /// Write the changes applied with this profile as CSV
+    ExportAudit {
+        /// File to write; standard output if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write time entries between two dates as CSV, or the days off as an ICS calendar
+    ExportEntries {
+        /// First date (YYYY-MM-DD)
+        #[arg(long)]
+        from: NaiveDate,
+
+        /// Last date, inclusive (YYYY-MM-DD)
+        #[arg(long)]
+        to: NaiveDate,
+
+        /// Name or employee ID; yourself if omitted
+        #[arg(short, long)]
+        employee: Option<String>,
+
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+
+        /// File to write; standard output if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Show the tokens and cost of model calls today and this month
+    Usage,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    /// Non-salary days as all-day events
+    Ics,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let mut config = match select_profile(load_config(), args.profile.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    agent::logging::init(&config);
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    if let Some(command) = &args.command {
+        let ok = match command {
+            Command::ImportIcs {
+                file,
+                employee,
+                from,
+                to,
+            } => {
+                let options = IcsImportOptions {
+                    employee: employee.clone(),
+                    from: *from,
+                    to: *to,
+                };
+                match read_file(file) {
+                    Some(text) => {
+                        let plan = import::plan_ics_import(&config, &text, &options);
+                        rt.block_on(run_import(&args, &config, &plan, &[]))
+                    }
+                    None => false,
+                }
+            }
+            Command::ImportCsv { file } => match read_file(file) {
+                Some(text) => match rt.block_on(import::plan_csv_import(&config, &text)) {
+                    Ok(csv) => rt.block_on(run_import(&args, &config, &csv.plan, &csv.rows)),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        false
+                    }
+                },
+                None => false,
+            },
+            Command::ExportAudit { output } => {
+                let profile = config.current_profile.clone().unwrap_or_default();
+                let records: Vec<_> = audit::load(&config)
+                    .into_iter()
+                    .filter(|r| r.profile == profile)
+                    .collect();
+                write_output(output.as_ref(), &export::audit_csv(&records))
+            }
+            Command::ExportEntries {
+                from,
+                to,
+                employee,
+                format,
+                output,
+            } => match rt.block_on(agent::get_time_entries(
+                &config,
+                employee.as_deref(),
+                *from,
+                *to,
+            )) {
+                Ok(entries) => {
+                    let text = match format {
+                        ExportFormat::Csv => export::entries_csv(&entries),
+                        ExportFormat::Ics => export::days_off_ics(&entries, chrono::Utc::now()),
+                    };
+                    write_output(output.as_ref(), &text)
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    false
+                }
+            },
+            Command::Usage => {
+                print_usage(&args, &config);
+                true
+            }
+        };
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    if let Some(profile) = config.profile_mut()
+        && let Err(e) = rt.block_on(agent::load_employee_name(profile))
+    {
+        tracing::warn!("Failed to look up {}: {}", profile.employee_id, e);
+    }
+    let mut conversation: Vec<ConversationMessage> = vec![];
+    match &args.prompt {
+        Some(prompt) => {
+            let ok = rt.block_on(run_prompt(&args, &config, prompt, &mut conversation));
+            if !ok {
+                std::process::exit(1);
+            }
+        }
+        None => {
+            let stdin = std::io::stdin();
+            loop {
+                eprint!(">> ");
+                std::io::stderr().flush().ok();
+                let mut line = String::new();
+                if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                    break;
+                }
+                let prompt = line.trim();
+                match prompt {
+                    "" => continue,
+                    "exit" | "quit" => break,
+                    _ => {
+                        rt.block_on(run_prompt(&args, &config, prompt, &mut conversation));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn select_profile(config: AppConfig, profile: Option<&str>) -> Result<AppConfig, String> {
+    match profile {
+        Some(name) => config
+            .with_profile(name)
+            .ok_or_else(|| format!("No profile named '{}'", name)),
+        None if config.profile().is_some() => Ok(config),
+        None => Err("No profile selected. Log in with the app or pass --profile".to_string()),
+    }
+}
+
+/// Runs one prompt, asking before applying unless `--yes` or `--dry-run` was given.
+/// Returns false if the prompt failed.
+async fn run_prompt(
+    args: &Args,
+    config: &AppConfig,
+    prompt: &str,
+    conversation: &mut Vec<ConversationMessage>,
+) -> bool {
+    let options = ExecutionOptions {
+        dry_run: !args.yes || args.dry_run,
+        on_progress: Some(progress_sink(args.json)),
+        ..Default::default()
+    };
+    let result = match agent::execute_prompt(config, prompt, conversation, &options).await {
+        Ok(ExecutionResult::Planned(function_call, changes)) if !args.dry_run => {
+            if changes.iter().all(|c| c.get_function_call().is_none()) {
+                // Nothing would change, so there's nothing to confirm
+                Ok(ExecutionResult::Success(changes))
+            } else {
+                print_result(
+                    args,
+                    config,
+                    &Ok(ExecutionResult::Planned(function_call.clone(), changes)),
+                );
+                if !confirm("Apply these changes?") {
+                    if args.json {
+                        println!("{}", serde_json::json!({ "status": "declined" }));
+                    } else {
+                        println!("No changes made");
+                    }
+                    return true;
+                }
+                let options = ExecutionOptions {
+                    on_progress: Some(progress_sink(args.json)),
+                    ..Default::default()
+                };
+                agent::execute_function_call(config, &function_call, &options).await
+            }
+        }
+        result => result,
+    };
+
+    print_result(args, config, &result);
+    if let Some(window) =
+        agent::next_conversation_window(config, &options.clock, conversation, prompt, &result).await
+    {
+        *conversation = window.messages;
+    }
+    result.is_ok()
+}
+
+// Progress goes to stderr so stdout only carries results; as JSON lines with --json
+fn progress_sink(json: bool) -> ProgressSink {
+    ProgressSink(std::sync::Arc::new(move |event: &ProgressEvent| {
+        if json {
+            eprintln!("{}", serde_json::json!({ "progress": event }));
+        } else {
+            eprintln!("{}", event);
+        }
+    }))
+}
+
+/// Previews the import, then applies it unless `--dry-run`, asking first unless `--yes`.
+/// `rows` are the CSV rows behind the plan, reported one by one. Returns false if
+/// anything failed to apply.
+async fn run_import(args: &Args, config: &AppConfig, plan: &ImportPlan, rows: &[CsvRow]) -> bool {
+    for skipped in &plan.skipped {
+        eprintln!("Skipped {}", skipped);
+    }
+    let previews = import::preview_import(config, plan).await;
+    print_previews(args, config, "planned", &previews, rows);
+
+    if args.dry_run || !previews.iter().any(ImportPreview::has_changes) {
+        if !args.json && !args.dry_run {
+            println!("Nothing to change");
+        }
+        return true;
+    }
+    if !args.yes && !confirm("Apply these changes?") {
+        if args.json {
+            println!("{}", serde_json::json!({ "status": "declined" }));
+        } else {
+            println!("No changes made");
+        }
+        return true;
+    }
+
+    let applied = import::apply_import(config, &previews).await;
+    print_previews(args, config, "applied", &applied, rows);
+    applied.iter().all(|p| p.changes.is_ok())
+}
+
+fn print_previews(
+    args: &Args,
+    config: &AppConfig,
+    status: &str,
+    previews: &[ImportPreview],
+    rows: &[CsvRow],
+) {
+    let row_results = import::row_results(rows, previews);
+    if args.json {
+        let items: Vec<serde_json::Value> = previews
+            .iter()
+            .map(|p| match &p.changes {
+                Ok(changes) => serde_json::json!({ "source": p.item.source, "changes": changes }),
+                Err(e) => serde_json::json!({ "source": p.item.source, "error": e }),
+            })
+            .collect();
+        let rows: Vec<serde_json::Value> = row_results
+            .iter()
+            .map(|(line, result)| match result {
+                Ok(change) => serde_json::json!({ "line": line, "change": change }),
+                Err(e) => serde_json::json!({ "line": line, "error": e }),
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({ "status": status, "items": items, "rows": rows })
+        );
+        return;
+    }
+    for preview in previews {
+        println!("{}:", preview.item.source);
+        match &preview.changes {
+            Ok(changes) => print_changes(config, changes),
+            Err(e) => eprintln!("  {}", e),
+        }
+    }
+    for (line, result) in row_results {
+        match result {
+            Ok(change) => println!("line {}: {}", line, change),
+            Err(e) => println!("line {}: FAILED {}", line, e),
+        }
+    }
+}
+
+fn print_usage(args: &Args, config: &AppConfig) {
+    let records = usage::load(config);
+    let today = Clock::System.today(config);
+    let periods = [
+        (
+            "today",
+            usage::totals_between(config, &records, usage::day_of(today)),
+        ),
+        (
+            "this month",
+            usage::totals_between(config, &records, usage::month_of(today)),
+        ),
+    ];
+    let budget = Some(config.monthly_budget_usd).filter(|b| *b > 0.0);
+    if args.json {
+        let mut json = serde_json::json!({ "monthly_budget_usd": budget });
+        for (name, totals) in &periods {
+            json[name.replace(' ', "_")] = serde_json::json!({
+                "calls": totals.calls,
+                "prompt_tokens": totals.usage.prompt_tokens,
+                "completion_tokens": totals.usage.completion_tokens,
+                "cost_usd": totals.cost,
+                "unpriced_calls": totals.unpriced_calls,
+            });
+        }
+        println!("{}", json);
+        return;
+    }
+    for (name, totals) in &periods {
+        println!(
+            "{:<11} {} calls, {}",
+            format!("{}:", name),
+            totals.calls,
+            totals
+        );
+    }
+    if let Some(budget) = budget {
+        println!("{:<11} ${:.2}", "budget:", budget);
+    }
+}
+
+fn read_file(path: &PathBuf) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn write_output(path: Option<&PathBuf>, text: &str) -> bool {
+    let result = match path {
+        Some(path) => std::fs::write(path, text),
+        None => std::io::stdout().write_all(text.as_bytes()),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to write the export: {}", e);
+        return false;
+    }
+    true
+}
+
+fn confirm(question: &str) -> bool {
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush().ok();
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn print_result(args: &Args, config: &AppConfig, result: &Result<ExecutionResult, ExecutionError>) {
+    if args.json {
+        println!("{}", agent::result_to_json(result));
+        return;
+    }
+    match result {
+        Ok(ExecutionResult::Message(msg)) => println!("Agent: {}", msg),
+        Ok(ExecutionResult::Success(changes)) => print_changes(config, changes),
+        Ok(ExecutionResult::Planned(_, changes)) => {
+            println!("Planned changes:");
+            print_changes(config, changes);
+        }
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+fn print_changes(config: &AppConfig, changes: &[PayTypeChange]) {
+    let today = Clock::System.today(config);
+    for change in changes {
+        println!("{}", change.describe(today));
+    }
+}
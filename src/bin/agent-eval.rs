@@ -0,0 +1,44 @@
+use agent::{config::load_config, eval};
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Run the golden prompts against the model and report which produce the expected calls
+#[derive(Parser)]
+#[command(name = "agent-eval")]
+struct Args {
+    /// Corpus of prompts and expected function calls
+    #[arg(default_value = "evals/golden_prompts.json")]
+    corpus: PathBuf,
+
+    /// Print the outcomes as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    let cases = match eval::load_corpus(&args.corpus) {
+        Ok(cases) => cases,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let config = load_config();
+    agent::logging::init(&config);
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let outcomes = rt.block_on(eval::run_corpus(&config, &cases));
+
+    if args.json {
+        println!("{}", serde_json::json!({ "outcomes": outcomes }));
+    } else {
+        println!("{}", eval::report(&outcomes));
+    }
+    if outcomes.iter().any(|o| !o.passed) {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,215 @@
+use agent::{
+    ExecutionOptions, ExecutionResult,
+    clock::Clock,
+    config::{AppConfig, load_config},
+    conversation_message::FunctionCall,
+};
+use chrono::NaiveDate;
+use clap::Parser;
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Model Context Protocol server publishing the EBMS time-entry tools over stdio
+#[derive(Parser)]
+#[command(name = "agent-mcp")]
+struct Args {
+    /// Connection profile to use instead of the one last logged in with
+    #[arg(short, long)]
+    profile: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let config = load_config();
+    agent::logging::init(&config);
+    let config = match &args.profile {
+        Some(name) => config.with_profile(name),
+        None => config.profile().is_some().then_some(config),
+    };
+    let Some(mut config) = config else {
+        eprintln!("No profile selected. Log in with the app or pass --profile");
+        std::process::exit(2);
+    };
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    if let Some(profile) = config.profile_mut()
+        && let Err(e) = rt.block_on(agent::load_employee_name(profile))
+    {
+        tracing::warn!("Failed to look up {}: {}", profile.employee_id, e);
+    }
+
+    // Messages are newline-delimited JSON-RPC; stdout carries nothing else
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => rt.block_on(handle_message(&config, &message)),
+            Err(e) => Some(error_response(Value::Null, -32700, &e.to_string())),
+        };
+        if let Some(response) = response {
+            writeln!(stdout, "{}", response).ok();
+            stdout.flush().ok();
+        }
+    }
+}
+
+// Notifications have no id and get no response
+async fn handle_message(config: &AppConfig, message: &Value) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let method = message["method"].as_str().unwrap_or_default();
+    let params = &message["params"];
+
+    let result = match method {
+        "initialize" => json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "ebms-agent", "version": env!("CARGO_PKG_VERSION") },
+        }),
+        "ping" => json!({}),
+        "tools/list" => json!({ "tools": tools(config) }),
+        "tools/call" => call_tool(config, params).await,
+        _ => return Some(error_response(id, -32601, "Method not found")),
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn tools(config: &AppConfig) -> Vec<Value> {
+    let mut tools = vec![json!({
+        "name": "get_time_entries",
+        "description": "List an employee's time entries and their pay codes between two dates",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "start": { "type": "string", "description": "First date (format: YYYY-MM-DD)" },
+                "end": { "type": "string", "description": "Last date, inclusive (format: YYYY-MM-DD)" },
+                "employee": {
+                    "type": "string",
+                    "description": "Name or employee ID. Omit for the logged-in user."
+                }
+            },
+            "required": ["start", "end"],
+            "additionalProperties": false
+        }
+    })];
+    // The same tools and schemas the agent's own model is given
+    tools.extend(agent::function_definitions(config).into_iter().map(|f| {
+        json!({
+            "name": f["name"],
+            "description": f["description"],
+            "inputSchema": f["parameters"],
+        })
+    }));
+    tools.push(json!({
+        "name": "undo_last_change",
+        "description": "Revert the most recent batch of pay type changes made with this profile",
+        "inputSchema": { "type": "object", "properties": {}, "additionalProperties": false }
+    }));
+    tools
+}
+
+async fn call_tool(config: &AppConfig, params: &Value) -> Value {
+    let name = params["name"].as_str().unwrap_or_default();
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+
+    let outcome = match name {
+        "get_time_entries" => get_time_entries(config, &arguments).await,
+        "undo_last_change" => agent_result(agent::undo_last(config).await),
+        name if agent::is_lookup(name) => {
+            let function_call = FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+                prompt_version: None,
+                usage: None,
+            };
+            agent::run_lookup(config, &Clock::System, &function_call)
+                .map(|result| (result.to_string(), result))
+        }
+        "set_pay_type" => {
+            let function_call = FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+                prompt_version: None,
+                usage: None,
+            };
+            agent_result(
+                agent::execute_function_call(config, &function_call, &ExecutionOptions::default())
+                    .await,
+            )
+        }
+        _ => Err(format!("Unknown tool: {}", name)),
+    };
+
+    match outcome {
+        Ok((text, structured)) => json!({
+            "content": [{ "type": "text", "text": text }],
+            "structuredContent": structured,
+            "isError": false,
+        }),
+        Err(e) => json!({
+            "content": [{ "type": "text", "text": e }],
+            "isError": true,
+        }),
+    }
+}
+
+async fn get_time_entries(
+    config: &AppConfig,
+    arguments: &Value,
+) -> Result<(String, Value), String> {
+    let date = |field: &str| {
+        let value = arguments[field]
+            .as_str()
+            .ok_or_else(|| format!("Missing '{}' field", field))?;
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|e| format!("Invalid '{}', expected YYYY-MM-DD: {}", field, e))
+    };
+    let (start, end) = (date("start")?, date("end")?);
+
+    let entries = agent::get_time_entries(config, arguments["employee"].as_str(), start, end)
+        .await
+        .map_err(|e| e.to_string())?;
+    let text = entries
+        .iter()
+        .map(|e| {
+            format!(
+                "{} {}: {}",
+                e.employee,
+                e.date.format("%a %Y-%m-%d"),
+                e.pay_code
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok((text, json!({ "entries": entries })))
+}
+
+fn agent_result(
+    result: Result<ExecutionResult, agent::ExecutionError>,
+) -> Result<(String, Value), String> {
+    let structured = agent::result_to_json(&result);
+    match result {
+        Ok(ExecutionResult::Message(msg)) => Ok((msg, structured)),
+        Ok(ExecutionResult::Success(changes)) | Ok(ExecutionResult::Planned(_, changes)) => {
+            let text = changes
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            Ok((text, structured))
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,343 @@
+use agent::{
+    ExecutionError, ExecutionOptions, ExecutionResult, audit,
+    config::{AppConfig, load_config},
+    conversation_message::ConversationMessage,
+    export,
+    progress::{ProgressEvent, ProgressSink},
+};
+use axum::{
+    Json, Router,
+    extract::{Query, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use chrono::NaiveDate;
+use clap::Parser;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Serve the agent as a local REST API
+#[derive(Parser)]
+#[command(name = "agent-server")]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8787")]
+    bind: String,
+
+    /// Bearer token clients must send in the Authorization header, at least 16 characters
+    #[arg(long, env = "AGENT_SERVER_TOKEN")]
+    token: String,
+
+    /// Conversations kept for clients; the least recently used is dropped to make room
+    #[arg(long, default_value_t = 1000)]
+    max_conversations: usize,
+}
+
+struct ServerState {
+    config: AppConfig,
+    token: String,
+    max_conversations: usize,
+    // Keyed by profile and the client's conversation id
+    conversations: Mutex<HashMap<(String, String), StoredConversation>>,
+}
+
+// When the conversation was last continued, and its messages
+type StoredConversation = (Instant, Vec<ConversationMessage>);
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+const CSV: &str = "text/csv; charset=utf-8";
+const MIN_TOKEN_LEN: usize = 16;
+// A conversation not continued for this long is forgotten
+const CONVERSATION_IDLE: Duration = Duration::from_secs(60 * 60);
+
+fn main() {
+    let args = Args::parse();
+    if args.token.trim().chars().count() < MIN_TOKEN_LEN {
+        eprintln!(
+            "The token must be at least {} characters, e.g. from `openssl rand -hex 32`",
+            MIN_TOKEN_LEN
+        );
+        std::process::exit(2);
+    }
+    let mut config = load_config();
+    agent::logging::init(&config);
+    agent::logging::add_secret(&args.token);
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    for profile in &mut config.profiles {
+        if let Err(e) = rt.block_on(agent::load_employee_name(profile)) {
+            tracing::warn!(
+                "Failed to look up {} for {}: {}",
+                profile.employee_id,
+                profile.name,
+                e
+            );
+        }
+    }
+    let state = Arc::new(ServerState {
+        config,
+        token: args.token,
+        max_conversations: args.max_conversations.max(1),
+        conversations: Mutex::new(HashMap::new()),
+    });
+
+    let app = Router::new()
+        .route("/prompt", post(prompt))
+        .route("/entries", get(entries))
+        .route("/audit", get(audit_history))
+        .route("/undo", post(undo))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state);
+
+    rt.block_on(async {
+        let listener = match tokio::net::TcpListener::bind(&args.bind).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Failed to listen on {}: {}", args.bind, e);
+                std::process::exit(1);
+            }
+        };
+        // The port chosen for "127.0.0.1:0", e.g. in tests
+        let address = listener
+            .local_addr()
+            .map_or(args.bind.clone(), |a| a.to_string());
+        eprintln!("Listening on http://{}", address);
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("Server error: {}", e);
+        }
+    });
+}
+
+async fn require_token(
+    State(state): State<Arc<ServerState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| tokens_match(token, &state.token));
+    if !authorized {
+        return error(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token").into_response();
+    }
+    next.run(request).await
+}
+
+// Looks at every byte whatever the first difference, so response times don't give the token away
+fn tokens_match(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn error(status: StatusCode, message: &str) -> ApiError {
+    (
+        status,
+        Json(serde_json::json!({ "status": "error", "error": message })),
+    )
+}
+
+fn select_profile(state: &ServerState, profile: Option<&str>) -> Result<AppConfig, ApiError> {
+    match profile {
+        Some(name) => state.config.with_profile(name).ok_or_else(|| {
+            error(
+                StatusCode::BAD_REQUEST,
+                &format!("No profile named '{}'", name),
+            )
+        }),
+        None if state.config.profile().is_some() => Ok(state.config.clone()),
+        None => Err(error(
+            StatusCode::BAD_REQUEST,
+            "No profile selected, pass one in the request",
+        )),
+    }
+}
+
+fn result_response(result: &Result<ExecutionResult, ExecutionError>) -> Response {
+    result_response_with(result, agent::result_to_json(result))
+}
+
+fn result_response_with(
+    result: &Result<ExecutionResult, ExecutionError>,
+    body: serde_json::Value,
+) -> Response {
+    let status = match result {
+        Ok(_) => StatusCode::OK,
+        Err(e) => error_status(e),
+    };
+    (status, Json(body)).into_response()
+}
+
+// The client's request was at fault for refusals and bad requests; EBMS or the model otherwise
+fn error_status(e: &ExecutionError) -> StatusCode {
+    match e {
+        ExecutionError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+        ExecutionError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+        ExecutionError::AgentError(_) | ExecutionError::EbmsError(_) => StatusCode::BAD_GATEWAY,
+    }
+}
+
+#[derive(Deserialize)]
+struct PromptRequest {
+    prompt: String,
+    profile: Option<String>,
+    /// Any id the client chooses; prompts with the same id continue one conversation
+    conversation: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+async fn prompt(
+    State(state): State<Arc<ServerState>>,
+    Json(request): Json<PromptRequest>,
+) -> Result<Response, ApiError> {
+    let config = select_profile(&state, request.profile.as_deref())?;
+    let profile_name = config.current_profile.clone().unwrap_or_default();
+    let conversation_key = request.conversation.map(|id| (profile_name.clone(), id));
+
+    let conversation = conversation_key
+        .as_ref()
+        .and_then(|key| {
+            let conversations = state.conversations.lock().unwrap();
+            conversations
+                .get(key)
+                .filter(|(used, _)| used.elapsed() < CONVERSATION_IDLE)
+                .map(|(_, messages)| messages.clone())
+        })
+        .unwrap_or_default();
+
+    // Recorded and returned with the result, so clients can show what was done
+    let events: Arc<Mutex<Vec<ProgressEvent>>> = Arc::default();
+    let options = ExecutionOptions {
+        dry_run: request.dry_run,
+        on_progress: Some({
+            let events = events.clone();
+            ProgressSink(Arc::new(move |event: &ProgressEvent| {
+                events.lock().unwrap().push(event.clone())
+            }))
+        }),
+        ..Default::default()
+    };
+    let result = agent::execute_prompt(&config, &request.prompt, &conversation, &options).await;
+
+    if let Some(key) = conversation_key
+        && let Some(window) = agent::next_conversation_window(
+            &config,
+            &options.clock,
+            &conversation,
+            &request.prompt,
+            &result,
+        )
+        .await
+    {
+        remember_conversation(&state, key, window.messages);
+    }
+    let mut body = agent::result_to_json(&result);
+    body["progress"] = serde_json::json!(*events.lock().unwrap());
+    Ok(result_response_with(&result, body))
+}
+
+fn remember_conversation(
+    state: &ServerState,
+    key: (String, String),
+    messages: Vec<ConversationMessage>,
+) {
+    let mut conversations = state.conversations.lock().unwrap();
+    conversations.retain(|_, (used, _)| used.elapsed() < CONVERSATION_IDLE);
+    if conversations.len() >= state.max_conversations
+        && !conversations.contains_key(&key)
+        && let Some(oldest) = conversations
+            .iter()
+            .min_by_key(|(_, (used, _))| *used)
+            .map(|(key, _)| key.clone())
+    {
+        conversations.remove(&oldest);
+    }
+    conversations.insert(key, (Instant::now(), messages));
+}
+
+#[derive(Deserialize)]
+struct EntriesQuery {
+    start: NaiveDate,
+    end: NaiveDate,
+    employee: Option<String>,
+    profile: Option<String>,
+    /// "json" (the default), "csv", or "ics" for the non-salary days
+    format: Option<String>,
+}
+
+async fn entries(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<EntriesQuery>,
+) -> Result<Response, ApiError> {
+    let config = select_profile(&state, query.profile.as_deref())?;
+    match agent::get_time_entries(&config, query.employee.as_deref(), query.start, query.end).await
+    {
+        Ok(entries) => match query.format.as_deref().unwrap_or("json") {
+            "json" => Ok(Json(serde_json::json!({ "entries": entries })).into_response()),
+            "csv" => {
+                Ok(([(header::CONTENT_TYPE, CSV)], export::entries_csv(&entries)).into_response())
+            }
+            "ics" => Ok((
+                [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+                export::days_off_ics(&entries, chrono::Utc::now()),
+            )
+                .into_response()),
+            other => Err(error(
+                StatusCode::BAD_REQUEST,
+                &format!("Unknown format '{}', expected json, csv or ics", other),
+            )),
+        },
+        Err(e) => Err(error(error_status(&e), &e.to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    profile: Option<String>,
+}
+
+/// The changes applied with the profile, as CSV.
+async fn audit_history(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response, ApiError> {
+    let config = select_profile(&state, query.profile.as_deref())?;
+    let profile = config.current_profile.clone().unwrap_or_default();
+    let records: Vec<_> = audit::load(&config)
+        .into_iter()
+        .filter(|r| r.profile == profile)
+        .collect();
+    Ok(([(header::CONTENT_TYPE, CSV)], export::audit_csv(&records)).into_response())
+}
+
+#[derive(Deserialize, Default)]
+struct UndoRequest {
+    profile: Option<String>,
+}
+
+async fn undo(
+    State(state): State<Arc<ServerState>>,
+    request: Option<Json<UndoRequest>>,
+) -> Result<Response, ApiError> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let config = select_profile(&state, request.profile.as_deref())?;
+    let result = agent::undo_last(&config).await;
+    Ok(result_response(&result))
+}
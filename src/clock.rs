@@ -0,0 +1,75 @@
+use crate::config::AppConfig;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+/// Where the agent gets "now" from. Relative dates like "yesterday" are resolved against it.
+#[derive(Debug, Clone, Default)]
+pub enum Clock {
+    /// The wall clock, in the company timezone if one is configured
+    #[default]
+    System,
+    /// A pinned time, for tests and replaying prompts
+    Fixed(NaiveDateTime),
+}
+
+impl Clock {
+    /// A clock pinned to midday on `date`.
+    pub fn fixed_date(date: NaiveDate) -> Self {
+        Clock::Fixed(date.and_hms_opt(12, 0, 0).unwrap())
+    }
+
+    pub fn now(&self, config: &AppConfig) -> NaiveDateTime {
+        match self {
+            Clock::Fixed(now) => *now,
+            Clock::System => {
+                let utc = chrono::Utc::now();
+                match company_timezone(config) {
+                    Some(tz) => tz.from_utc_datetime(&utc.naive_utc()).naive_local(),
+                    None => utc.with_timezone(&chrono::Local).naive_local(),
+                }
+            }
+        }
+    }
+
+    pub fn today(&self, config: &AppConfig) -> NaiveDate {
+        self.now(config).date()
+    }
+
+    /// The moment it is, e.g. to timestamp a record. A fixed clock's time is read in the
+    /// company timezone.
+    pub fn instant(&self, config: &AppConfig) -> DateTime<Local> {
+        let Clock::Fixed(now) = self else {
+            return Local::now();
+        };
+        let instant = match company_timezone(config) {
+            Some(tz) => tz
+                .from_local_datetime(now)
+                .earliest()
+                .map(|t| t.with_timezone(&Local)),
+            None => Local.from_local_datetime(now).earliest(),
+        };
+        instant.unwrap_or_else(|| Local.from_utc_datetime(now))
+    }
+}
+
+/// The company's calendar date at `instant`, e.g. the day a recorded model call was made on.
+pub fn company_date(config: &AppConfig, instant: &DateTime<Local>) -> NaiveDate {
+    match company_timezone(config) {
+        Some(tz) => instant.with_timezone(&tz).date_naive(),
+        None => instant.date_naive(),
+    }
+}
+
+/// The configured company timezone; `None` means local time.
+pub fn company_timezone(config: &AppConfig) -> Option<chrono_tz::Tz> {
+    let name = config.company_timezone.trim();
+    if name.is_empty() {
+        return None;
+    }
+    match name.parse() {
+        Ok(tz) => Some(tz),
+        Err(_) => {
+            tracing::warn!("Unknown company timezone '{}', using local time", name);
+            None
+        }
+    }
+}
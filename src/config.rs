@@ -1,34 +1,496 @@
+use crate::{
+    Employee, PayType, api::format_pay_code, holidays::Holiday, import::ImportRule, logging,
+    usage::ModelRate,
+};
+use chrono::Weekday;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+use strum::IntoEnumIterator;
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
 pub struct AppConfig {
     pub gpt_api_key: String,
+    /// Chat completions endpoint; OpenAI's if empty
+    pub gpt_api_url: String,
+    /// Model to ask, e.g. "gpt-4o"; gpt-4 if empty
+    pub gpt_model: String,
+    /// Seconds to wait for the model to answer; 120 if 0
+    pub gpt_timeout_secs: u64,
+    /// How the model asks for functions to be run; set for models without function calling
+    pub tool_calling: ToolCalling,
+    /// When prompts are read by the offline rules instead of the model
+    pub offline_rules: OfflineRules,
+    pub profiles: Vec<ConnectionProfile>,
+    pub current_profile: Option<String>,
+    /// Where applied changes are logged; `audit.jsonl` in the config directory if unset
+    pub audit_log_path: Option<std::path::PathBuf>,
+    /// IANA timezone name, e.g. "America/Chicago", that decides what "today" is; local time if empty
+    pub company_timezone: String,
+    /// Day the week begins on, e.g. "Monday", for "this week" and "next friday"; Sunday if empty
+    pub week_start: String,
+    /// Days the company works, e.g. ["Mon", "Tue"], for date ranges; Monday to Friday if empty
+    pub work_days: Vec<String>,
+    /// Work schedules that differ from `work_days`, by EBMS employee ID
+    pub employee_work_days: BTreeMap<String, Vec<String>>,
+    /// Days off for everyone, skipped when a date range is expanded
+    pub company_holidays: Vec<Holiday>,
+    /// ICS calendar of further company holidays; `holidays.ics` in the config directory if unset
+    pub holiday_calendar_path: Option<std::path::PathBuf>,
+    /// Most days one start/end range may span, so a mistaken range can't touch a whole year;
+    /// 31 if 0
+    pub max_range_days: u32,
+    /// How calendar events map to pay types when importing; see `import::default_import_rules`
+    pub import_rules: Vec<ImportRule>,
+    /// Hours in a full work day; CSV import rows with fewer hours are refused, since EBMS
+    /// changes whole days. 8 if 0
+    pub full_day_hours: f64,
+    /// Template for the model's instructions; `system_prompt.txt` in the config directory if
+    /// unset, else the built-in one. See the `prompt` module for its variables
+    pub system_prompt_path: Option<std::path::PathBuf>,
+    /// Most tokens of earlier conversation to resend with each prompt; 2000 if 0
+    pub context_token_budget: usize,
+    /// What happens to the messages that don't fit in `context_token_budget`
+    pub context_policy: ContextPolicy,
+    /// US dollars per million tokens by model name, for models `usage::default_rates` doesn't
+    /// price or prices differently
+    pub model_rates: BTreeMap<String, ModelRate>,
+    /// Model calls are refused once this month's cost reaches it, in US dollars; no limit if 0
+    pub monthly_budget_usd: f64,
+    /// Least severe messages written to the log file, e.g. "debug"; info if empty.
+    /// The `AGENT_LOG` environment variable overrides it
+    pub log_level: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCalling {
+    /// The API's function calling
+    #[default]
+    Functions,
+    /// A JSON plan in the reply, enforced with a `response_format` JSON schema
+    JsonSchema,
+    /// A JSON plan in the reply, asked for in the prompt only, for APIs without `response_format`
+    JsonPrompt,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OfflineRules {
+    /// When the model can't be reached, e.g. no API key or no network
+    #[default]
+    Fallback,
+    /// For every prompt; the model is never asked
+    Always,
+    /// Never; a prompt fails when the model can't be reached
+    Never,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextPolicy {
+    /// Drop the oldest messages
+    #[default]
+    Trim,
+    /// Ask the model to summarize the oldest messages, costing one more call
+    Summarize,
+}
+
+/// A named EBMS connection, e.g. a test company and production, or one per employee.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConnectionProfile {
+    pub name: String,
     pub ebms_url: String,
     pub ebms_username: String,
     pub ebms_password: String,
     pub employee_id: String,
+    /// The logged-in employee's name as EBMS has it, for the model and change summaries.
+    /// Filled in once per session by `load_employee_name`; the employee ID stands in if empty
+    pub employee_name: String,
+    pub production: bool,
+    /// Seconds to wait for EBMS to answer; 30 if 0
+    pub timeout_secs: u64,
+    /// The company's PAY_LEVEL code for a pay type, e.g. {"Vacation": "VAC"}, where it
+    /// differs from the built-in one
+    pub pay_codes: BTreeMap<String, String>,
+}
+
+impl ConnectionProfile {
+    /// The logged-in employee, named as far as the profile knows.
+    pub fn employee(&self) -> Employee {
+        let id = self.employee_id.trim().to_string();
+        Employee {
+            name: match self.employee_name.trim() {
+                "" => id.clone(),
+                name => name.to_string(),
+            },
+            id,
+        }
+    }
+
+    /// The PAY_LEVEL code this company uses for the pay type.
+    pub fn pay_code(&self, pay_type: &PayType) -> String {
+        self.pay_codes
+            .get(&pay_type.to_string())
+            .map(|code| code.trim())
+            .filter(|code| !code.is_empty())
+            .unwrap_or(format_pay_code(pay_type))
+            .to_string()
+    }
+
+    /// The pay type a PAY_LEVEL code maps to, if it's one the agent knows about.
+    pub fn pay_type_for_code(&self, pay_code: &str) -> Option<PayType> {
+        PayType::iter().find(|pt| self.pay_code(pt).eq_ignore_ascii_case(pay_code.trim()))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(match self.timeout_secs {
+            0 => 30,
+            secs => secs,
+        })
+    }
+
+    /// Problems that would stop the profile from working, e.g. a URL that isn't one.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.name.trim().is_empty() {
+            problems.push("The profile needs a name".to_string());
+        }
+        let url = self.ebms_url.trim();
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            problems.push(format!("EBMS URL '{}' should start with https://", url));
+        }
+        if self.employee_id.trim().is_empty() {
+            problems.push("Employee ID is required".to_string());
+        }
+        let mut codes: Vec<(String, String)> = Vec::new();
+        for pay_type in PayType::iter() {
+            let code = self.pay_code(&pay_type);
+            match codes.iter().find(|(c, _)| c.eq_ignore_ascii_case(&code)) {
+                Some((_, other)) => problems.push(format!(
+                    "{} and {} both use pay code {}",
+                    other, pay_type, code
+                )),
+                None => codes.push((code, pay_type.to_string())),
+            }
+        }
+        for name in self.pay_codes.keys() {
+            if !PayType::iter().any(|pt| pt.to_string() == *name) {
+                problems.push(format!("'{}' isn't a pay type", name));
+            }
+        }
+        problems
+    }
 }
 
 impl AppConfig {
     pub fn empty() -> Self {
         AppConfig {
             gpt_api_key: String::new(),
-            ebms_url: String::new(),
-            ebms_username: String::new(),
-            ebms_password: String::new(),
-            employee_id: String::new(),
+            gpt_api_url: String::new(),
+            gpt_model: String::new(),
+            gpt_timeout_secs: 0,
+            tool_calling: ToolCalling::Functions,
+            offline_rules: OfflineRules::Fallback,
+            profiles: Vec::new(),
+            current_profile: None,
+            audit_log_path: None,
+            company_timezone: String::new(),
+            week_start: String::new(),
+            work_days: Vec::new(),
+            employee_work_days: BTreeMap::new(),
+            company_holidays: Vec::new(),
+            holiday_calendar_path: None,
+            max_range_days: 0,
+            import_rules: Vec::new(),
+            full_day_hours: 0.0,
+            system_prompt_path: None,
+            context_token_budget: 0,
+            context_policy: ContextPolicy::Trim,
+            model_rates: BTreeMap::new(),
+            monthly_budget_usd: 0.0,
+            log_level: String::new(),
         }
     }
+
+    pub fn gpt_model(&self) -> &str {
+        match self.gpt_model.trim() {
+            "" => DEFAULT_GPT_MODEL,
+            model => model,
+        }
+    }
+
+    pub fn context_token_budget(&self) -> usize {
+        match self.context_token_budget {
+            0 => 2000,
+            budget => budget,
+        }
+    }
+
+    pub fn max_range_days(&self) -> u32 {
+        match self.max_range_days {
+            0 => 31,
+            days => days,
+        }
+    }
+
+    pub fn full_day_hours(&self) -> f64 {
+        if self.full_day_hours > 0.0 {
+            self.full_day_hours
+        } else {
+            8.0
+        }
+    }
+
+    pub fn log_level(&self) -> &str {
+        match self.log_level.trim() {
+            "" => "info",
+            level => level,
+        }
+    }
+
+    pub fn gpt_timeout(&self) -> Duration {
+        Duration::from_secs(match self.gpt_timeout_secs {
+            0 => 120,
+            secs => secs,
+        })
+    }
+
+    /// Problems with the settings and the current profile, worded for the user.
+    /// Empty if the config is fine to save.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let timezone = self.company_timezone.trim();
+        if !timezone.is_empty() && timezone.parse::<chrono_tz::Tz>().is_err() {
+            problems.push(format!(
+                "Unknown timezone '{}', expected a name like America/Chicago",
+                timezone
+            ));
+        }
+        let week_start = self.week_start.trim();
+        if !week_start.is_empty() && week_start.parse::<Weekday>().is_err() {
+            problems.push(format!("Week start '{}' isn't a day", week_start));
+        }
+        let schedules = std::iter::once((None, &self.work_days)).chain(
+            self.employee_work_days
+                .iter()
+                .map(|(id, days)| (Some(id), days)),
+        );
+        for (employee, days) in schedules {
+            for day in days {
+                if day.trim().parse::<Weekday>().is_err() {
+                    problems.push(match employee {
+                        Some(id) => format!("Work day '{}' for {} isn't a day", day, id),
+                        None => format!("Work day '{}' isn't a day", day),
+                    });
+                }
+            }
+        }
+        for rule in &self.import_rules {
+            if rule.pattern.trim().is_empty() {
+                problems.push(format!(
+                    "An import rule for {} has no pattern",
+                    rule.pay_type
+                ));
+            }
+        }
+        for (model, rate) in &self.model_rates {
+            if rate.prompt_per_million < 0.0 || rate.completion_per_million < 0.0 {
+                problems.push(format!("The rates for {} can't be negative", model));
+            }
+        }
+        if self.monthly_budget_usd < 0.0 {
+            problems.push("The monthly budget can't be negative".to_string());
+        }
+        if self.log_level().parse::<tracing::Level>().is_err() {
+            problems.push(format!(
+                "Log level '{}' should be one of error, warn, info, debug or trace",
+                self.log_level
+            ));
+        }
+        let files = [
+            ("Holiday calendar", &self.holiday_calendar_path),
+            ("System prompt", &self.system_prompt_path),
+        ];
+        for (label, path) in files {
+            if let Some(path) = path
+                && !path.is_file()
+            {
+                problems.push(format!("{} file {} doesn't exist", label, path.display()));
+            }
+        }
+        if let Some(profile) = self.profile() {
+            problems.extend(profile.validate());
+        }
+        problems
+    }
+
+    /// The configured first day of the week, Sunday if unset or not a day name.
+    pub fn week_start(&self) -> Weekday {
+        if self.week_start.trim().is_empty() {
+            return Weekday::Sun;
+        }
+        self.week_start.trim().parse().unwrap_or_else(|_| {
+            tracing::warn!("Unknown week start '{}', using Sunday", self.week_start);
+            Weekday::Sun
+        })
+    }
+
+    /// The days the employee works, or the company's work days if they have no schedule of their own.
+    pub fn work_days(&self, employee_id: Option<&str>) -> Vec<Weekday> {
+        let names = employee_id
+            .and_then(|id| self.employee_work_days.get(id))
+            .unwrap_or(&self.work_days);
+        let days: Vec<Weekday> = names
+            .iter()
+            .filter_map(|name| match name.trim().parse() {
+                Ok(day) => Some(day),
+                Err(_) => {
+                    tracing::warn!("Unknown work day '{}', ignoring it", name);
+                    None
+                }
+            })
+            .collect();
+        if days.is_empty() {
+            vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ]
+        } else {
+            days
+        }
+    }
+
+    /// The profile the user is logged in with, if any.
+    pub fn profile(&self) -> Option<&ConnectionProfile> {
+        let name = self.current_profile.as_ref()?;
+        self.find_profile(name)
+    }
+
+    pub fn profile_mut(&mut self) -> Option<&mut ConnectionProfile> {
+        let name = self.current_profile.clone()?;
+        self.profiles.iter_mut().find(|p| p.name == name)
+    }
+
+    pub fn find_profile(&self, name: &str) -> Option<&ConnectionProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Adds the profile, replacing any existing profile with the same name.
+    pub fn upsert_profile(&mut self, mut profile: ConnectionProfile) {
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => {
+                // The name was looked up for the old employee ID
+                if existing.employee_id.trim() != profile.employee_id.trim() {
+                    profile.employee_name.clear();
+                }
+                *existing = profile
+            }
+            None => self.profiles.push(profile),
+        }
+    }
+
+    pub fn remove_profile(&mut self, name: &str) {
+        self.profiles.retain(|p| p.name != name);
+        if self.current_profile.as_deref() == Some(name) {
+            self.current_profile = None;
+        }
+    }
+
+    /// Returns a copy of this config switched to the named profile.
+    pub fn with_profile(&self, name: &str) -> Option<AppConfig> {
+        self.find_profile(name)?;
+        let mut config = self.clone();
+        config.current_profile = Some(name.to_string());
+        Some(config)
+    }
 }
 
+/// Environment variable naming a config file to use instead of the one in the user's config
+/// directory, e.g. to run the MCP or REST server against a separate company.
+pub const CONFIG_ENV: &str = "AGENT_CONFIG";
+
 const EBMS_API_AGENT: &str = "ebms_api_agent";
+const DEFAULT_GPT_MODEL: &str = "gpt-4";
+const DEFAULT_PROFILE_NAME: &str = "Default";
+
+// The config file used to hold a single connection at the top level
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct LegacyConfig {
+    ebms_url: String,
+    ebms_username: String,
+    ebms_password: String,
+    employee_id: String,
+}
+
+fn config_path() -> Option<std::path::PathBuf> {
+    match std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        Some(path) => Some(path.into()),
+        None => confy::get_configuration_file_path(EBMS_API_AGENT, None).ok(),
+    }
+}
 
 pub fn load_config() -> AppConfig {
-    confy::load(EBMS_API_AGENT, None).unwrap_or(AppConfig::empty())
+    let Some(path) = config_path() else {
+        return AppConfig::empty();
+    };
+    let mut config: AppConfig = match confy::load_path(&path) {
+        Ok(config) => config,
+        // The next save would overwrite the file, so keep a copy for the user to fix
+        Err(e) => {
+            let backup = path.with_extension("toml.bak");
+            let kept = match std::fs::copy(&path, &backup) {
+                Ok(_) => format!("it was copied to {}", backup.display()),
+                Err(copy_error) => format!("copying it failed: {}", copy_error),
+            };
+            let message = format!(
+                "Failed to read the config file {}: {}; starting with an empty config, {}",
+                path.display(),
+                e,
+                kept
+            );
+            // Logging isn't set up until the config is loaded
+            eprintln!("{}", message);
+            tracing::error!("{}", message);
+            AppConfig::empty()
+        }
+    };
+    if config.profiles.is_empty()
+        && let Ok(legacy) = confy::load_path::<LegacyConfig>(&path)
+        && !legacy.ebms_username.is_empty()
+    {
+        config.profiles.push(ConnectionProfile {
+            name: DEFAULT_PROFILE_NAME.to_string(),
+            ebms_url: legacy.ebms_url,
+            ebms_username: legacy.ebms_username,
+            ebms_password: legacy.ebms_password,
+            employee_id: legacy.employee_id,
+            ..Default::default()
+        });
+        config.current_profile = Some(DEFAULT_PROFILE_NAME.to_string());
+    }
+    config
+}
+
+/// The directory the config file lives in, where the app keeps its other files too.
+pub fn config_dir() -> Option<std::path::PathBuf> {
+    let path = config_path()?;
+    path.parent().map(|dir| dir.to_path_buf())
 }
 
 pub fn save_config(config: &AppConfig) {
-    if let Err(e) = confy::store(EBMS_API_AGENT, None, config) {
-        eprintln!("Failed to save config: {}", e);
+    // A new password or key must be masked in the logs from now on
+    logging::register_secrets(config);
+    let Some(path) = config_path() else {
+        tracing::error!("Failed to save config: no config directory");
+        return;
+    };
+    if let Err(e) = confy::store_path(path, config) {
+        tracing::error!("Failed to save config: {}", e);
     }
 }
@@ -0,0 +1,157 @@
+//! Keeps the conversation resent with each prompt within a token budget, so a long
+//! clarification chat doesn't outgrow the model's context window or run up cost.
+
+use crate::{
+    AgentResponse,
+    clock::Clock,
+    config::{AppConfig, ContextPolicy},
+    conversation_message::{ConversationMessage, Role},
+    gpt,
+};
+
+/// Starts the message that stands in for summarized turns.
+pub const SUMMARY_PREFIX: &str = "Summary of the earlier conversation: ";
+
+// Each message costs a few tokens for its role and separators
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// A rough token count, at about four characters a token. Good enough for budgeting;
+/// the model's own tokenizer isn't available here.
+pub fn estimate_tokens(message: &ConversationMessage) -> usize {
+    let mut chars = message.content.chars().count();
+    if let Some(call) = &message.function_call {
+        chars += call.name.len() + call.arguments.chars().count();
+    }
+    if let Some(name) = &message.name {
+        chars += name.len();
+    }
+    MESSAGE_OVERHEAD + chars.div_ceil(4)
+}
+
+pub fn total_tokens(messages: &[ConversationMessage]) -> usize {
+    messages.iter().map(estimate_tokens).sum()
+}
+
+/// The conversation as it will be resent.
+#[derive(Clone)]
+pub struct ContextWindow {
+    pub messages: Vec<ConversationMessage>,
+    /// How many earlier messages were dropped or folded into a summary
+    pub removed: usize,
+    pub summarized: bool,
+}
+
+impl ContextWindow {
+    pub fn tokens(&self) -> usize {
+        total_tokens(&self.messages)
+    }
+}
+
+/// Drops the oldest messages until the rest fit in `budget` tokens. The newest message is
+/// always kept, and a function result is never kept without the call it answers.
+pub fn trim(conversation: &[ConversationMessage], budget: usize) -> ContextWindow {
+    let keep_from = window_start(conversation, budget);
+    ContextWindow {
+        messages: conversation[keep_from..].to_vec(),
+        removed: keep_from,
+        summarized: false,
+    }
+}
+
+/// Applies the configured policy. With [`ContextPolicy::Summarize`] the messages that don't
+/// fit are replaced by a summary the model writes; if that fails they're dropped instead.
+/// `clock` decides which month's budget the summary counts against.
+pub async fn fit(
+    config: &AppConfig,
+    clock: &Clock,
+    conversation: &[ConversationMessage],
+) -> ContextWindow {
+    let budget = config.context_token_budget();
+    let trimmed = trim(conversation, budget);
+    if trimmed.removed == 0 || config.context_policy != ContextPolicy::Summarize {
+        return trimmed;
+    }
+
+    let older = &conversation[..trimmed.removed];
+    match summarize(config, clock, older).await {
+        Ok(summary) => {
+            let mut messages = vec![ConversationMessage::new_content(
+                Role::System,
+                format!("{}{}", SUMMARY_PREFIX, summary),
+            )];
+            // Leave room for the summary itself
+            let room = budget.saturating_sub(estimate_tokens(&messages[0]));
+            let recent = trim(&trimmed.messages, room);
+            messages.extend(recent.messages);
+            ContextWindow {
+                messages,
+                removed: trimmed.removed + recent.removed,
+                summarized: true,
+            }
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Failed to summarize the conversation, dropping older messages: {}",
+                e
+            );
+            trimmed
+        }
+    }
+}
+
+fn window_start(conversation: &[ConversationMessage], budget: usize) -> usize {
+    let mut start = conversation.len();
+    let mut tokens = 0;
+    for (i, message) in conversation.iter().enumerate().rev() {
+        tokens += estimate_tokens(message);
+        if tokens > budget && start < conversation.len() {
+            break;
+        }
+        start = i;
+    }
+    while start < conversation.len().saturating_sub(1)
+        && matches!(conversation[start].role, Role::Function)
+    {
+        start += 1;
+    }
+    start
+}
+
+async fn summarize(
+    config: &AppConfig,
+    clock: &Clock,
+    messages: &[ConversationMessage],
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let transcript: Vec<String> = messages
+        .iter()
+        .map(|m| {
+            let speaker = match m.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+                Role::System => "Note",
+                Role::Function => "Lookup",
+            };
+            match &m.function_call {
+                Some(call) if m.content.is_empty() => {
+                    format!("{}: called {}({})", speaker, call.name, call.arguments)
+                }
+                _ => format!("{}: {}", speaker, m.content),
+            }
+        })
+        .collect();
+    let request = ConversationMessage::new_content(Role::User, transcript.join("\n"));
+    match gpt::call_gpt(
+        config,
+        clock,
+        "Summarize this conversation about setting pay types in two or three sentences. \
+         Keep every date, employee and pay type that was mentioned or changed. \
+         Reply with the summary only and don't call any functions.",
+        &[request],
+    )
+    .await?
+    .0
+    {
+        AgentResponse::Message(summary) => Ok(summary.trim().to_string()),
+        AgentResponse::FunctionCall(_) => Err("The model called a function instead".into()),
+    }
+}
@@ -1,10 +1,13 @@
-use serde::Deserialize;
+use crate::usage::Usage;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone)]
 pub enum Role {
     User,
     System,
     Assistant,
+    /// The result of a function the agent ran for the model, e.g. resolved dates
+    Function,
 }
 
 #[derive(Clone)]
@@ -12,6 +15,8 @@ pub struct ConversationMessage {
     pub role: Role,
     pub content: String,
     pub function_call: Option<FunctionCall>,
+    /// The function a [`Role::Function`] message answers
+    pub name: Option<String>,
 }
 
 impl ConversationMessage {
@@ -20,6 +25,7 @@ impl ConversationMessage {
             role,
             content: content.to_string(),
             function_call: None,
+            name: None,
         }
     }
     pub fn new_function_call(function_call: FunctionCall, content: String) -> Self {
@@ -27,12 +33,28 @@ impl ConversationMessage {
             role: Role::Assistant,
             content: content.to_string(),
             function_call: Some(function_call),
+            name: None,
+        }
+    }
+    pub fn new_function_result(name: &str, content: String) -> Self {
+        Self {
+            role: Role::Function,
+            content,
+            function_call: None,
+            name: Some(name.to_string()),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FunctionCall {
     pub name: String,
     pub arguments: String,
+    /// Version of the system prompt the model was given when it made this call; `None` for
+    /// calls that didn't come from the agent's model, e.g. imports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_version: Option<String>,
+    /// Tokens the model used to arrive at this call, including any lookups along the way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
 }
@@ -0,0 +1,433 @@
+//! Turns date expressions like "next friday", "the week after thanksgiving" or
+//! "mondays and wednesdays in june" into concrete dates, so the model doesn't have to.
+
+use crate::holidays::HolidayCalendar;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+const MONTHS: [(&str, u32); 12] = [
+    ("january", 1),
+    ("february", 2),
+    ("march", 3),
+    ("april", 4),
+    ("may", 5),
+    ("june", 6),
+    ("july", 7),
+    ("august", 8),
+    ("september", 9),
+    ("october", 10),
+    ("november", 11),
+    ("december", 12),
+];
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("monday", Weekday::Mon),
+    ("tuesday", Weekday::Tue),
+    ("wednesday", Weekday::Wed),
+    ("thursday", Weekday::Thu),
+    ("friday", Weekday::Fri),
+    ("saturday", Weekday::Sat),
+    ("sunday", Weekday::Sun),
+];
+
+/// What relative expressions are resolved against.
+#[derive(Debug, Clone)]
+pub struct DateContext {
+    pub today: NaiveDate,
+    pub week_start: Weekday,
+    /// Company holidays, found by name before the common US ones
+    pub holidays: HolidayCalendar,
+}
+
+/// Every date the expression covers, sorted and without duplicates.
+pub fn resolve(expression: &str, context: &DateContext) -> Result<Vec<NaiveDate>, String> {
+    let normalized = normalize(expression);
+    if normalized.is_empty() {
+        return Err("The date expression is empty".to_string());
+    }
+    let mut dates = resolve_normalized(&normalized, context)?;
+    dates.sort();
+    dates.dedup();
+    Ok(dates)
+}
+
+fn resolve_normalized(expression: &str, context: &DateContext) -> Result<Vec<NaiveDate>, String> {
+    if let Some(dates) = weekday_set(expression, context)? {
+        return Ok(dates);
+    }
+
+    let parts: Vec<&str> = expression
+        .split([',', ';', '&'])
+        .flat_map(|part| part.split(" and "))
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.len() > 1 {
+        let mut dates = Vec::new();
+        for part in parts {
+            dates.extend(resolve_normalized(part, context)?);
+        }
+        return Ok(dates);
+    }
+
+    for separator in [" to ", " through ", " thru ", " until ", " till ", " - "] {
+        if let Some((from, to)) = expression.split_once(separator) {
+            let (start, _) = span(from, context)?;
+            let (_, end) = span(to, context)?;
+            if end < start {
+                return Err(format!(
+                    "'{}' ends before it starts ({} to {})",
+                    expression, start, end
+                ));
+            }
+            return Ok(days_between(start, end));
+        }
+    }
+
+    let (start, end) = span(expression, context)?;
+    Ok(days_between(start, end))
+}
+
+// Lowercases, drops punctuation and ordinal suffixes, and collapses whitespace
+fn normalize(expression: &str) -> String {
+    let cleaned: String = expression
+        .to_lowercase()
+        .replace("'s", "")
+        .replace('\u{2013}', " - ")
+        .chars()
+        .map(|c| match c {
+            '.' | '!' | '?' | '"' | '\'' => ' ',
+            c => c,
+        })
+        .collect();
+    cleaned
+        .split_whitespace()
+        .map(strip_ordinal)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn strip_ordinal(word: &str) -> &str {
+    for suffix in ["st", "nd", "rd", "th"] {
+        if let Some(number) = word.strip_suffix(suffix)
+            && !number.is_empty()
+            && number.chars().all(|c| c.is_ascii_digit())
+        {
+            return number;
+        }
+    }
+    word
+}
+
+fn days_between(start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+    start.iter_days().take_while(|d| *d <= end).collect()
+}
+
+// "mondays and wednesdays in june", "every friday next month", "tuesdays this week"
+fn weekday_set(expression: &str, context: &DateContext) -> Result<Option<Vec<NaiveDate>>, String> {
+    let words: Vec<&str> = expression.split_whitespace().collect();
+    let mut weekdays = Vec::new();
+    let mut index = 0;
+    while index < words.len() {
+        match words[index] {
+            "every" | "all" | "each" | "and" | "," => {}
+            word => match weekday(word.trim_end_matches(',')) {
+                Some(day) => weekdays.push(day),
+                None => break,
+            },
+        }
+        index += 1;
+    }
+    // A bare weekday list is handled as separate dates, not a set
+    let plural = words[..index].iter().any(|w| w.ends_with('s'))
+        || words
+            .first()
+            .is_some_and(|w| matches!(*w, "every" | "all" | "each"));
+    if weekdays.is_empty() || index == words.len() || !plural {
+        return Ok(None);
+    }
+
+    let mut rest = &words[index..];
+    if let Some(first) = rest.first()
+        && matches!(*first, "in" | "of" | "during" | "for")
+    {
+        rest = &rest[1..];
+    }
+    let (start, end) = span(&rest.join(" "), context)?;
+    Ok(Some(
+        days_between(start, end)
+            .into_iter()
+            .filter(|d| weekdays.contains(&d.weekday()))
+            .collect(),
+    ))
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    let word = word.strip_suffix('s').unwrap_or(word);
+    WEEKDAYS
+        .iter()
+        .find(|(name, _)| *name == word || (word.len() >= 3 && name.starts_with(word)))
+        .map(|(_, day)| *day)
+}
+
+fn month(word: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .find(|(name, _)| *name == word || (word.len() >= 3 && name.starts_with(word)))
+        .map(|(_, month)| *month)
+}
+
+fn year(word: &str) -> Option<i32> {
+    if word.len() == 4 {
+        word.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// The first day of the week containing `date`.
+pub fn week_start_of(date: NaiveDate, week_start: Weekday) -> NaiveDate {
+    let offset =
+        (date.weekday().num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7;
+    date - Duration::days(offset as i64)
+}
+
+fn week_of(date: NaiveDate, context: &DateContext) -> (NaiveDate, NaiveDate) {
+    let start = week_start_of(date, context.week_start);
+    (start, start + Duration::days(6))
+}
+
+fn month_of(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((start, next - Duration::days(1)))
+}
+
+// The first and last day of a single period or date
+fn span(expression: &str, context: &DateContext) -> Result<(NaiveDate, NaiveDate), String> {
+    let expression = expression.trim();
+    let expression = ["from ", "on ", "the ", "for "]
+        .iter()
+        .fold(expression, |e, prefix| e.strip_prefix(prefix).unwrap_or(e));
+    let today = context.today;
+
+    match expression {
+        "this week" => return Ok(week_of(today, context)),
+        "next week" => return Ok(week_of(today + Duration::days(7), context)),
+        "last week" => return Ok(week_of(today - Duration::days(7), context)),
+        "week after next" => return Ok(week_of(today + Duration::days(14), context)),
+        "this month" | "next month" | "last month" => {
+            let offset = match expression {
+                "next month" => 1,
+                "last month" => -1,
+                _ => 0,
+            };
+            let months = today.year() * 12 + today.month0() as i32 + offset;
+            return month_of(months.div_euclid(12), months.rem_euclid(12) as u32 + 1)
+                .ok_or_else(|| format!("Can't work out '{}'", expression));
+        }
+        _ => {}
+    }
+
+    for (prefix, shift) in [("week of ", 0), ("week after ", 7), ("week before ", -7)] {
+        if let Some(rest) = expression.strip_prefix(prefix) {
+            let (date, _) = span(rest, context)?;
+            return Ok(week_of(date + Duration::days(shift), context));
+        }
+    }
+    for (prefix, shift) in [("day after ", 1), ("day before ", -1)] {
+        if let Some(rest) = expression.strip_prefix(prefix) {
+            let (start, end) = span(rest, context)?;
+            let date = if shift > 0 { end } else { start } + Duration::days(shift);
+            return Ok((date, date));
+        }
+    }
+
+    // "june" or "june 2025" is the whole month
+    let words: Vec<&str> = expression.split_whitespace().collect();
+    if let Some(month) = words.first().and_then(|w| month(w)) {
+        let year = match &words[1..] {
+            [] => Some(today.year()),
+            [year_word] => year(year_word),
+            _ => None,
+        };
+        if let Some(year) = year
+            && let Some(range) = month_of(year, month)
+        {
+            return Ok(range);
+        }
+    }
+
+    let date = point(expression, context)?;
+    Ok((date, date))
+}
+
+fn point(expression: &str, context: &DateContext) -> Result<NaiveDate, String> {
+    let today = context.today;
+    match expression {
+        "today" | "now" => return Ok(today),
+        "yesterday" => return Ok(today - Duration::days(1)),
+        "tomorrow" => return Ok(today + Duration::days(1)),
+        "day after tomorrow" => return Ok(today + Duration::days(2)),
+        "day before yesterday" => return Ok(today - Duration::days(2)),
+        _ => {}
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(expression, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    let words: Vec<&str> = expression.split_whitespace().collect();
+    if let Some(date) = weekday_point(&words, context) {
+        return Ok(date);
+    }
+    if let Some(date) = calendar_date(&words, today) {
+        return Ok(date);
+    }
+
+    let (name, year) = match words.last().and_then(|w| year(w)) {
+        Some(year) => (words[..words.len() - 1].join(" "), year),
+        None => (words.join(" "), today.year()),
+    };
+    if let Some(date) = company_holiday(&name, year, context).or_else(|| holiday(&name, year)) {
+        return Ok(date);
+    }
+
+    Err(format!("I don't understand the date '{}'", expression))
+}
+
+// "friday", "this friday", "next friday", "last friday", "coming friday"
+fn weekday_point(words: &[&str], context: &DateContext) -> Option<NaiveDate> {
+    let (modifier, day) = match words {
+        [day] => ("this", weekday(day)?),
+        [modifier, day] => (*modifier, weekday(day)?),
+        _ => return None,
+    };
+    let today = context.today;
+    let in_week =
+        |week_start: NaiveDate| week_start.iter_days().take(7).find(|d| d.weekday() == day);
+    match modifier {
+        "this" => in_week(week_start_of(today, context.week_start)),
+        "next" => in_week(week_start_of(today + Duration::days(7), context.week_start)),
+        "last" | "past" | "previous" => (1..=7)
+            .map(|n| today - Duration::days(n))
+            .find(|d| d.weekday() == day),
+        "coming" | "upcoming" => (1..=7)
+            .map(|n| today + Duration::days(n))
+            .find(|d| d.weekday() == day),
+        _ => None,
+    }
+}
+
+// "june 3", "june 3 2025", "3 june", "6/3", "6/3/2025"
+fn calendar_date(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    let day = |word: &str| word.parse::<u32>().ok();
+    match words {
+        [month_word, day_word] => {
+            let (month, day) = match (month(month_word), month(day_word)) {
+                (Some(month), None) => (month, day(day_word)?),
+                (None, Some(month)) => (month, day(month_word)?),
+                _ => return None,
+            };
+            NaiveDate::from_ymd_opt(today.year(), month, day)
+        }
+        [first, second, year_word] => {
+            let year = year(year_word)?;
+            let (month, day) = match (month(first), month(second)) {
+                (Some(month), None) => (month, day(second)?),
+                (None, Some(month)) => (month, day(first)?),
+                _ => return None,
+            };
+            NaiveDate::from_ymd_opt(year, month, day)
+        }
+        [numeric] => {
+            let parts: Vec<&str> = numeric.split('/').collect();
+            match parts.as_slice() {
+                [month, day_part] => {
+                    NaiveDate::from_ymd_opt(today.year(), month.parse().ok()?, day(day_part)?)
+                }
+                [month, day_part, year] => {
+                    let year: i32 = year.parse().ok()?;
+                    let year = if year < 100 { 2000 + year } else { year };
+                    NaiveDate::from_ymd_opt(year, month.parse().ok()?, day(day_part)?)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn nth_weekday(year: i32, month: u32, day: Weekday, n: u32) -> Option<NaiveDate> {
+    NaiveDate::from_weekday_of_month_opt(year, month, day, n as u8)
+}
+
+fn last_weekday(year: i32, month: u32, day: Weekday) -> Option<NaiveDate> {
+    let (_, end) = month_of(year, month)?;
+    (0..7)
+        .map(|n| end - Duration::days(n))
+        .find(|d| d.weekday() == day)
+}
+
+// Anonymous Gregorian algorithm
+fn easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+/// A holiday from the company calendar by name, e.g. "founders day". The company may
+/// observe a common holiday on another day, so its calendar is asked first.
+fn company_holiday(name: &str, year: i32, context: &DateContext) -> Option<NaiveDate> {
+    let name = name.trim_end_matches(" day");
+    context
+        .holidays
+        .in_year(year)
+        .into_iter()
+        .find(|h| normalize(&h.name).trim_end_matches(" day") == name)
+        .map(|h| h.date)
+}
+
+/// Common US holidays by name, e.g. "thanksgiving" or "memorial day".
+pub fn holiday(name: &str, year: i32) -> Option<NaiveDate> {
+    let name = name.trim().trim_end_matches(" day").trim();
+    let fixed = |month, day| NaiveDate::from_ymd_opt(year, month, day);
+    match name {
+        "new year" | "new years" => fixed(1, 1),
+        "mlk" | "martin luther king" | "martin luther king jr" => {
+            nth_weekday(year, 1, Weekday::Mon, 3)
+        }
+        "presidents" | "president" | "washington birthday" => nth_weekday(year, 2, Weekday::Mon, 3),
+        "good friday" => easter(year).map(|d| d - Duration::days(2)),
+        "easter" | "easter sunday" => easter(year),
+        "easter monday" => easter(year).map(|d| d + Duration::days(1)),
+        "memorial" => last_weekday(year, 5, Weekday::Mon),
+        "juneteenth" => fixed(6, 19),
+        "independence" | "fourth of july" | "4 of july" | "july 4" => fixed(7, 4),
+        "labor" | "labour" => nth_weekday(year, 9, Weekday::Mon, 1),
+        "columbus" | "indigenous peoples" => nth_weekday(year, 10, Weekday::Mon, 2),
+        "veterans" => fixed(11, 11),
+        "thanksgiving" => nth_weekday(year, 11, Weekday::Thu, 4),
+        "black friday" => nth_weekday(year, 11, Weekday::Thu, 4).map(|d| d + Duration::days(1)),
+        "christmas eve" => fixed(12, 24),
+        "christmas" | "xmas" => fixed(12, 25),
+        "boxing" => fixed(12, 26),
+        "new years eve" | "new year eve" => fixed(12, 31),
+        _ => None,
+    }
+}
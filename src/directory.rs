@@ -0,0 +1,86 @@
+use crate::{Employee, ExecutionError, api, config::ConnectionProfile};
+
+/// Resolves the `employee` tool argument to someone the logged-in user may modify: themselves,
+/// or someone EBMS lists them as the supervisor of. No argument means the logged-in employee,
+/// which needs no lookup.
+pub async fn resolve_employee(
+    profile: &ConnectionProfile,
+    requested: Option<&str>,
+) -> Result<Employee, ExecutionError> {
+    let requested = requested.map(str::trim).filter(|r| !r.is_empty());
+    let Some(requested) = requested else {
+        return Ok(profile.employee());
+    };
+    if requested.eq_ignore_ascii_case(profile.employee_id.trim()) {
+        return Ok(profile.employee());
+    }
+
+    // Only the user and their reports are fetched, so anyone else can't be matched
+    let directory = team(profile).await.map_err(ExecutionError::EbmsError)?;
+    if let Some(employee) = directory
+        .iter()
+        .find(|e| e.id.eq_ignore_ascii_case(requested))
+    {
+        return Ok(employee.clone());
+    }
+
+    let matches = match_name(&directory, requested);
+    match matches.as_slice() {
+        [employee] => Ok((*employee).clone()),
+        [] => Err(ExecutionError::PermissionDenied(format!(
+            "You don't have permission to change time for '{}', or they aren't in the employee directory",
+            requested
+        ))),
+        _ => Err(ExecutionError::InvalidRequest(format!(
+            "'{}' matches more than one employee: {}. Please be more specific.",
+            requested,
+            matches
+                .iter()
+                .map(|e| format!("{} ({})", e.name, e.id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// The logged-in employee and the people they may change time for.
+pub async fn team(profile: &ConnectionProfile) -> Result<Vec<Employee>, String> {
+    api::get_team(profile)
+        .await
+        .map_err(|e| format!("Failed to look up employees: {}", e))
+}
+
+// Prefers an exact full-name match, then a first or last name, then any partial match.
+// "Smith, Pat" is read as "Pat Smith", as in HR spreadsheets
+fn match_name<'a>(directory: &'a [Employee], requested: &str) -> Vec<&'a Employee> {
+    let requested = match requested.split_once(',') {
+        Some((last, first)) => format!("{} {}", first.trim(), last.trim()),
+        None => requested.to_string(),
+    }
+    .to_lowercase();
+    let exact: Vec<&Employee> = directory
+        .iter()
+        .filter(|e| e.name.to_lowercase() == requested)
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+
+    let by_part: Vec<&Employee> = directory
+        .iter()
+        .filter(|e| {
+            e.name
+                .to_lowercase()
+                .split_whitespace()
+                .any(|part| part == requested)
+        })
+        .collect();
+    if !by_part.is_empty() {
+        return by_part;
+    }
+
+    directory
+        .iter()
+        .filter(|e| e.name.to_lowercase().contains(&requested))
+        .collect()
+}
@@ -0,0 +1,221 @@
+//! Golden-prompt evaluation: runs a corpus of prompts against a model with a pinned "today"
+//! and checks the function call each one produces.
+
+use crate::{
+    AgentResponse, ExecutionOptions, PayType, agent_response,
+    clock::Clock,
+    config::AppConfig,
+    conversation_message::{ConversationMessage, FunctionCall, Role},
+    range_days,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalCase {
+    pub name: String,
+    pub prompt: String,
+    pub today: NaiveDate,
+    /// Earlier turns, e.g. the change an "undo that" prompt refers to
+    #[serde(default)]
+    pub conversation: Vec<EvalMessage>,
+    pub expect: Expectation,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalMessage {
+    pub role: String,
+    pub content: String,
+    #[serde(default)]
+    pub function_call: Option<EvalFunctionCall>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Expectation {
+    /// Only the listed arguments are checked; arrays are compared ignoring order
+    FunctionCall(EvalFunctionCall),
+    /// A text reply, e.g. asking for clarification
+    Message,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalFunctionCall {
+    pub name: String,
+    pub arguments: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalOutcome {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+pub fn load_corpus(path: &std::path::Path) -> Result<Vec<EvalCase>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid corpus {}: {}", path.display(), e))
+}
+
+/// Runs every case in order.
+pub async fn run_corpus(config: &AppConfig, cases: &[EvalCase]) -> Vec<EvalOutcome> {
+    let mut outcomes = Vec::new();
+    for case in cases {
+        outcomes.push(run_case(config, case).await);
+    }
+    outcomes
+}
+
+pub async fn run_case(config: &AppConfig, case: &EvalCase) -> EvalOutcome {
+    let conversation: Vec<ConversationMessage> = case
+        .conversation
+        .iter()
+        .map(to_conversation_message)
+        .collect();
+    let options = ExecutionOptions {
+        dry_run: true,
+        clock: Clock::fixed_date(case.today),
+        ..Default::default()
+    };
+
+    let response = agent_response(config, &case.prompt, &conversation, &options).await;
+    let (passed, detail) = match response {
+        Err(e) => (false, e.to_string()),
+        Ok(response) => check(config, &case.expect, &response),
+    };
+    EvalOutcome {
+        name: case.name.clone(),
+        passed,
+        detail,
+    }
+}
+
+fn to_conversation_message(message: &EvalMessage) -> ConversationMessage {
+    match &message.function_call {
+        Some(call) => ConversationMessage::new_function_call(
+            FunctionCall {
+                name: call.name.clone(),
+                arguments: call.arguments.to_string(),
+                prompt_version: None,
+                usage: None,
+            },
+            message.content.clone(),
+        ),
+        None => {
+            let role = match message.role.as_str() {
+                "assistant" => Role::Assistant,
+                "system" => Role::System,
+                _ => Role::User,
+            };
+            ConversationMessage::new_content(role, message.content.clone())
+        }
+    }
+}
+
+fn check(config: &AppConfig, expect: &Expectation, response: &AgentResponse) -> (bool, String) {
+    match (expect, response) {
+        (Expectation::Message, AgentResponse::Message(msg)) => (true, msg.clone()),
+        (Expectation::Message, AgentResponse::FunctionCall(call)) => (
+            false,
+            format!("Expected a reply, got {}({})", call.name, call.arguments),
+        ),
+        (Expectation::FunctionCall(expected), AgentResponse::Message(msg)) => (
+            false,
+            format!("Expected {}, got reply: {}", expected.name, msg),
+        ),
+        (Expectation::FunctionCall(expected), AgentResponse::FunctionCall(call)) => {
+            if call.name != expected.name {
+                return (
+                    false,
+                    format!("Expected {}, got {}", expected.name, call.name),
+                );
+            }
+            let actual: Value = match serde_json::from_str(&call.arguments) {
+                Ok(actual) => expand_range(config, actual),
+                Err(e) => return (false, format!("Arguments aren't valid JSON: {}", e)),
+            };
+            let mismatches: Vec<String> = expected
+                .arguments
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(key, value)| !same_value(value, &actual[key.as_str()]))
+                .map(|(key, value)| format!("{}: expected {}, got {}", key, value, actual[key]))
+                .collect();
+            if mismatches.is_empty() {
+                (true, call.arguments.clone())
+            } else {
+                (false, mismatches.join("; "))
+            }
+        }
+    }
+}
+
+// A start/end range counts as the days it changes, so either form matches expected dates
+fn expand_range(config: &AppConfig, mut arguments: Value) -> Value {
+    let parse = |field: &str| {
+        arguments[field]
+            .as_str()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    };
+    let (Some(start), Some(end)) = (parse("start"), parse("end")) else {
+        return arguments;
+    };
+    let Some(pay_type) = arguments["pay_type"]
+        .as_str()
+        .and_then(|p| p.parse::<PayType>().ok())
+    else {
+        return arguments;
+    };
+    let workdays_only = arguments["workdays_only"].as_bool().unwrap_or(true);
+    let mut dates: Vec<Value> = arguments["dates"].as_array().cloned().unwrap_or_default();
+    dates.extend(
+        range_days(
+            config,
+            arguments["employee"].as_str(),
+            &pay_type,
+            (start, end),
+            workdays_only,
+        )
+        .iter()
+        .map(|d| Value::String(d.format("%Y-%m-%d").to_string())),
+    );
+    arguments["dates"] = Value::Array(dates);
+    arguments
+}
+
+fn same_value(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::Array(expected), Value::Array(actual)) => {
+            let sorted = |values: &Vec<Value>| {
+                let mut values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                values.sort();
+                values.dedup();
+                values
+            };
+            sorted(expected) == sorted(actual)
+        }
+        (Value::String(expected), Value::String(actual)) => expected.eq_ignore_ascii_case(actual),
+        _ => expected == actual,
+    }
+}
+
+/// One line per case followed by a summary.
+pub fn report(outcomes: &[EvalOutcome]) -> String {
+    let mut lines: Vec<String> = outcomes
+        .iter()
+        .map(|o| {
+            format!(
+                "{} {}: {}",
+                if o.passed { "PASS" } else { "FAIL" },
+                o.name,
+                o.detail
+            )
+        })
+        .collect();
+    let passed = outcomes.iter().filter(|o| o.passed).count();
+    lines.push(format!("{}/{} passed", passed, outcomes.len()));
+    lines.join("\n")
+}
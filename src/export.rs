@@ -0,0 +1,122 @@
+//! Getting data back out of the agent: the audit history and time entries as CSV for
+//! spreadsheets, and days off as an ICS calendar for sharing out-of-office time.
+
+use crate::{
+    PayType, TimeEntry,
+    audit::AuditRecord,
+    ics::{self, IcsEvent},
+};
+use chrono::Duration;
+
+/// One row per pay type that was actually changed, oldest first.
+pub fn audit_csv(records: &[AuditRecord]) -> String {
+    let mut rows = vec![csv_row(&[
+        "timestamp",
+        "record_id",
+        "profile",
+        "employee_id",
+        "employee_name",
+        "date",
+        "old_pay_code",
+        "new_pay_type",
+        "undoes",
+        "prompt_version",
+    ])];
+    for record in records {
+        let timestamp = record.timestamp.to_rfc3339();
+        for change in &record.changes {
+            if change.get_function_call().is_none() {
+                continue;
+            }
+            rows.push(csv_row(&[
+                &timestamp,
+                &record.id,
+                &record.profile,
+                &change.employee.id,
+                &change.employee.name,
+                &change.date.format("%Y-%m-%d").to_string(),
+                &change.old_pay_type,
+                &change.pay_type.to_string(),
+                record.undoes.as_deref().unwrap_or_default(),
+                change
+                    .function_call
+                    .as_ref()
+                    .and_then(|f| f.prompt_version.as_deref())
+                    .unwrap_or_default(),
+            ]));
+        }
+    }
+    rows.concat()
+}
+
+/// One row per time detail line.
+pub fn entries_csv(entries: &[TimeEntry]) -> String {
+    let mut rows = vec![csv_row(&[
+        "employee_id",
+        "employee_name",
+        "date",
+        "weekday",
+        "pay_code",
+        "pay_type",
+    ])];
+    for entry in entries {
+        rows.push(csv_row(&[
+            &entry.employee.id,
+            &entry.employee.name,
+            &entry.date.format("%Y-%m-%d").to_string(),
+            &entry.date.format("%A").to_string(),
+            &entry.pay_code,
+            &entry
+                .pay_type
+                .as_ref()
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+        ]));
+    }
+    rows.concat()
+}
+
+/// An all-day event for each run of consecutive days an employee had the same
+/// non-salary pay type, e.g. "Pat Smith - Vacation".
+pub fn days_off_ics(entries: &[TimeEntry], stamp: chrono::DateTime<chrono::Utc>) -> String {
+    let mut days_off: Vec<(&TimeEntry, &PayType)> = entries
+        .iter()
+        .filter_map(|e| match &e.pay_type {
+            Some(PayType::Salary) | None => None,
+            Some(pay_type) => Some((e, pay_type)),
+        })
+        .collect();
+    days_off.sort_by(|(a, _), (b, _)| (&a.employee.id, a.date).cmp(&(&b.employee.id, b.date)));
+
+    let mut events: Vec<IcsEvent> = Vec::new();
+    let mut previous: Option<(&str, &PayType)> = None;
+    for (entry, pay_type) in days_off {
+        let summary = format!("{} - {}", entry.employee.name, pay_type);
+        let same_run = previous == Some((entry.employee.id.as_str(), pay_type));
+        match events.last_mut() {
+            Some(event) if same_run && event.end + Duration::days(1) >= entry.date => {
+                event.end = event.end.max(entry.date);
+            }
+            _ => events.push(IcsEvent {
+                summary,
+                start: entry.date,
+                end: entry.date,
+            }),
+        }
+        previous = Some((entry.employee.id.as_str(), pay_type));
+    }
+    ics::write(&events, stamp)
+}
+
+fn csv_row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+    format!("{}\r\n", fields.join(","))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
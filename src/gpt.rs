@@ -4,18 +4,26 @@ use crate::{
 };
 
 use super::FunctionCall;
-use crate::api::format_pay_code;
+use crate::{
+    clock::Clock,
+    config::{AppConfig, ToolCalling},
+    plan,
+    usage::{self, Usage},
+};
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
 use strum::IntoEnumIterator;
 
+const DEFAULT_GPT_API_URL: &str = "https://api.openai.com/v1/chat/completions";
+
 impl Role {
     fn as_str(&self) -> &'static str {
         match self {
             Role::User => "user",
             Role::Assistant => "assistant",
             Role::System => "system",
+            Role::Function => "function",
         }
     }
 }
@@ -24,6 +32,7 @@ impl Role {
 #[derive(Debug, Deserialize)]
 struct GptApiResponse {
     choices: Vec<GptChoice>,
+    usage: Option<Usage>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,38 +46,157 @@ struct GptMessage {
     content: Option<String>,
 }
 
+// A streamed completion arrives as chunks, each holding a piece of the message
+#[derive(Debug, Deserialize)]
+struct GptChunk {
+    #[serde(default)]
+    choices: Vec<GptChunkChoice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GptChunkChoice {
+    #[serde(default)]
+    delta: GptDelta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct GptDelta {
+    content: Option<String>,
+    function_call: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FunctionCallDelta {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+/// `conversation` ends with the user's prompt, followed by any functions already run for it.
+/// `system_prompt` is the rendered template from the `prompt` module.
+/// Returns the tokens the call used too, if the API reported them.
 pub async fn call_gpt(
-    api_key: &str,
-    prompt: &str,
-    conversation: &Vec<ConversationMessage>,
-) -> Result<AgentResponse, Box<dyn std::error::Error>> {
-    let client = Client::new();
-    //this is important to let gpt know the context, otherwise it gets confused by "today", "wednesday", etc.
-    let now = chrono::Local::now();
-    let today = now.format("%A, %Y-%m-%d").to_string(); // e.g. "Monday, 2025-05-26"
+    config: &AppConfig,
+    clock: &Clock,
+    system_prompt: &str,
+    conversation: &[ConversationMessage],
+) -> Result<(AgentResponse, Option<Usage>), Box<dyn std::error::Error + Send + Sync>> {
+    let body = request_body(config, system_prompt, conversation, false);
+    let res = send(config, clock, &body).await?;
+
+    let response: GptApiResponse = res.json().await?;
+    if let Some(usage) = response.usage {
+        usage::record(config, clock, config.gpt_model(), usage);
+    }
 
+    let choice = response.choices.first();
+    let reply = match choice {
+        Some(c) => match &c.message.function_call {
+            Some(fc) => AgentResponse::FunctionCall(fc.clone()),
+            None => match &c.message.content {
+                Some(content) if plan::enabled(config) => plan::parse(content)?,
+                Some(content) => AgentResponse::Message(content.clone()),
+                None => return Err("No content or function call returned from GPT API".into()),
+            },
+        },
+        None => return Err("No choices returned from GPT API".into()),
+    };
+    Ok((reply, response.usage))
+}
+
+/// Like [`call_gpt`], but the completion is streamed and each piece of the reply's text is
+/// handed to `on_text` as it arrives. A function call is only returned once all of its
+/// arguments have arrived. A JSON plan isn't text for the user, so it isn't handed on.
+pub async fn call_gpt_streaming(
+    config: &AppConfig,
+    clock: &Clock,
+    system_prompt: &str,
+    conversation: &[ConversationMessage],
+    on_text: &(dyn Fn(&str) + Send + Sync),
+) -> Result<(AgentResponse, Option<Usage>), Box<dyn std::error::Error + Send + Sync>> {
+    let body = request_body(config, system_prompt, conversation, true);
+    let mut res = send(config, clock, &body).await?;
+
+    let mut content = String::new();
+    let mut function_name = String::new();
+    let mut arguments = String::new();
+    let mut usage = None;
+    // Server-sent events: "data: {chunk}" lines, ending with "data: [DONE]"
+    let mut buffer: Vec<u8> = Vec::new();
+    'stream: while let Some(bytes) = res.chunk().await? {
+        buffer.extend_from_slice(&bytes);
+        while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            if data.trim() == "[DONE]" {
+                break 'stream;
+            }
+            let chunk: GptChunk = serde_json::from_str(data.trim())?;
+            usage = chunk.usage.or(usage);
+            let Some(delta) = chunk.choices.into_iter().next().map(|c| c.delta) else {
+                continue;
+            };
+            if let Some(text) = delta.content.filter(|t| !t.is_empty()) {
+                if !plan::enabled(config) {
+                    on_text(&text);
+                }
+                content.push_str(&text);
+            }
+            if let Some(call) = delta.function_call {
+                function_name.push_str(&call.name.unwrap_or_default());
+                arguments.push_str(&call.arguments.unwrap_or_default());
+            }
+        }
+    }
+
+    if let Some(usage) = usage {
+        usage::record(config, clock, config.gpt_model(), usage);
+    }
+    let reply = if !function_name.is_empty() {
+        AgentResponse::FunctionCall(FunctionCall {
+            name: function_name,
+            arguments,
+            prompt_version: None,
+            usage: None,
+        })
+    } else if !content.is_empty() && plan::enabled(config) {
+        plan::parse(&content)?
+    } else if !content.is_empty() {
+        AgentResponse::Message(content)
+    } else {
+        return Err("No content or function call returned from GPT API".into());
+    };
+    Ok((reply, usage))
+}
+
+fn request_body(
+    config: &AppConfig,
+    system_prompt: &str,
+    conversation: &[ConversationMessage],
+    stream: bool,
+) -> serde_json::Value {
+    let plan_mode = plan::enabled(config);
+    let system_prompt = if plan_mode {
+        format!("{}\n\n{}", system_prompt, plan::instructions(config))
+    } else {
+        system_prompt.to_string()
+    };
     let mut full_conversation: Vec<ConversationMessage> = vec![ConversationMessage::new_content(
-        Role::Assistant,
-        format!(
-            "You are a helpful assistant that can set pay types for employees. \
-             If no pay type is specified, use Salary by default. \
-             Today's date is {}, the week begins on Sunday \
-             If the user asks you to undo a change and the record shows that you made a change, you should set it back to what you originally said it was.",
-            today
-        ),
+        Role::System,
+        system_prompt,
     )];
 
-    if !conversation.is_empty() {
-        full_conversation.extend(conversation.clone());
+    if plan_mode {
+        full_conversation.extend(conversation.iter().map(plan::to_plain_message));
+    } else {
+        full_conversation.extend_from_slice(conversation);
     }
 
-    full_conversation.push(ConversationMessage::new_content(
-        Role::User,
-        prompt.to_string(),
-    ));
-
-    let body = json!({
-        "model": "gpt-4",
+    let mut body = json!({
+        "model": config.gpt_model(),
         "messages": full_conversation
             .iter()
             .map(|msg| {
@@ -76,30 +204,68 @@ pub async fn call_gpt(
                     "role": msg.role.as_str(),
                     "content": msg.content,
                 });
-                if let Some(fc) = &msg.function_call {
-                    if let serde_json::Value::Object(ref mut map) = obj {
-                        map.insert(
-                            "function_call".to_string(),
-                            serde_json::json!({
-                                "name": fc.name,
-                                "arguments": fc.arguments
-                            }),
-                        );
-                    }
+                if let Some(fc) = &msg.function_call
+                    && let serde_json::Value::Object(ref mut map) = obj
+                {
+                    map.insert(
+                        "function_call".to_string(),
+                        serde_json::json!({
+                            "name": fc.name,
+                            "arguments": fc.arguments
+                        }),
+                    );
+                }
+                if let Some(name) = &msg.name
+                    && let serde_json::Value::Object(ref mut map) = obj
+                {
+                    map.insert("name".to_string(), json!(name));
                 }
                 obj
             })
             .collect::<Vec<_>>(),
-        "functions": get_functions_metadata(),
-        "function_call": "auto"
     });
+    match config.tool_calling {
+        ToolCalling::Functions => {
+            body["functions"] = json!(get_functions_metadata(config));
+            body["function_call"] = json!("auto");
+        }
+        ToolCalling::JsonSchema => {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": "agent_plan", "schema": plan::schema(config) }
+            });
+        }
+        ToolCalling::JsonPrompt => {}
+    }
+    if stream {
+        body["stream"] = json!(true);
+        // Usage comes in a last chunk of its own
+        body["stream_options"] = json!({ "include_usage": true });
+    }
+    body
+}
+
+async fn send(
+    config: &AppConfig,
+    clock: &Clock,
+    body: &serde_json::Value,
+) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+    usage::check_budget(config, clock)?;
+    if config.gpt_api_key.is_empty() && config.gpt_api_url.is_empty() {
+        return Err("No OpenAI API key is set".into());
+    }
+    let client = Client::builder().timeout(config.gpt_timeout()).build()?;
 
-    println!("Calling GPT with body: {}", body);
+    tracing::debug!(%body, "Calling the model");
 
     let res = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(api_key)
-        .json(&body)
+        .post(if config.gpt_api_url.is_empty() {
+            DEFAULT_GPT_API_URL
+        } else {
+            &config.gpt_api_url
+        })
+        .bearer_auth(&config.gpt_api_key)
+        .json(body)
         .send()
         .await?;
 
@@ -107,56 +273,102 @@ pub async fn call_gpt(
         let err = res.text().await?;
         return Err(format!("GPT API Error: {}", err).into());
     }
-
-    let response: GptApiResponse = res.json().await?;
-
-    let choice = response.choices.first();
-    match choice {
-        Some(c) => match &c.message.function_call {
-            Some(fc) => Ok(AgentResponse::FunctionCall(fc.clone())),
-            None => match &c.message.content {
-                Some(content) => Ok(AgentResponse::Message(content.clone())),
-                None => Err("No content or function call returned from GPT API".into()),
-            },
-        },
-        None => Err("No choices returned from GPT API".into()),
-    }
+    Ok(res)
 }
 
-fn get_functions_metadata() -> Vec<serde_json::Value> {
-    vec![json!({
-        "name": "set_pay_type",
-        "description": "Set a pay type for a set of dates",
-        "parameters": {
-            "type": "object",
-            "properties": {
-                "dates": {
-                    "type": "array",
-                    "items": {
+pub(crate) fn get_functions_metadata(config: &AppConfig) -> Vec<serde_json::Value> {
+    let profile = config.profile().cloned().unwrap_or_default();
+    vec![
+        json!({
+            "name": "set_pay_type",
+            "description": "Set a pay type for a set of dates, or for the work days in a range",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "dates": {
+                        "type": "array",
+                        "items": {
+                            "type": "string",
+                            "format": "date",
+                            "description": "A date to apply the pay type (format: YYYY-MM-DD)"
+                        },
+                        "description": "The dates to apply the pay type (format: YYYY-MM-DD)",
+                        "minItems": 1
+                    },
+                    "start": {
+                        "type": "string",
+                        "format": "date",
+                        "description": "First day of a range to apply the pay type to (format: YYYY-MM-DD)"
+                    },
+                    "end": {
                         "type": "string",
-                        "description": "A date to apply the pay type (format: YYYY-MM-DD)"
+                        "format": "date",
+                        "description": "Last day of the range, inclusive (format: YYYY-MM-DD)"
                     },
-                    "description": "The dates to apply the pay type (format: YYYY-MM-DD)",
-                    "minItems": 1
+                    "workdays_only": {
+                        "type": "boolean",
+                        "description": "Skip days outside the work week in the range. Defaults to true."
+                    },
+                    "pay_type": {
+                        "type": "string",
+                        "enum": PayType::iter().map(|pt| pt.to_string()).collect::<Vec<_>>(),
+                        "description": &format!(
+                            "One of: {}. Salary by default. See this mapping for details: {}",
+                            PayType::iter()
+                                .map(|pt| pt.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            PayType::iter()
+                        .map(|pt| format!("{} is {}", &pt.to_string(), profile.pay_code(&pt)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                        )
+                    },
+                    "employee": {
+                        "type": "string",
+                        "description": "Name or employee ID of the person whose time to change. Omit to change the logged-in user's own time."
+                    }
                 },
-                "pay_type": {
-                    "type": "string",
-                    "enum": PayType::iter().map(|pt| pt.to_string()).collect::<Vec<_>>(),
-                    "description": &format!(
-                        "One of: {}. Salary by default. See this mapping for details: {}",
-                        PayType::iter()
-                            .map(|pt| pt.to_string())
-                            .collect::<Vec<_>>()
-                            .join(", "),
-                        PayType::iter()
-                    .map(|pt| format!("{} is {}", &pt.to_string(), format_pay_code(&pt)))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-                    )
-                }
-            },
-            "required": ["dates", "pay_type"],
-            "additionalProperties": false
-        }
-    })]
+                "required": ["pay_type"],
+                "dependentRequired": { "start": ["end"], "end": ["start"] },
+                "additionalProperties": false
+            }
+        }),
+        json!({
+            "name": crate::RESOLVE_DATES,
+            "description": "Turn a date expression into exact dates. \"friday\" and \"this friday\" are in the current week, \"next friday\" is in the following week and \"last friday\" is the most recent one. \
+                            Understands today/yesterday/tomorrow, weekdays, this/next/last week or month, month names, dates like \"july 3\" or \"7/3/2025\", \
+                            US holidays like \"thanksgiving\", \"the week of/after/before ...\", \"the day after/before ...\", ranges (\"X to Y\"), lists (\"X and Y\") \
+                            and weekday sets like \"mondays and wednesdays in june\".",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "expression": {
+                        "type": "string",
+                        "description": "The dates in the user's words, e.g. \"the week after thanksgiving\""
+                    },
+                    "workdays_only": {
+                        "type": "boolean",
+                        "description": "Drop days outside the company's work week and company holidays, e.g. for a week of vacation. Defaults to false."
+                    }
+                },
+                "required": ["expression"],
+                "additionalProperties": false
+            }
+        }),
+        json!({
+            "name": crate::LIST_HOLIDAYS,
+            "description": "List the company holidays in a year, with their dates and names",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "year": {
+                        "type": "integer",
+                        "description": "The year, e.g. 2025. Defaults to the current year."
+                    }
+                },
+                "additionalProperties": false
+            }
+        }),
+    ]
 }
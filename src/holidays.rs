@@ -0,0 +1,71 @@
+//! The company holiday calendar: holidays listed in the config file plus any ICS calendar.
+//! EBMS's OData API doesn't publish a holiday list, so these are the only sources.
+
+use crate::{clock, config::AppConfig, ics};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HolidayCalendar {
+    holidays: Vec<Holiday>,
+}
+
+impl HolidayCalendar {
+    /// Reads the configured holidays and ICS calendar. A calendar that can't be read
+    /// is reported and left out rather than failing the request.
+    pub fn load(config: &AppConfig) -> Self {
+        let mut holidays = config.company_holidays.clone();
+        if let Some(path) = calendar_path(config) {
+            match std::fs::read_to_string(&path) {
+                Ok(text) => holidays.extend(
+                    ics::parse(&text, clock::company_timezone(config))
+                        .iter()
+                        .flat_map(|event| {
+                            event.dates().map(|date| Holiday {
+                                date,
+                                name: event.summary.clone(),
+                            })
+                        }),
+                ),
+                Err(e) => tracing::warn!("Failed to read holidays from {}: {}", path.display(), e),
+            }
+        }
+        HolidayCalendar::new(holidays)
+    }
+
+    pub fn new(mut holidays: Vec<Holiday>) -> Self {
+        holidays.sort_by_key(|h| h.date);
+        holidays.dedup_by_key(|h| h.date);
+        HolidayCalendar { holidays }
+    }
+
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.holidays.iter().any(|h| h.date == date)
+    }
+
+    pub fn in_year(&self, year: i32) -> Vec<Holiday> {
+        self.holidays
+            .iter()
+            .filter(|h| h.date.year() == year)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The configured ICS calendar, or `holidays.ics` in the config directory if that exists.
+pub fn calendar_path(config: &AppConfig) -> Option<PathBuf> {
+    match &config.holiday_calendar_path {
+        Some(path) => Some(path.clone()),
+        None => crate::config::config_dir()
+            .map(|dir| dir.join("holidays.ics"))
+            .filter(|path| path.exists()),
+    }
+}
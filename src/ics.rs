@@ -0,0 +1,336 @@
+//! Just enough iCalendar (RFC 5545) to read events as dates and write all-day events.
+
+use chrono::{
+    DateTime, Datelike, Days, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcsEvent {
+    pub summary: String,
+    pub start: NaiveDate,
+    /// Last day of the event, inclusive
+    pub end: NaiveDate,
+}
+
+impl IcsEvent {
+    pub fn dates(&self) -> impl Iterator<Item = NaiveDate> + '_ {
+        self.start.iter_days().take_while(|d| *d <= self.end)
+    }
+}
+
+#[derive(Default)]
+struct PartialEvent {
+    summary: String,
+    start: Option<DateValue>,
+    end: Option<DateValue>,
+    rrule: Option<String>,
+    /// Occurrences of a repeating event that were cancelled
+    exdates: Vec<NaiveDate>,
+}
+
+// A DTSTART or DTEND, on the calendar of the zone events are read in
+#[derive(Debug, Clone, Copy)]
+struct DateValue {
+    date: NaiveDate,
+    /// `None` for date-only values, which mark all-day events
+    time: Option<NaiveTime>,
+}
+
+/// Every VEVENT with a start date, on the days it falls on in `zone` (local time if `None`).
+/// A repeating event becomes one event per occurrence, through the end of next year if the
+/// rule doesn't end sooner; rules this doesn't understand are reported and only the first
+/// occurrence is kept. Events without a start, and properties this doesn't understand, are
+/// skipped.
+pub fn parse(text: &str, zone: Option<Tz>) -> Vec<IcsEvent> {
+    let mut events = Vec::new();
+    let mut current: Option<PartialEvent> = None;
+
+    for line in unfold(text) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // "DTSTART;TZID=America/Chicago" -> "DTSTART" and "America/Chicago"
+        let mut parameters = name.split(';');
+        let property = parameters.next().unwrap_or_default().to_ascii_uppercase();
+        let tzid = parameters
+            .filter_map(|p| p.split_once('='))
+            .find(|(key, _)| key.eq_ignore_ascii_case("TZID"))
+            .map(|(_, tzid)| tzid.trim_matches('"'));
+        match (property.as_str(), &mut current) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VEVENT") => {
+                current = Some(PartialEvent::default());
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                if let Some(event) = current.take() {
+                    events.extend(occurrences(event));
+                }
+            }
+            ("SUMMARY", Some(event)) => event.summary = unescape(value),
+            ("DTSTART", Some(event)) => event.start = parse_date(value, tzid, zone),
+            ("DTEND", Some(event)) => event.end = parse_date(value, tzid, zone),
+            ("RRULE", Some(event)) => event.rrule = Some(value.trim().to_string()),
+            ("EXDATE", Some(event)) => event.exdates.extend(
+                value
+                    .split(',')
+                    .filter_map(|v| parse_date(v, tzid, zone))
+                    .map(|v| v.date),
+            ),
+            _ => {}
+        }
+    }
+    events
+}
+
+fn occurrences(event: PartialEvent) -> Vec<IcsEvent> {
+    let Some(start) = event.start else {
+        return Vec::new();
+    };
+    let end = match event.end {
+        // All-day DTEND is exclusive, and so is a timed end at midnight
+        Some(DateValue { date, time: None }) if date > start.date => date - Duration::days(1),
+        Some(DateValue {
+            date,
+            time: Some(NaiveTime::MIN),
+        }) if date > start.date => date - Duration::days(1),
+        Some(DateValue {
+            date,
+            time: Some(_),
+        }) if date >= start.date => date,
+        _ => start.date,
+    };
+    let first = IcsEvent {
+        summary: event.summary,
+        start: start.date,
+        end,
+    };
+    let Some(rule) = event.rrule else {
+        return vec![first];
+    };
+    match repeat_dates(&rule, start.date) {
+        Ok(dates) => dates
+            .into_iter()
+            .filter(|date| !event.exdates.contains(date))
+            .map(|date| IcsEvent {
+                summary: first.summary.clone(),
+                start: date,
+                end: date + (first.end - first.start),
+            })
+            .collect(),
+        Err(e) => {
+            tracing::warn!(
+                "'{}' repeats by {}, which isn't supported ({}); only its first day is used",
+                first.summary,
+                rule,
+                e
+            );
+            vec![first]
+        }
+    }
+}
+
+// The start dates of a repeating event, for the RRULE parts FREQ, INTERVAL, COUNT and UNTIL.
+// BY parts that only restate the start date, as some calendars write them, are allowed
+fn repeat_dates(rule: &str, start: NaiveDate) -> Result<Vec<NaiveDate>, String> {
+    let (mut months, mut days) = (0, 0);
+    let (mut interval, mut count, mut until) = (1u32, None, None);
+    for part in rule.split(';').filter(|p| !p.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' isn't KEY=VALUE", part))?;
+        let value = value.trim();
+        match (key.trim().to_ascii_uppercase().as_str(), value) {
+            ("FREQ", "DAILY") => days = 1,
+            ("FREQ", "WEEKLY") => days = 7,
+            ("FREQ", "MONTHLY") => months = 1,
+            ("FREQ", "YEARLY") => months = 12,
+            ("INTERVAL", _) => {
+                interval = value
+                    .parse()
+                    .ok()
+                    .filter(|i| *i > 0)
+                    .ok_or_else(|| format!("invalid {}", part))?
+            }
+            ("COUNT", _) => {
+                count = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid {}", part))?,
+                )
+            }
+            ("UNTIL", _) => {
+                until = Some(
+                    value
+                        .get(..8)
+                        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+                        .ok_or_else(|| format!("invalid {}", part))?,
+                )
+            }
+            ("WKST", _) => {}
+            ("BYMONTH", _) if value == start.month().to_string() => {}
+            ("BYMONTHDAY", _) if value == start.day().to_string() => {}
+            ("BYDAY", _) if value.eq_ignore_ascii_case(&start.weekday().to_string()[..2]) => {}
+            _ => return Err(format!("{} isn't understood", part)),
+        }
+    }
+    if months == 0 && days == 0 {
+        return Err("it has no FREQ this understands".to_string());
+    }
+
+    let horizon =
+        NaiveDate::from_ymd_opt(Local::now().year() + 1, 12, 31).unwrap_or(NaiveDate::MAX);
+    let last = until.map_or(horizon, |until: NaiveDate| until.min(horizon));
+    let mut dates = Vec::new();
+    for n in 0u32.. {
+        let Some(steps) = n.checked_mul(interval) else {
+            break;
+        };
+        let date = if months > 0 {
+            steps
+                .checked_mul(months)
+                .and_then(|m| start.checked_add_months(Months::new(m)))
+        } else {
+            start.checked_add_days(Days::new(u64::from(steps) * days))
+        };
+        let Some(date) = date else {
+            break;
+        };
+        if date > last || count.is_some_and(|count| dates.len() >= count) {
+            break;
+        }
+        // Months without the start's day, e.g. the 31st or February 29, are skipped
+        if months == 0 || date.day() == start.day() {
+            dates.push(date);
+        }
+    }
+    Ok(dates)
+}
+
+// Long lines continue on lines starting with a space or tab
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+    lines
+}
+
+// "20251225" is a date. "20251225T090000" is a time in `tzid`, or UTC with a trailing "Z",
+// or the same everywhere with neither; the day it falls on is taken in `zone`
+fn parse_date(value: &str, tzid: Option<&str>, zone: Option<Tz>) -> Option<DateValue> {
+    let value = value.trim();
+    if value.len() == 8 {
+        return Some(DateValue {
+            date: NaiveDate::parse_from_str(value, "%Y%m%d").ok()?,
+            time: None,
+        });
+    }
+    let (text, utc) = match value.strip_suffix(['Z', 'z']) {
+        Some(text) => (text, true),
+        None => (value, false),
+    };
+    let written = NaiveDateTime::parse_from_str(text, "%Y%m%dT%H%M%S").ok()?;
+    let instant: Option<DateTime<Utc>> = match tzid {
+        _ if utc => Some(Utc.from_utc_datetime(&written)),
+        Some(name) => match name.parse::<Tz>() {
+            Ok(tz) => tz
+                .from_local_datetime(&written)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+            Err(_) => {
+                tracing::warn!(
+                    "Unknown calendar timezone '{}', reading times as written",
+                    name
+                );
+                None
+            }
+        },
+        None => None,
+    };
+    let local = match (instant, zone) {
+        (None, _) => written,
+        (Some(instant), Some(tz)) => instant.with_timezone(&tz).naive_local(),
+        (Some(instant), None) => instant.with_timezone(&Local).naive_local(),
+    };
+    Some(DateValue {
+        date: local.date(),
+        time: Some(local.time()),
+    })
+}
+
+// One pass, so an escaped backslash before an "n" isn't read as a line break
+fn unescape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push(' '),
+            // "\\", "\," and "\;"
+            Some(escaped) => text.push(escaped),
+            None => text.push(c),
+        }
+    }
+    text.trim().to_string()
+}
+
+/// A calendar of all-day events, e.g. to share who is out of office.
+/// `stamp` is when the calendar was generated.
+pub fn write(events: &[IcsEvent], stamp: chrono::DateTime<chrono::Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//ebms-agent//{}//EN", env!("CARGO_PKG_VERSION")),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    // Events with the same day and summary are numbered, so each UID is unique
+    let mut uids: HashMap<String, usize> = HashMap::new();
+    for event in events {
+        let slug: String = event
+            .summary
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let uid = format!("{}-{}", event.start.format("%Y%m%d"), slug);
+        let seen = uids.entry(uid.clone()).or_default();
+        *seen += 1;
+        let uid = match *seen {
+            1 => uid,
+            n => format!("{}-{}", uid, n),
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@ebms-agent", uid),
+            format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")),
+            format!("DTSTART;VALUE=DATE:{}", event.start.format("%Y%m%d")),
+            // All-day DTEND is exclusive
+            format!(
+                "DTEND;VALUE=DATE:{}",
+                (event.end + Duration::days(1)).format("%Y%m%d")
+            ),
+            format!("SUMMARY:{}", escape(&event.summary)),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    let mut text = lines.join("\r\n");
+    text.push_str("\r\n");
+    text
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,440 @@
+//! Bulk changes from outside the chat, e.g. a PTO calendar or an HR spreadsheet. Each source is turned into
+//! `set_pay_type` calls that are previewed as a dry run before anything is applied.
+
+use crate::{
+    Employee, ExecutionOptions, ExecutionResult, PayType, PayTypeChange, clock,
+    config::{AppConfig, ConnectionProfile},
+    conversation_message::FunctionCall,
+    directory, execute_function_call, ics,
+};
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use strum::IntoEnumIterator;
+
+/// Calendar events whose title contains `pattern`, ignoring case, become `pay_type`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImportRule {
+    pub pattern: String,
+    pub pay_type: PayType,
+}
+
+/// Used when no rules are configured.
+pub fn default_import_rules() -> Vec<ImportRule> {
+    [
+        ("Sick", PayType::Sick),
+        ("Vacation", PayType::Vacation),
+        ("PTO", PayType::Vacation),
+        ("OOO", PayType::Vacation),
+        ("Out of office", PayType::Vacation),
+    ]
+    .into_iter()
+    .map(|(pattern, pay_type)| ImportRule {
+        pattern: pattern.to_string(),
+        pay_type,
+    })
+    .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IcsImportOptions {
+    /// Name or employee ID; the logged-in user if `None`
+    pub employee: Option<String>,
+    /// Only import days from this date on
+    pub from: Option<NaiveDate>,
+    /// Only import days up to this date, inclusive
+    pub to: Option<NaiveDate>,
+}
+
+/// One event from the source and the call it becomes.
+#[derive(Debug, Clone)]
+pub struct ImportItem {
+    pub source: String,
+    pub function_call: FunctionCall,
+    /// The CSV lines it came from; empty for calendar events
+    pub lines: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportPlan {
+    pub items: Vec<ImportItem>,
+    /// Events no rule matched, or that fall outside the dates asked for
+    pub skipped: Vec<String>,
+}
+
+/// An item and what it would change, or why it can't be applied.
+#[derive(Debug, Clone)]
+pub struct ImportPreview {
+    pub item: ImportItem,
+    pub changes: Result<Vec<PayTypeChange>, String>,
+}
+
+impl ImportPreview {
+    /// Whether applying it would change anything in EBMS.
+    pub fn has_changes(&self) -> bool {
+        self.changes
+            .as_ref()
+            .is_ok_and(|changes| changes.iter().any(|c| c.get_function_call().is_some()))
+    }
+}
+
+/// Matches each event against the configured rules. Multi-day events become `start`/`end`
+/// calls, so weekends and company holidays are skipped as usual; events longer than
+/// `max_range_days`, e.g. parental leave, are split into ranges that fit.
+pub fn plan_ics_import(config: &AppConfig, text: &str, options: &IcsImportOptions) -> ImportPlan {
+    let rules = if config.import_rules.is_empty() {
+        default_import_rules()
+    } else {
+        config.import_rules.clone()
+    };
+
+    let mut plan = ImportPlan::default();
+    for event in ics::parse(text, clock::company_timezone(config)) {
+        let start = options
+            .from
+            .map_or(event.start, |from| from.max(event.start));
+        let end = options.to.map_or(event.end, |to| to.min(event.end));
+        let source = if event.start == event.end {
+            format!("{} ({})", event.summary, event.start)
+        } else {
+            format!("{} ({} to {})", event.summary, event.start, event.end)
+        };
+        if end < start {
+            plan.skipped
+                .push(format!("{}: outside the dates to import", source));
+            continue;
+        }
+        let summary = event.summary.to_lowercase();
+        let Some(rule) = rules
+            .iter()
+            .find(|rule| summary.contains(&rule.pattern.to_lowercase()))
+        else {
+            plan.skipped.push(format!("{}: no rule matches", source));
+            continue;
+        };
+
+        let chunk_days = i64::from(config.max_range_days());
+        let chunks: Vec<(NaiveDate, NaiveDate)> = start
+            .iter_days()
+            .take_while(|d| *d <= end)
+            .step_by(chunk_days as usize)
+            .map(|first| (first, end.min(first + Duration::days(chunk_days - 1))))
+            .collect();
+        for (i, (first, last)) in chunks.iter().enumerate() {
+            let mut arguments = serde_json::json!({
+                "start": first.format("%Y-%m-%d").to_string(),
+                "end": last.format("%Y-%m-%d").to_string(),
+                "pay_type": rule.pay_type.to_string(),
+            });
+            if let Some(employee) = &options.employee {
+                arguments["employee"] = serde_json::json!(employee);
+            }
+            let source = if chunks.len() > 1 {
+                format!("{}, part {} of {}", source, i + 1, chunks.len())
+            } else {
+                source.clone()
+            };
+            plan.items.push(ImportItem {
+                source,
+                function_call: FunctionCall {
+                    name: "set_pay_type".to_string(),
+                    arguments: arguments.to_string(),
+                    prompt_version: None,
+                    usage: None,
+                },
+                lines: Vec::new(),
+            });
+        }
+    }
+    plan
+}
+
+/// Dry-runs every item against EBMS.
+pub async fn preview_import(config: &AppConfig, plan: &ImportPlan) -> Vec<ImportPreview> {
+    let options = ExecutionOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let mut previews = Vec::new();
+    for item in &plan.items {
+        let changes = match execute_function_call(config, &item.function_call, &options).await {
+            Ok(ExecutionResult::Planned(_, changes)) | Ok(ExecutionResult::Success(changes)) => {
+                Ok(changes)
+            }
+            Ok(ExecutionResult::Message(msg)) => Err(msg),
+            Err(e) => Err(e.to_string()),
+        };
+        previews.push(ImportPreview {
+            item: item.clone(),
+            changes,
+        });
+    }
+    previews
+}
+
+/// Applies the previewed items that change something, each audited like a prompt's changes.
+/// Items with nothing to change keep their preview.
+pub async fn apply_import(config: &AppConfig, previews: &[ImportPreview]) -> Vec<ImportPreview> {
+    let mut results = Vec::new();
+    for preview in previews {
+        if !preview.has_changes() {
+            results.push(preview.clone());
+            continue;
+        }
+        let changes = match execute_function_call(
+            config,
+            &preview.item.function_call,
+            &ExecutionOptions::default(),
+        )
+        .await
+        {
+            Ok(ExecutionResult::Success(changes)) | Ok(ExecutionResult::Planned(_, changes)) => {
+                Ok(changes)
+            }
+            Ok(ExecutionResult::Message(msg)) => Err(msg),
+            Err(e) => Err(e.to_string()),
+        };
+        results.push(ImportPreview {
+            item: preview.item.clone(),
+            changes,
+        });
+    }
+    results
+}
+
+/// A CSV row that passed validation.
+#[derive(Debug, Clone)]
+pub struct CsvRow {
+    /// Line number in the file, counting the header as line 1
+    pub line: usize,
+    pub employee: Employee,
+    pub date: NaiveDate,
+    pub pay_type: PayType,
+    /// At least `AppConfig::full_day_hours`, as EBMS changes whole time detail lines
+    pub hours: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CsvImport {
+    pub rows: Vec<CsvRow>,
+    /// One item per employee and pay type; rows that failed validation are in `skipped`
+    pub plan: ImportPlan,
+}
+
+/// Validates every row of a CSV with `employee`, `date` and `pay_type` columns and an
+/// optional `hours` column; rows with fewer hours than a full day are refused, as the
+/// whole day's pay type would change. Employees are looked up in EBMS like the `employee` tool
+/// argument, pay types may be names ("Vacation") or pay codes ("Vac-SAL"), and dates
+/// are YYYY-MM-DD or M/D/YYYY. Valid rows are grouped so each group is one batched call.
+pub async fn plan_csv_import(config: &AppConfig, text: &str) -> Result<CsvImport, String> {
+    let profile = config
+        .profile()
+        .ok_or_else(|| "No connection profile selected".to_string())?;
+    let mut records = parse_csv(text).into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or_else(|| "The CSV file is empty".to_string())?
+        .iter()
+        .map(|h| h.trim().to_lowercase().replace(' ', "_"))
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let (Some(employee_col), Some(date_col), Some(pay_type_col)) =
+        (column("employee"), column("date"), column("pay_type"))
+    else {
+        return Err("The CSV needs employee, date and pay_type columns".to_string());
+    };
+    let hours_col = column("hours");
+
+    let mut import = CsvImport::default();
+    let mut employees: HashMap<String, Result<Employee, String>> = HashMap::new();
+    for (index, record) in records.enumerate() {
+        let line = index + 2;
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |col: usize| record.get(col).map(|f| f.trim()).unwrap_or_default();
+
+        let employee_input = field(employee_col).to_string();
+        if !employees.contains_key(&employee_input) {
+            let requested = (!employee_input.is_empty()).then_some(employee_input.as_str());
+            let employee = directory::resolve_employee(profile, requested)
+                .await
+                .map_err(|e| e.to_string());
+            employees.insert(employee_input.clone(), employee);
+        }
+        let row = validate_row(
+            config,
+            profile,
+            line,
+            &employees[&employee_input],
+            field(date_col),
+            field(pay_type_col),
+            hours_col.map(field),
+        );
+        let row = row.and_then(|row| {
+            match import
+                .rows
+                .iter()
+                .find(|r| r.employee == row.employee && r.date == row.date)
+            {
+                Some(earlier) if earlier.pay_type != row.pay_type => Err(format!(
+                    "conflicts with line {}, which sets {} to {}",
+                    earlier.line, row.date, earlier.pay_type
+                )),
+                _ => Ok(row),
+            }
+        });
+        match row {
+            Ok(row) => import.rows.push(row),
+            Err(e) => import.plan.skipped.push(format!("line {}: {}", line, e)),
+        }
+    }
+
+    let mut groups: Vec<(&Employee, &PayType, Vec<&CsvRow>)> = Vec::new();
+    for row in &import.rows {
+        match groups.iter_mut().find(|(employee, pay_type, _)| {
+            **employee == row.employee && **pay_type == row.pay_type
+        }) {
+            Some((_, _, rows)) => rows.push(row),
+            None => groups.push((&row.employee, &row.pay_type, vec![row])),
+        }
+    }
+    import.plan.items = groups
+        .into_iter()
+        .map(|(employee, pay_type, rows)| {
+            let mut dates: Vec<String> = rows
+                .iter()
+                .map(|r| r.date.format("%Y-%m-%d").to_string())
+                .collect();
+            dates.sort();
+            dates.dedup();
+            let lines: Vec<String> = rows.iter().map(|r| r.line.to_string()).collect();
+            ImportItem {
+                source: format!("{}: {} (lines {})", employee, pay_type, lines.join(", ")),
+                function_call: FunctionCall {
+                    name: "set_pay_type".to_string(),
+                    arguments: serde_json::json!({
+                        "dates": dates,
+                        "pay_type": pay_type.to_string(),
+                        "employee": employee.id,
+                    })
+                    .to_string(),
+                    prompt_version: None,
+                    usage: None,
+                },
+                lines: rows.iter().map(|r| r.line).collect(),
+            }
+        })
+        .collect();
+    Ok(import)
+}
+
+fn validate_row(
+    config: &AppConfig,
+    profile: &ConnectionProfile,
+    line: usize,
+    employee: &Result<Employee, String>,
+    date: &str,
+    pay_type: &str,
+    hours: Option<&str>,
+) -> Result<CsvRow, String> {
+    let employee = employee.clone()?;
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%m/%d/%Y"))
+        .map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD", date))?;
+    let pay_type = PayType::iter()
+        .find(|pt| pt.to_string().eq_ignore_ascii_case(pay_type))
+        .or_else(|| profile.pay_type_for_code(pay_type))
+        .ok_or_else(|| {
+            format!(
+                "unknown pay type '{}', expected one of {}",
+                pay_type,
+                PayType::iter()
+                    .map(|pt| pt.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+    let hours = match hours.filter(|h| !h.is_empty()) {
+        Some(hours) => match hours.parse::<f64>() {
+            Ok(hours) if hours > 0.0 && hours < config.full_day_hours() => {
+                return Err(format!(
+                    "{} hours is less than a full day ({}); EBMS changes the pay type of \
+                     the whole day, so change part of a day in EBMS",
+                    hours,
+                    config.full_day_hours()
+                ));
+            }
+            Ok(hours) if hours > 0.0 && hours <= 24.0 => Some(hours),
+            _ => return Err(format!("invalid hours '{}'", hours)),
+        },
+        None => None,
+    };
+    Ok(CsvRow {
+        line,
+        employee,
+        date,
+        pay_type,
+        hours,
+    })
+}
+
+/// What happened to each row, in file order, given the previews or applied results.
+pub fn row_results(
+    rows: &[CsvRow],
+    previews: &[ImportPreview],
+) -> Vec<(usize, Result<String, String>)> {
+    rows.iter()
+        .map(|row| {
+            let preview = previews.iter().find(|p| p.item.lines.contains(&row.line));
+            let result = match preview.map(|p| &p.changes) {
+                None => Err("not applied".to_string()),
+                Some(Err(e)) => Err(e.clone()),
+                Some(Ok(changes)) => {
+                    match changes
+                        .iter()
+                        .find(|c| c.employee == row.employee && c.date == row.date)
+                    {
+                        Some(change) => Ok(change.to_string()),
+                        None => Err(format!(
+                            "no time entry for {} on {}",
+                            row.employee, row.date
+                        )),
+                    }
+                }
+            };
+            (row.line, result)
+        })
+        .collect()
+}
+
+// RFC 4180: fields may be quoted, with "" for a quote and line breaks inside quotes
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
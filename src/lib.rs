@@ -2,16 +2,44 @@ use std::fmt::Display;
 
 use api::format_pay_code;
 use chrono::Datelike;
-use config::AppConfig;
-use conversation_message::{ConversationMessage, FunctionCall};
+use clock::Clock;
+use config::{AppConfig, ConnectionProfile, OfflineRules};
+use conversation_message::{ConversationMessage, FunctionCall, Role};
+use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
 
 mod api;
+pub mod audit;
+pub mod clock;
 pub mod config;
+pub mod context;
 pub mod conversation_message;
+pub mod dates;
+mod directory;
+pub mod eval;
+pub mod export;
 mod gpt;
+pub mod holidays;
+pub mod ics;
+pub mod import;
+pub mod logging;
+pub mod offline;
+pub mod plan;
+pub mod progress;
+pub mod prompt;
+pub mod schema;
+pub mod usage;
 
-#[derive(EnumIter, Debug, Clone)]
+/// The function the model calls to turn the user's wording into dates.
+pub const RESOLVE_DATES: &str = "resolve_dates";
+/// The function the model calls to look up company holidays.
+pub const LIST_HOLIDAYS: &str = "list_holidays";
+/// How many lookups one prompt may make before the model must decide.
+pub const MAX_TOOL_STEPS: usize = 4;
+// Times the model is asked to fix a function call whose arguments don't fit its schema
+const MAX_REPAIRS: usize = 2;
+
+#[derive(EnumIter, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PayType {
     Sick,
     Vacation,
@@ -35,61 +63,112 @@ impl std::str::FromStr for PayType {
     }
 }
 
-impl ToString for PayType {
-    fn to_string(&self) -> String {
-        format!("{:?}", self)
+impl Display for PayType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Employee {
+    pub id: String,
+    pub name: String,
+}
+
+impl Display for Employee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name == self.id {
+            write!(f, "{}", self.id)
+        } else {
+            write!(f, "{} ({})", self.name, self.id)
+        }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayTypeChange {
+    pub employee: Employee,
     pub date: chrono::NaiveDate,
     pub old_pay_type: String,
     pub pay_type: PayType,
+    /// The PAY_LEVEL code `pay_type` was written as; empty in records from before pay codes
+    /// could be configured, which used the built-in codes
+    #[serde(default)]
+    pub pay_code: String,
     pub function_call: Option<FunctionCall>,
 }
 
 impl Display for PayTypeChange {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let now = chrono::Local::now().naive_local().date();
-        let formatted_date = if self.date.year() == now.year() {
-            format!("{}", self.date.format("%a %B %d"))
+        self.write_summary(f, "%a %B %d, %Y")
+    }
+}
+
+impl PayTypeChange {
+    /// Like `to_string`, but leaves out the year for dates in the current year.
+    pub fn describe(&self, today: chrono::NaiveDate) -> String {
+        let date_format = if self.date.year() == today.year() {
+            "%a %B %d"
         } else {
-            format!("{}", self.date.format("%a %B %d, %Y"))
+            "%a %B %d, %Y"
         };
+        let mut summary = String::new();
+        // Writing to a String can't fail
+        let _ = self.write_summary(&mut summary, date_format);
+        summary
+    }
 
+    fn write_summary(&self, f: &mut impl std::fmt::Write, date_format: &str) -> std::fmt::Result {
+        let formatted_date = self.date.format(date_format);
         let from = self.old_pay_type.to_string();
-        let to = format_pay_code(&self.pay_type);
+        let to = self.new_pay_code();
         if from == to {
             return write!(
                 f,
-                "Pay type for {} was already set to {}",
-                formatted_date, from
+                "{}: pay type for {} was already set to {}",
+                self.employee, formatted_date, from
             );
         }
         write!(
             f,
-            "Set pay type for {} from {} to {}",
-            formatted_date, from, to,
+            "{}: set pay type for {} from {} to {}",
+            self.employee, formatted_date, from, to,
         )
     }
-}
 
-impl PayTypeChange {
+    fn new_pay_code(&self) -> &str {
+        if self.pay_code.is_empty() {
+            format_pay_code(&self.pay_type)
+        } else {
+            &self.pay_code
+        }
+    }
+
     pub fn get_function_call(&self) -> Option<String> {
         let from = self.old_pay_type.to_string();
-        let to = format_pay_code(&self.pay_type);
+        let to = self.new_pay_code();
         if from == to {
             return None;
         }
-        return Some(format!(
-            "I set the pay type for {} from {} to {}",
+        Some(format!(
+            "I set the pay type for {} on {} from {} to {}",
+            self.employee,
             self.date.format("%a %B %d, %Y"),
             from,
             to,
-        ));
+        ))
     }
 }
 
+/// A day's time detail as stored in EBMS.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeEntry {
+    pub employee: Employee,
+    pub date: chrono::NaiveDate,
+    pub pay_code: String,
+    pub pay_type: Option<PayType>,
+}
+
 pub enum AgentResponse {
     FunctionCall(FunctionCall),
     Message(String),
@@ -97,68 +176,712 @@ pub enum AgentResponse {
 
 pub enum ExecutionResult {
     Success(Vec<PayTypeChange>),
+    /// A dry run: the changes the function call would make, nothing was written to EBMS
+    Planned(FunctionCall, Vec<PayTypeChange>),
     Message(String),
 }
 
 pub enum ExecutionError {
     AgentError(String),
     EbmsError(String),
+    /// The user may not change the employee's time
+    PermissionDenied(String),
+    /// The request can't be carried out as asked, e.g. a range that's too long
+    InvalidRequest(String),
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionError::AgentError(msg) => write!(f, "Agent Error: {}", msg),
+            ExecutionError::EbmsError(msg) => write!(f, "EBMS: {}", msg),
+            ExecutionError::PermissionDenied(msg) | ExecutionError::InvalidRequest(msg) => {
+                write!(f, "{}", msg)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ExecutionOptions {
+    /// Work out the changes without writing them to EBMS
+    pub dry_run: bool,
+    /// Decides what "today" is for relative dates
+    pub clock: Clock,
+    /// Streams the model's replies, passing their text here as it arrives
+    pub on_text: Option<TextSink>,
+    /// Told about each step as it happens
+    pub on_progress: Option<progress::ProgressSink>,
+}
+
+impl ExecutionOptions {
+    pub(crate) fn report(&self, event: progress::ProgressEvent) {
+        if let Some(sink) = &self.on_progress {
+            (sink.0)(&event);
+        }
+    }
 }
 
+/// Receives the model's reply text piece by piece.
+#[derive(Clone)]
+pub struct TextSink(pub std::sync::Arc<dyn Fn(&str) + Send + Sync>);
+
+impl std::fmt::Debug for TextSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TextSink")
+    }
+}
+
+#[tracing::instrument(
+    name = "prompt",
+    skip_all,
+    fields(profile = config.current_profile.as_deref(), dry_run = options.dry_run)
+)]
 pub async fn execute_prompt(
     config: &AppConfig,
     prompt: &str,
-    conversation: &Vec<ConversationMessage>,
+    conversation: &[ConversationMessage],
+    options: &ExecutionOptions,
 ) -> Result<ExecutionResult, ExecutionError> {
-    println!("{}", format!("Calling GPT with prompt: {}", prompt));
+    match agent_response(config, prompt, conversation, options).await? {
+        AgentResponse::Message(content) => Ok(ExecutionResult::Message(content)),
+        AgentResponse::FunctionCall(function_call) => {
+            execute_function_call(config, &function_call, options).await
+        }
+    }
+}
 
-    let gpt_result = gpt::call_gpt(&config.gpt_api_key, &prompt, conversation).await;
+/// Asks the model what to do with the prompt without acting on it.
+/// Dates the model asks to have resolved are answered here and the model is asked again.
+pub async fn agent_response(
+    config: &AppConfig,
+    prompt: &str,
+    conversation: &[ConversationMessage],
+    options: &ExecutionOptions,
+) -> Result<AgentResponse, ExecutionError> {
+    if config.offline_rules == OfflineRules::Always {
+        return offline_response(config, options, prompt, None);
+    }
+    tracing::info!(prompt, "Asking the model");
 
-    match gpt_result {
-        Err(e) => return Err(ExecutionError::AgentError(e.to_string())),
-        Ok(AgentResponse::Message(content)) => return Ok(ExecutionResult::Message(content)),
-        Ok(AgentResponse::FunctionCall(function_call)) => {
-            match handle_api_call(config, &function_call).await {
-                Ok(response) => Ok(ExecutionResult::Success(response)),
-                Err(e) => Err(ExecutionError::EbmsError(e)),
+    let template = prompt::PromptTemplate::load(config);
+    let variables =
+        prompt::PromptVariables::new(config, options.clock.today(config), &user_name(config));
+    let system_prompt = template.render(&variables);
+    // Callers normally fit the conversation already; this only catches one that grew unchecked
+    let mut messages = context::trim(conversation, config.context_token_budget()).messages;
+    messages.push(ConversationMessage::new_content(
+        Role::User,
+        prompt.to_string(),
+    ));
+
+    let mut usage: Option<usage::Usage> = None;
+    let (mut lookups, mut repairs) = (0, 0);
+    for step in 1.. {
+        options.report(progress::ProgressEvent::ModelRequest { step });
+        let reply = match &options.on_text {
+            Some(sink) => {
+                gpt::call_gpt_streaming(
+                    config,
+                    &options.clock,
+                    &system_prompt,
+                    &messages,
+                    sink.0.as_ref(),
+                )
+                .await
+            }
+            None => gpt::call_gpt(config, &options.clock, &system_prompt, &messages).await,
+        };
+        let (response, call_usage) = match reply {
+            Ok(reply) => reply,
+            Err(e) if config.offline_rules == OfflineRules::Fallback => {
+                tracing::warn!("The model failed, trying the offline rules: {}", e);
+                return offline_response(config, options, prompt, Some(e.to_string()));
+            }
+            Err(e) => return Err(ExecutionError::AgentError(e.to_string())),
+        };
+        if let Some(call_usage) = call_usage {
+            *usage.get_or_insert_default() += call_usage;
+        }
+        options.report(progress::ProgressEvent::ModelResponse {
+            function: match &response {
+                AgentResponse::FunctionCall(call) => Some(call.name.clone()),
+                AgentResponse::Message(_) => None,
+            },
+        });
+        if let AgentResponse::FunctionCall(call) = &response
+            && let Err(problem) = schema::check_call(config, call)
+        {
+            if repairs == MAX_REPAIRS {
+                return Err(ExecutionError::AgentError(format!(
+                    "The model couldn't give valid arguments for {}: {}",
+                    call.name, problem
+                )));
+            }
+            repairs += 1;
+            tracing::warn!(function = %call.name, arguments = %call.arguments, %problem, "Invalid arguments");
+            options.report(progress::ProgressEvent::Repairing {
+                function: call.name.clone(),
+                problem: problem.clone(),
+            });
+            messages.push(ConversationMessage::new_function_call(
+                call.clone(),
+                String::new(),
+            ));
+            messages.push(ConversationMessage::new_function_result(
+                &call.name,
+                serde_json::json!({
+                    "error": format!("Invalid arguments: {}. Call {} again with them fixed.", problem, call.name)
+                })
+                .to_string(),
+            ));
+            continue;
+        }
+        match response {
+            AgentResponse::FunctionCall(call) if is_lookup(&call.name) => {
+                if lookups == MAX_TOOL_STEPS {
+                    break;
+                }
+                lookups += 1;
+                // Errors go back to the model so it can rephrase the request
+                let result = run_lookup(config, &options.clock, &call)
+                    .unwrap_or_else(|e| serde_json::json!({ "error": e }));
+                tracing::debug!(
+                    function = %call.name,
+                    arguments = %call.arguments,
+                    %result,
+                    "Looked up"
+                );
+                messages.push(ConversationMessage::new_function_call(
+                    call.clone(),
+                    String::new(),
+                ));
+                messages.push(ConversationMessage::new_function_result(
+                    &call.name,
+                    result.to_string(),
+                ));
+            }
+            AgentResponse::FunctionCall(call) => {
+                return Ok(AgentResponse::FunctionCall(FunctionCall {
+                    prompt_version: Some(template.version),
+                    usage,
+                    ..call
+                }));
             }
+            response => return Ok(response),
         }
     }
+    Err(ExecutionError::AgentError(
+        "The agent kept looking things up without deciding what to change".to_string(),
+    ))
 }
 
-async fn handle_api_call(
+/// The offline rules' reading of the prompt, used instead of the model's. `reason` is why
+/// the model wasn't asked, if it was meant to be.
+fn offline_response(
     config: &AppConfig,
+    options: &ExecutionOptions,
+    prompt: &str,
+    reason: Option<String>,
+) -> Result<AgentResponse, ExecutionError> {
+    tracing::info!(prompt, "Reading the prompt with the offline rules");
+    options.report(progress::ProgressEvent::OfflineRules {
+        reason: reason.clone(),
+    });
+    match offline::parse(config, &options.clock, prompt) {
+        Ok(call) => Ok(AgentResponse::FunctionCall(call)),
+        Err(e) => Err(match reason {
+            Some(reason) => ExecutionError::AgentError(format!(
+                "{}. The offline rules didn't understand the prompt either: {}",
+                reason, e
+            )),
+            // Nothing failed, the prompt needs rewording
+            None => ExecutionError::InvalidRequest(format!(
+                "The offline rules didn't understand the prompt: {}",
+                e
+            )),
+        }),
+    }
+}
+
+/// Who the model is talking to, for the system prompt.
+fn user_name(config: &AppConfig) -> String {
+    match config.profile() {
+        Some(profile) => profile.employee().name,
+        None => "the user".to_string(),
+    }
+}
+
+/// Whether the function only looks something up, so the agent answers it and asks the model again.
+pub fn is_lookup(name: &str) -> bool {
+    name == RESOLVE_DATES || name == LIST_HOLIDAYS
+}
+
+/// Runs a lookup function such as `resolve_dates` and returns what to hand back to the model.
+pub fn run_lookup(
+    config: &AppConfig,
+    clock: &Clock,
     function_call: &FunctionCall,
-) -> Result<Vec<PayTypeChange>, String> {
-    let args: serde_json::Value = serde_json::from_str(&function_call.arguments)
+) -> Result<serde_json::Value, String> {
+    match function_call.name.as_str() {
+        RESOLVE_DATES => resolve_dates(config, clock, &function_call.arguments),
+        LIST_HOLIDAYS => list_holidays(config, clock, &function_call.arguments),
+        name => Err(format!("{} is not a lookup", name)),
+    }
+}
+
+// `{"expression": "next week", "workdays_only": true}`
+// becomes `{"dates": ["2025-06-09", ...], "weekdays": ["Monday", ...]}`
+fn resolve_dates(
+    config: &AppConfig,
+    clock: &Clock,
+    arguments: &str,
+) -> Result<serde_json::Value, String> {
+    let args: serde_json::Value = serde_json::from_str(arguments)
+        .map_err(|e| format!("Failed to parse function call arguments: {}", e))?;
+    let expression = args["expression"]
+        .as_str()
+        .ok_or_else(|| "Missing expression field".to_string())?;
+    let workdays_only = args["workdays_only"].as_bool().unwrap_or(false);
+    let work_days = config.work_days(None);
+
+    let context = dates::DateContext {
+        today: clock.today(config),
+        week_start: config.week_start(),
+        holidays: holidays::HolidayCalendar::load(config),
+    };
+    let dates: Vec<chrono::NaiveDate> = dates::resolve(expression, &context)?
+        .into_iter()
+        .filter(|d| {
+            !workdays_only || (work_days.contains(&d.weekday()) && !context.holidays.is_holiday(*d))
+        })
+        .collect();
+    if dates.is_empty() {
+        return Err(format!("'{}' doesn't include any workdays", expression));
+    }
+
+    Ok(serde_json::json!({
+        "expression": expression,
+        "dates": dates.iter().map(|d| d.format("%Y-%m-%d").to_string()).collect::<Vec<_>>(),
+        "weekdays": dates.iter().map(|d| d.format("%A").to_string()).collect::<Vec<_>>(),
+    }))
+}
+
+// `{"year": 2025}` becomes `{"year": 2025, "holidays": [{"date": "2025-12-25", "name": "Christmas"}]}`;
+// the current year if `year` is left out
+fn list_holidays(
+    config: &AppConfig,
+    clock: &Clock,
+    arguments: &str,
+) -> Result<serde_json::Value, String> {
+    let args: serde_json::Value = serde_json::from_str(arguments)
         .map_err(|e| format!("Failed to parse function call arguments: {}", e))?;
+    let year = match args.get("year") {
+        Some(year) => year
+            .as_i64()
+            .and_then(|y| i32::try_from(y).ok())
+            .ok_or_else(|| "Invalid 'year' field, expected a number".to_string())?,
+        None => clock.today(config).year(),
+    };
+    let holidays = holidays::HolidayCalendar::load(config).in_year(year);
+    Ok(serde_json::json!({ "year": year, "holidays": holidays }))
+}
+
+/// Runs a function call the agent returned earlier, e.g. one confirmed after a dry run.
+#[tracing::instrument(
+    skip_all,
+    fields(function = %function_call.name, dry_run = options.dry_run)
+)]
+pub async fn execute_function_call(
+    config: &AppConfig,
+    function_call: &FunctionCall,
+    options: &ExecutionOptions,
+) -> Result<ExecutionResult, ExecutionError> {
+    match handle_api_call(config, function_call, options).await {
+        Ok(changes) if options.dry_run => {
+            Ok(ExecutionResult::Planned(function_call.clone(), changes))
+        }
+        Ok(changes) => {
+            audit::append(
+                config,
+                &audit::AuditRecord::new(
+                    config.current_profile.as_deref().unwrap_or_default(),
+                    Some(function_call.clone()),
+                    changes.clone(),
+                    None,
+                ),
+            );
+            Ok(ExecutionResult::Success(changes))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Reverts the most recent batch in the audit log for the current profile.
+#[tracing::instrument(skip_all, fields(profile = config.current_profile.as_deref()))]
+pub async fn undo_last(config: &AppConfig) -> Result<ExecutionResult, ExecutionError> {
+    let profile = config.current_profile.as_deref().unwrap_or_default();
+    let Some(record) = audit::last_undoable(config, profile) else {
+        return Ok(ExecutionResult::Message(
+            "There is nothing to undo".to_string(),
+        ));
+    };
+
+    let undone = undo_changes(config, &record.changes).await?;
+    audit::append(
+        config,
+        &audit::AuditRecord::new(profile, None, undone.clone(), Some(record.id)),
+    );
+    Ok(ExecutionResult::Success(undone))
+}
+
+// Sets each changed day back to the pay type it had before
+async fn undo_changes(
+    config: &AppConfig,
+    changes: &[PayTypeChange],
+) -> Result<Vec<PayTypeChange>, ExecutionError> {
+    let profile = config
+        .profile()
+        .ok_or_else(|| ExecutionError::EbmsError("No connection profile selected".to_string()))?;
+    // One set_pay_type call per employee and original pay type
+    let mut groups: Vec<(&Employee, &str, Vec<chrono::NaiveDate>)> = Vec::new();
+    for change in changes.iter().filter(|c| c.get_function_call().is_some()) {
+        match groups
+            .iter_mut()
+            .find(|(e, code, _)| **e == change.employee && *code == change.old_pay_type)
+        {
+            Some((_, _, dates)) => dates.push(change.date),
+            None => groups.push((&change.employee, &change.old_pay_type, vec![change.date])),
+        }
+    }
+    let mut undone = Vec::new();
+    for (employee, old_pay_code, dates) in groups {
+        let pay_type = profile.pay_type_for_code(old_pay_code).ok_or_else(|| {
+            ExecutionError::EbmsError(format!(
+                "Can't undo back to pay code {}, it isn't one the agent can set",
+                old_pay_code
+            ))
+        })?;
+        let function_call = FunctionCall {
+            name: "set_pay_type".to_string(),
+            arguments: serde_json::json!({
+                "dates": dates.iter().map(|d| d.format("%Y-%m-%d").to_string()).collect::<Vec<_>>(),
+                "pay_type": pay_type.to_string(),
+                "employee": employee.id,
+            })
+            .to_string(),
+            prompt_version: None,
+            usage: None,
+        };
+        let changes = handle_api_call(config, &function_call, &ExecutionOptions::default()).await?;
+        undone.extend(changes);
+    }
+    Ok(undone)
+}
+
+/// The time entries for an employee between two dates. `employee` is resolved like the
+/// agent's `employee` argument; `None` means the logged-in employee.
+pub async fn get_time_entries(
+    config: &AppConfig,
+    employee: Option<&str>,
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+) -> Result<Vec<TimeEntry>, ExecutionError> {
+    let profile = config
+        .profile()
+        .ok_or_else(|| ExecutionError::EbmsError("No connection profile selected".to_string()))?;
+    let employee = directory::resolve_employee(profile, employee).await?;
+    let pytmdets = api::get_pytmdets_between(profile, &employee.id, start, end)
+        .await
+        .map_err(|e| ExecutionError::EbmsError(e.to_string()))?;
+
+    let mut entries: Vec<TimeEntry> = pytmdets
+        .iter()
+        .filter_map(|d| {
+            Some(TimeEntry {
+                employee: employee.clone(),
+                date: d.get_date()?,
+                pay_code: d.pay_type.clone(),
+                pay_type: profile.pay_type_for_code(&d.pay_type),
+            })
+        })
+        .collect();
+    entries.sort_by_key(|e| e.date);
+    Ok(entries)
+}
+
+/// The logged-in employee followed by everyone EBMS lists them as the supervisor of, i.e. the
+/// people whose time they may change.
+pub async fn get_team(config: &AppConfig) -> Result<Vec<Employee>, ExecutionError> {
+    let profile = config
+        .profile()
+        .ok_or_else(|| ExecutionError::EbmsError("No connection profile selected".to_string()))?;
+    let mut team = directory::team(profile)
+        .await
+        .map_err(ExecutionError::EbmsError)?;
+    team.sort_by_key(|e| !e.id.eq_ignore_ascii_case(profile.employee_id.trim()));
+    Ok(team)
+}
 
-    let date_values = args["dates"]
-        .as_array()
-        .ok_or_else(|| "Missing or invalid 'dates' field, expected array".to_string())?;
+/// Checks the profile can reach EBMS by looking up its own employee, e.g. after changing settings.
+pub async fn test_connection(config: &AppConfig) -> Result<Employee, ExecutionError> {
+    let profile = config
+        .profile()
+        .ok_or_else(|| ExecutionError::EbmsError("No connection profile selected".to_string()))?;
+    let employees = api::get_employees(profile, std::slice::from_ref(&profile.employee_id))
+        .await
+        .map_err(|e| ExecutionError::EbmsError(e.to_string()))?;
+    employees.into_iter().next().ok_or_else(|| {
+        ExecutionError::EbmsError(format!(
+            "Connected, but EBMS has no employee {}",
+            profile.employee_id
+        ))
+    })
+}
+
+/// Looks up the profile's `employee_name` in EBMS if it isn't known yet. Front ends call this
+/// once per session, so prompts don't each ask EBMS who the user is.
+pub async fn load_employee_name(profile: &mut ConnectionProfile) -> Result<(), ExecutionError> {
+    if !profile.employee_name.trim().is_empty() {
+        return Ok(());
+    }
+    let employees = api::get_employees(profile, std::slice::from_ref(&profile.employee_id))
+        .await
+        .map_err(|e| ExecutionError::EbmsError(e.to_string()))?;
+    if let Some(employee) = employees.into_iter().next() {
+        profile.employee_name = employee.name;
+    }
+    Ok(())
+}
+
+/// The functions offered to the model, as name, description and JSON schema `parameters`.
+pub fn function_definitions(config: &AppConfig) -> Vec<serde_json::Value> {
+    gpt::get_functions_metadata(config)
+}
+
+/// The JSON shape front ends use to report a prompt's outcome.
+pub fn result_to_json(result: &Result<ExecutionResult, ExecutionError>) -> serde_json::Value {
+    match result {
+        Ok(ExecutionResult::Message(msg)) => {
+            serde_json::json!({ "status": "message", "message": msg })
+        }
+        Ok(ExecutionResult::Success(changes)) => {
+            serde_json::json!({ "status": "applied", "changes": changes })
+        }
+        Ok(ExecutionResult::Planned(_, changes)) => {
+            serde_json::json!({ "status": "planned", "changes": changes })
+        }
+        Err(e) => {
+            let kind = match e {
+                ExecutionError::AgentError(_) => "agent",
+                ExecutionError::EbmsError(_) => "ebms",
+                ExecutionError::PermissionDenied(_) => "permission",
+                ExecutionError::InvalidRequest(_) => "invalid",
+            };
+            serde_json::json!({ "status": "error", "kind": kind, "error": e.to_string() })
+        }
+    }
+}
+
+/// The conversation to send with the next prompt, or `None` to keep the current one.
+/// Chat replies are appended so the agent can ask for clarification. Applied changes
+/// replace the conversation with what actually happened, which lets the agent undo them.
+pub fn next_conversation(
+    conversation: &[ConversationMessage],
+    prompt: &str,
+    result: &Result<ExecutionResult, ExecutionError>,
+) -> Option<Vec<ConversationMessage>> {
+    match result {
+        Ok(ExecutionResult::Message(msg)) => {
+            let mut new_conversation = conversation.to_vec();
+            new_conversation.push(ConversationMessage::new_content(
+                Role::User,
+                prompt.to_string(),
+            ));
+            new_conversation.push(ConversationMessage::new_content(
+                Role::Assistant,
+                msg.clone(),
+            ));
+            Some(new_conversation)
+        }
+        Ok(ExecutionResult::Success(changes)) => Some(
+            changes
+                .iter()
+                .filter_map(|change| {
+                    change.function_call.as_ref().map(|function_call| {
+                        ConversationMessage::new_function_call(
+                            function_call.clone(),
+                            change.to_string(),
+                        )
+                    })
+                })
+                .collect(),
+        ),
+        Ok(ExecutionResult::Planned(..)) | Err(_) => None,
+    }
+}
+
+/// Like [`next_conversation`], then fitted to the configured context budget.
+pub async fn next_conversation_window(
+    config: &AppConfig,
+    clock: &Clock,
+    conversation: &[ConversationMessage],
+    prompt: &str,
+    result: &Result<ExecutionResult, ExecutionError>,
+) -> Option<context::ContextWindow> {
+    let next = next_conversation(conversation, prompt, result)?;
+    let window = context::fit(config, clock, &next).await;
+    if window.removed > 0 {
+        tracing::info!(
+            "{} {} earlier messages to stay within {} tokens",
+            if window.summarized {
+                "Summarized"
+            } else {
+                "Dropped"
+            },
+            window.removed,
+            config.context_token_budget()
+        );
+    }
+    Some(window)
+}
+
+/// The days a start/end range of `pay_type` changes. Days off are left out unless
+/// `workdays_only` is false; holiday pay is what goes on holidays, everything else skips them.
+pub fn range_days(
+    config: &AppConfig,
+    employee_id: Option<&str>,
+    pay_type: &PayType,
+    (start, end): (chrono::NaiveDate, chrono::NaiveDate),
+    workdays_only: bool,
+) -> Vec<chrono::NaiveDate> {
+    let work_days = config.work_days(employee_id);
+    let calendar = holidays::HolidayCalendar::load(config);
+    start
+        .iter_days()
+        .take_while(|d| *d <= end)
+        .filter(|d| !workdays_only || work_days.contains(&d.weekday()))
+        .filter(|d| matches!(pay_type, PayType::Holiday) || !calendar.is_holiday(*d))
+        .collect()
+}
+
+fn parse_date(value: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date format, expected YYYY-MM-DD: {}", e))
+}
+
+async fn handle_api_call(
+    config: &AppConfig,
+    function_call: &FunctionCall,
+    options: &ExecutionOptions,
+) -> Result<Vec<PayTypeChange>, ExecutionError> {
+    let profile = config
+        .profile()
+        .ok_or_else(|| ExecutionError::EbmsError("No connection profile selected".to_string()))?;
+
+    // Reports every problem at once, not just the first field that fails to parse below
+    let args = schema::check_call(config, function_call).map_err(|e| {
+        ExecutionError::InvalidRequest(format!("Invalid {} arguments: {}", function_call.name, e))
+    })?;
+    let invalid = ExecutionError::InvalidRequest;
 
     let mut dates = Vec::new();
-    for date_val in date_values {
-        let date_str = date_val
-            .as_str()
-            .ok_or_else(|| "Invalid date value in 'dates' array".to_string())?;
-        let date = chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
-            .map_err(|e| format!("Invalid date format, expected YYYY-MM-DD: {}", e))?;
-        dates.push(date);
+    if let Some(date_values) = args.get("dates") {
+        let date_values = date_values
+            .as_array()
+            .ok_or_else(|| invalid("Invalid 'dates' field, expected array".to_string()))?;
+        for date_val in date_values {
+            let date_str = date_val
+                .as_str()
+                .ok_or_else(|| invalid("Invalid date value in 'dates' array".to_string()))?;
+            dates.push(parse_date(date_str).map_err(invalid)?);
+        }
+    }
+
+    let range = match (args["start"].as_str(), args["end"].as_str()) {
+        (Some(start), Some(end)) => Some((
+            parse_date(start).map_err(invalid)?,
+            parse_date(end).map_err(invalid)?,
+        )),
+        (None, None) => None,
+        _ => {
+            return Err(invalid(
+                "'start' and 'end' must be given together".to_string(),
+            ));
+        }
+    };
+    if dates.is_empty() && range.is_none() {
+        return Err(invalid(
+            "Missing dates: give 'dates' or a 'start' and 'end'".to_string(),
+        ));
     }
 
     let pay_type_str = args["pay_type"]
         .as_str()
-        .ok_or_else(|| "Missing pay_type field".to_string())?;
-    let pay_type = pay_type_str
-        .parse::<PayType>()
-        .map_err(|_| format!("Invalid pay type returned from agent: {}", pay_type_str))?;
-
-    println!("Setting pay type '{}' for dates {:?}", pay_type_str, dates);
-    // Serialize the GPT function call to a JSON string for logging or debugging
-    let result = api::set_pay_type(config, &dates, &pay_type, &function_call)
-        .await
-        .map_err(|e| e.to_string())?;
+        .ok_or_else(|| invalid("Missing pay_type field".to_string()))?;
+    let pay_type = pay_type_str.parse::<PayType>().map_err(|_| {
+        invalid(format!(
+            "Invalid pay type returned from agent: {}",
+            pay_type_str
+        ))
+    })?;
+
+    let employee = directory::resolve_employee(profile, args["employee"].as_str()).await?;
+
+    if let Some((start, end)) = range {
+        if end < start {
+            return Err(invalid(format!(
+                "The range ends ({}) before it starts ({})",
+                end, start
+            )));
+        }
+        let span = (end - start).num_days() + 1;
+        if span > i64::from(config.max_range_days()) {
+            return Err(invalid(format!(
+                "{} to {} spans {} days, more than the {} one change may cover; split it into \
+                 ranges of at most {} days",
+                start,
+                end,
+                span,
+                config.max_range_days(),
+                config.max_range_days()
+            )));
+        }
+        let workdays_only = args["workdays_only"].as_bool().unwrap_or(true);
+        let expanded = range_days(
+            config,
+            Some(&employee.id),
+            &pay_type,
+            (start, end),
+            workdays_only,
+        );
+        if expanded.is_empty() && dates.is_empty() {
+            return Err(invalid(format!(
+                "{} to {} has no work days for {}",
+                start, end, employee
+            )));
+        }
+        dates.extend(expanded);
+    }
+    dates.sort();
+    dates.dedup();
+
+    tracing::info!(
+        "Setting pay type '{}' for employee {} on dates {:?}",
+        pay_type_str,
+        employee.id,
+        dates
+    );
+    let result = api::set_pay_type(
+        profile,
+        &employee,
+        &dates,
+        &pay_type,
+        function_call,
+        options,
+    )
+    .await
+    .map_err(|e| ExecutionError::EbmsError(e.to_string()))?;
 
     Ok(result)
 }
@@ -0,0 +1,290 @@
+//! Logging through `tracing`. Warnings go to stderr; everything at the configured level goes
+//! to a daily log file in `logs` next to the audit log and to a buffer of recent lines the app
+//! shows. Passwords, API keys and bearer tokens are masked before anything is written.
+
+use crate::config::{AppConfig, config_dir};
+use std::{collections::VecDeque, io::Write, path::PathBuf, sync::Mutex};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    EnvFilter, Layer, filter::LevelFilter, fmt::MakeWriter, layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
+
+/// Overrides the configured level, with `tracing` filter directives such as "agent=debug".
+/// When set, stderr shows everything it lets through too.
+pub const LOG_ENV: &str = "AGENT_LOG";
+
+const LOG_DIR: &str = "logs";
+const LOG_FILE_PREFIX: &str = "agent";
+const KEPT_LOG_FILES: usize = 14;
+const RECENT_LINES: usize = 500;
+const REDACTED: &str = "[REDACTED]";
+
+// Keys whose values are masked wherever they appear as `key: value`, `"key": "value"` or
+// `key=value`; matched at the end of longer keys too, e.g. ebms_password
+const SECRET_KEYS: [&str; 6] = [
+    "password",
+    "api_key",
+    "apikey",
+    "token",
+    "secret",
+    "authorization",
+];
+// Credentials that follow an authentication scheme, as in an Authorization header
+const SCHEMES: [&str; 2] = ["bearer ", "basic "];
+
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Starts logging for the process. Later calls only update the secrets to mask.
+pub fn init(config: &AppConfig) {
+    register_secrets(config);
+    let (filter, stderr_level) = match EnvFilter::try_from_env(LOG_ENV) {
+        Ok(filter) => (filter, LevelFilter::TRACE),
+        Err(_) => (EnvFilter::new(config.log_level()), LevelFilter::WARN),
+    };
+
+    let file = log_dir(config).and_then(|dir| {
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix("log")
+            .max_log_files(KEPT_LOG_FILES)
+            .build(&dir)
+            .inspect_err(|e| eprintln!("Failed to open the log in {}: {}", dir.display(), e))
+            .ok()
+    });
+    let file_layer = file.map(|appender| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(Redacting(appender))
+    });
+
+    let result = tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(Redacting(std::io::stderr))
+                .with_filter(stderr_level),
+        )
+        .with(file_layer)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(Redacting(RecentLogs)),
+        )
+        .try_init();
+    if result.is_err() {
+        tracing::debug!("Logging was already started");
+    }
+}
+
+/// `logs` next to the audit log, else in the config directory.
+pub fn log_dir(config: &AppConfig) -> Option<PathBuf> {
+    match &config.audit_log_path {
+        Some(path) => Some(path.with_file_name(LOG_DIR)),
+        None => config_dir().map(|dir| dir.join(LOG_DIR)),
+    }
+}
+
+/// Masks the config's API key and EBMS passwords wherever they appear, in addition to the
+/// patterns [`redact`] always masks.
+pub fn register_secrets(config: &AppConfig) {
+    add_secret(&config.gpt_api_key);
+    for profile in &config.profiles {
+        add_secret(&profile.ebms_password);
+    }
+}
+
+/// Masks this value wherever it appears, e.g. a server's access token.
+pub fn add_secret(secret: &str) {
+    let secret = secret.trim();
+    // Anything shorter would mask ordinary words
+    if secret.len() < 4 {
+        return;
+    }
+    let mut secrets = SECRETS.lock().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+        // Longest first, so a secret containing another is masked whole
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// The text with registered secrets, credentials after "Bearer" or "Basic", values of keys
+/// such as "password" or "api_key", and OpenAI-style "sk-" keys replaced by `[REDACTED]`.
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for secret in SECRETS.lock().unwrap().iter() {
+        text = text.replace(secret.as_str(), REDACTED);
+    }
+    for scheme in SCHEMES {
+        text = mask(&text, scheme, |_, rest| {
+            let (start, end) = token(rest);
+            // Not "basic pay"
+            (end - start >= 8).then_some((start, end))
+        });
+    }
+    for key in SECRET_KEYS {
+        text = mask(&text, key, |_, rest| keyed_value(rest));
+    }
+    mask(&text, "sk-", |before, rest| {
+        let starts_word = !before.ends_with(|c: char| c.is_alphanumeric() || c == '_');
+        let (start, end) = token(rest);
+        (starts_word && end - start >= 16).then_some((start, end))
+    })
+}
+
+/// The most recent log lines, oldest first.
+pub fn recent() -> Vec<String> {
+    RECENT.lock().unwrap().iter().cloned().collect()
+}
+
+// Replaces a value after each case-insensitive match of `needle`. `value_at` gets the text
+// before the match and after it, and returns where the value is in the text after it.
+fn mask(
+    text: &str,
+    needle: &str,
+    value_at: impl Fn(&str, &str) -> Option<(usize, usize)>,
+) -> String {
+    // ASCII lowercasing keeps every byte offset the same
+    let lower = text.to_ascii_lowercase();
+    let mut masked = String::new();
+    let mut copied = 0;
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(needle) {
+        let at = search + found;
+        let after = at + needle.len();
+        search = after;
+        let Some((start, end)) = value_at(&text[..at], &text[after..]) else {
+            continue;
+        };
+        let (start, end) = (after + start, after + end);
+        if start < end && &text[start..end] != REDACTED {
+            masked.push_str(&text[copied..start]);
+            masked.push_str(REDACTED);
+            copied = end;
+            search = end;
+        }
+    }
+    masked.push_str(&text[copied..]);
+    masked
+}
+
+// The run of characters up to the next space, quote or separator
+fn token(rest: &str) -> (usize, usize) {
+    let start = rest.len() - rest.trim_start_matches(' ').len();
+    let end = rest[start..]
+        .find(|c: char| c.is_whitespace() || "\"',;&})]".contains(c))
+        .map_or(rest.len(), |end| start + end);
+    (start, end)
+}
+
+// The value after a key: `": "value"`, `: value` or `=value`
+fn keyed_value(rest: &str) -> Option<(usize, usize)> {
+    let bytes = rest.as_bytes();
+    let mut i = 0;
+    if matches!(bytes.get(i), Some(b'"' | b'\'')) {
+        i += 1;
+    }
+    while bytes.get(i) == Some(&b' ') {
+        i += 1;
+    }
+    if !matches!(bytes.get(i), Some(b':' | b'=')) {
+        return None;
+    }
+    i += 1;
+    while bytes.get(i) == Some(&b' ') {
+        i += 1;
+    }
+    match bytes.get(i) {
+        Some(&quote @ (b'"' | b'\'')) => {
+            let start = i + 1;
+            let end = rest[start..]
+                .find(quote as char)
+                .map_or(rest.len(), |end| start + end);
+            Some((start, end))
+        }
+        // "Authorization: Bearer ..." is masked after the scheme instead
+        _ if SCHEMES.iter().any(|scheme| {
+            rest.get(i..i + scheme.len())
+                .is_some_and(|word| word.eq_ignore_ascii_case(scheme))
+        }) =>
+        {
+            None
+        }
+        _ => {
+            let (start, end) = token(&rest[i..]);
+            Some((i + start, i + end))
+        }
+    }
+}
+
+// Collects what a log event writes and passes it on redacted, in one piece
+struct Redacting<M>(M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.0.make_writer(),
+            buffer: Vec::new(),
+        }
+    }
+}
+
+struct RedactingWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.buffer.is_empty() {
+            let text = redact(&String::from_utf8_lossy(&self.buffer));
+            self.buffer.clear();
+            self.inner.write_all(text.as_bytes())?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for RedactingWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+// Keeps the last lines for the app's log window
+struct RecentLogs;
+
+impl Write for RecentLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut recent = RECENT.lock().unwrap();
+        for line in String::from_utf8_lossy(buf).lines() {
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl MakeWriter<'_> for RecentLogs {
+    type Writer = RecentLogs;
+
+    fn make_writer(&self) -> Self::Writer {
+        RecentLogs
+    }
+}
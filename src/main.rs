@@ -1,12 +1,29 @@
 use agent::{
-    ExecutionError,
-    config::{AppConfig, load_config, save_config},
-    conversation_message::{ConversationMessage, Role},
+    Employee, ExecutionOptions, PayType, TextSink, TimeEntry,
+    clock::Clock,
+    config::{
+        AppConfig, ConnectionProfile, ContextPolicy, OfflineRules, ToolCalling, load_config,
+        save_config,
+    },
+    context,
+    conversation_message::{ConversationMessage, FunctionCall, Role},
+    dates,
+    holidays::{Holiday, HolidayCalendar},
+    import::{self, CsvRow, IcsImportOptions, ImportPreview, ImportRule},
+    logging,
+    progress::{ProgressEvent, ProgressSink},
+    prompt, usage,
 };
+use chrono::{DateTime, Duration, Local, NaiveDate, Weekday};
 use eframe::egui::{self, Id, RichText};
-use std::sync::{Arc, Mutex};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use strum::IntoEnumIterator;
 
 fn main() {
+    logging::init(&load_config());
     let options = eframe::NativeOptions::default();
     if let Err(e) = eframe::run_native(
         "Personal Agent",
@@ -17,7 +34,7 @@ fn main() {
             ))
         }),
     ) {
-        eprintln!("Failed to launch the app: {}", e);
+        tracing::error!("Failed to launch the app: {}", e);
     }
 }
 
@@ -25,6 +42,8 @@ struct AgentApp {
     pub config: AppConfig,
 
     // Login form fields
+    profile_name: String,
+    production: bool,
     ebms_url: String,
     username: String,
     password: String,
@@ -38,24 +57,290 @@ struct AgentApp {
     pub output: Arc<Mutex<Vec<RichText>>>,
     current_conversation: Arc<Mutex<Vec<ConversationMessage>>>, //this allows you to chat with the agent but gets cleared on successful changes
     is_working: Arc<Mutex<bool>>,
+    show_context: bool,
+    show_logs: bool,
+    session_start: DateTime<Local>,
+    /// Token and cost totals for the status bar, refreshed after each prompt
+    usage_status: Arc<Mutex<String>>,
+    /// Earlier messages dropped or summarized to keep the conversation within budget
+    context_removed: Arc<Mutex<usize>>,
+    /// The step the running prompt is on, shown under the prompt box
+    progress: Arc<Mutex<Option<String>>>,
+
+    // calendar import window
+    show_import: bool,
+    import_path: String,
+    /// Employee ID calendar events are imported for; the logged-in user if `None`
+    import_employee: Option<String>,
+    /// First and last day of calendar events to import, as YYYY-MM-DD; unbounded if empty
+    import_from: String,
+    import_to: String,
+    import_preview: Arc<Mutex<Option<ImportState>>>,
+
+    // settings window, editing a copy of the config until it's saved
+    settings: Option<SettingsState>,
+    connection_test: Arc<Mutex<Option<Result<String, String>>>>,
+
+    // timesheet grid
+    grid_week: NaiveDate,
+    /// Employee ID shown in the grid; the logged-in user if `None`
+    grid_employee: Option<String>,
+    timesheet: Arc<Mutex<TimesheetState>>,
+    /// Set whenever EBMS may have changed, so the grid reloads on the next frame
+    timesheet_stale: Arc<Mutex<bool>>,
+    /// Whether the user's name and team have been looked up since logging in
+    session_requested: bool,
+    /// A name looked up in EBMS, as profile, employee ID and name, waiting to be saved
+    loaded_name: Arc<Mutex<Option<(String, String, String)>>>,
+    /// The user followed by the people EBMS lists them as the supervisor of
+    team: Arc<Mutex<Vec<Employee>>>,
+}
+
+/// The grid's time entries, loaded in the background.
+#[derive(Default)]
+struct TimesheetState {
+    /// Week start and employee of the latest load; older loads still running are dropped
+    requested: Option<(NaiveDate, Option<String>)>,
+    /// `None` while loading
+    entries: Option<Result<Vec<TimeEntry>, String>>,
+    /// Read along with the entries rather than on every frame, as it may come from an ICS file
+    holidays: HolidayCalendar,
+}
+
+/// The settings window's fields. Lists are edited as text, one entry per line.
+struct SettingsState {
+    config: AppConfig,
+    profile: ConnectionProfile,
+    work_days: Vec<Weekday>,
+    /// "E100: Mon, Tue, Wed"
+    employee_work_days: String,
+    /// "2025-12-25 Christmas Day"
+    company_holidays: String,
+    holiday_calendar_path: String,
+    audit_log_path: String,
+    /// "Doctor = Sick"
+    import_rules: String,
+    system_prompt_path: String,
+    /// "gpt-4o: 2.50 / 10.00", dollars per million prompt / completion tokens
+    model_rates: String,
+    /// What was wrong with the settings when Save was last pressed; checked then rather than
+    /// every frame, as checking reads the disk
+    problems: Vec<String>,
+}
+
+impl SettingsState {
+    fn new(config: &AppConfig) -> Self {
+        let profile = config.profile().cloned().unwrap_or_default();
+        SettingsState {
+            work_days: config.work_days(None),
+            employee_work_days: config
+                .employee_work_days
+                .iter()
+                .map(|(id, days)| format!("{}: {}", id, days.join(", ")))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            company_holidays: config
+                .company_holidays
+                .iter()
+                .map(|h| format!("{} {}", h.date, h.name).trim().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            holiday_calendar_path: path_text(&config.holiday_calendar_path),
+            audit_log_path: path_text(&config.audit_log_path),
+            system_prompt_path: path_text(&config.system_prompt_path),
+            model_rates: config
+                .model_rates
+                .iter()
+                .map(|(model, rate)| {
+                    format!(
+                        "{}: {} / {}",
+                        model, rate.prompt_per_million, rate.completion_per_million
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            import_rules: config
+                .import_rules
+                .iter()
+                .map(|r| format!("{} = {}", r.pattern, r.pay_type))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            profile,
+            config: config.clone(),
+            problems: Vec::new(),
+        }
+    }
+
+    /// The config with these settings, or what's wrong with them.
+    fn to_config(&self) -> Result<AppConfig, Vec<String>> {
+        let mut problems = Vec::new();
+        let mut config = self.config.clone();
+        let mut profile = self.profile.clone();
+        profile.pay_codes.retain(|_, code| !code.trim().is_empty());
+        config.upsert_profile(profile);
+
+        config.work_days = self.work_days.iter().map(|d| d.to_string()).collect();
+        config.employee_work_days.clear();
+        for line in lines(&self.employee_work_days) {
+            match line.split_once(':') {
+                Some((id, days)) if !id.trim().is_empty() => {
+                    config
+                        .employee_work_days
+                        .insert(id.trim().to_string(), split_list(days));
+                }
+                _ => problems.push(format!("Expected 'employee ID: days' in '{}'", line)),
+            }
+        }
+        config.company_holidays.clear();
+        for line in lines(&self.company_holidays) {
+            let (date, name) = line.split_once(' ').unwrap_or((line, ""));
+            match date.parse() {
+                Ok(date) => config.company_holidays.push(Holiday {
+                    date,
+                    name: name.trim().to_string(),
+                }),
+                Err(_) => problems.push(format!("Expected 'YYYY-MM-DD name' in '{}'", line)),
+            }
+        }
+        config.import_rules.clear();
+        for line in lines(&self.import_rules) {
+            let rule = line.rsplit_once('=').and_then(|(pattern, pay_type)| {
+                let pay_type = PayType::iter()
+                    .find(|pt| pt.to_string().eq_ignore_ascii_case(pay_type.trim()))?;
+                Some(ImportRule {
+                    pattern: pattern.trim().to_string(),
+                    pay_type,
+                })
+            });
+            match rule {
+                Some(rule) => config.import_rules.push(rule),
+                None => problems.push(format!("Expected 'pattern = pay type' in '{}'", line)),
+            }
+        }
+        config.holiday_calendar_path = text_path(&self.holiday_calendar_path);
+        config.audit_log_path = text_path(&self.audit_log_path);
+        config.system_prompt_path = text_path(&self.system_prompt_path);
+        config.model_rates.clear();
+        for line in lines(&self.model_rates) {
+            let rate = line.split_once(':').and_then(|(model, rates)| {
+                let (prompt, completion) = rates.split_once('/')?;
+                Some((
+                    model.trim().to_string(),
+                    usage::ModelRate {
+                        prompt_per_million: prompt.trim().parse().ok()?,
+                        completion_per_million: completion.trim().parse().ok()?,
+                    },
+                ))
+            });
+            match rate {
+                Some((model, rate)) => {
+                    config.model_rates.insert(model, rate);
+                }
+                None => problems.push(format!(
+                    "Expected 'model: prompt rate / completion rate' in '{}'",
+                    line
+                )),
+            }
+        }
+
+        problems.extend(config.validate());
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty())
+}
+
+fn split_list(text: &str) -> Vec<String> {
+    text.split([',', ' '])
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn path_text(path: &Option<PathBuf>) -> String {
+    path.as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default()
+}
+
+fn text_path(text: &str) -> Option<PathBuf> {
+    Some(text.trim())
+        .filter(|t| !t.is_empty())
+        .map(PathBuf::from)
+}
+
+/// A date typed into the import window, or `None` if left empty.
+fn import_date(text: &str) -> Result<Option<NaiveDate>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    text.parse()
+        .map(Some)
+        .map_err(|_| format!("{} is not a date; use YYYY-MM-DD", text))
+}
+
+/// A previewed import waiting to be applied.
+struct ImportState {
+    skipped: Vec<String>,
+    previews: Vec<ImportPreview>,
+    /// The spreadsheet rows behind the previews, if it was a CSV import
+    rows: Vec<CsvRow>,
 }
 
 impl Default for AgentApp {
     fn default() -> Self {
         let config = load_config();
+        let profile = config
+            .profile()
+            .or(config.profiles.first())
+            .cloned()
+            .unwrap_or_default();
+        let grid_week = dates::week_start_of(Clock::System.today(&config), config.week_start());
+        let session_start = Local::now();
+        let status = usage_status(&config, session_start);
         Self {
-            ebms_url: config.ebms_url.clone(),
-            username: config.ebms_username.clone(),
-            password: config.ebms_password.clone(),
-            employee_id: config.employee_id.clone(),
+            profile_name: profile.name,
+            production: profile.production,
+            ebms_url: profile.ebms_url,
+            username: profile.ebms_username,
+            password: profile.ebms_password,
+            employee_id: profile.employee_id,
             gpt_api_key: config.gpt_api_key.clone(),
-            is_logged_in: !config.ebms_username.is_empty(),
+            is_logged_in: config.profile().is_some(),
             focused: false,
             config,
             prompt: String::new(),
             output: Arc::new(Mutex::new(vec![])),
             current_conversation: Arc::new(Mutex::new(vec![])),
             is_working: Arc::new(Mutex::new(false)),
+            show_context: false,
+            show_logs: false,
+            session_start,
+            usage_status: Arc::new(Mutex::new(status)),
+            context_removed: Arc::new(Mutex::new(0)),
+            progress: Arc::new(Mutex::new(None)),
+            show_import: false,
+            import_path: String::new(),
+            import_employee: None,
+            import_from: String::new(),
+            import_to: String::new(),
+            import_preview: Arc::new(Mutex::new(None)),
+            settings: None,
+            connection_test: Arc::new(Mutex::new(None)),
+            grid_week,
+            grid_employee: None,
+            timesheet: Arc::new(Mutex::new(TimesheetState::default())),
+            timesheet_stale: Arc::new(Mutex::new(true)),
+            session_requested: false,
+            loaded_name: Arc::new(Mutex::new(None)),
+            team: Arc::new(Mutex::new(Vec::new())),
         }
     }
 }
@@ -80,6 +365,55 @@ impl AgentApp {
                     .striped(true)
                     .min_col_width(120.0)
                     .show(ui, |ui| {
+                        ui.label("Profile:");
+                        ui.horizontal(|ui| {
+                            let mut selected: Option<ConnectionProfile> = None;
+                            egui::ComboBox::from_id_salt("profile_picker")
+                                .width(220.0)
+                                .selected_text(if self.profile_name.is_empty() {
+                                    "New profile"
+                                } else {
+                                    &self.profile_name
+                                })
+                                .show_ui(ui, |ui| {
+                                    for profile in &self.config.profiles {
+                                        if ui
+                                            .selectable_label(
+                                                profile.name == self.profile_name,
+                                                &profile.name,
+                                            )
+                                            .clicked()
+                                        {
+                                            selected = Some(profile.clone());
+                                        }
+                                    }
+                                    if ui.selectable_label(false, "New profile").clicked() {
+                                        selected = Some(ConnectionProfile::default());
+                                    }
+                                });
+                            if let Some(profile) = selected {
+                                self.load_profile_fields(profile);
+                            }
+                            if self.config.find_profile(&self.profile_name).is_some()
+                                && ui.button("Delete").clicked()
+                            {
+                                self.config.remove_profile(&self.profile_name);
+                                save_config(&self.config);
+                                let next =
+                                    self.config.profiles.first().cloned().unwrap_or_default();
+                                self.load_profile_fields(next);
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Profile Name:");
+                        ui.add_sized(
+                            [300.0, 24.0],
+                            egui::TextEdit::singleline(&mut self.profile_name)
+                                .hint_text("e.g. Test Company"),
+                        );
+                        ui.end_row();
+
                         ui.label("EBMS API URL:");
                         ui.add_sized(
                             [300.0, 24.0],
@@ -110,6 +444,10 @@ impl AgentApp {
                         );
                         ui.end_row();
 
+                        ui.label("Production:");
+                        ui.checkbox(&mut self.production, "This is a live company");
+                        ui.end_row();
+
                         ui.label("GPT API Key:");
                         ui.add_sized(
                             [300.0, 24.0],
@@ -121,31 +459,68 @@ impl AgentApp {
 
             ui.add_space(10.0);
             let enter_pressed = ui.input(|i| i.key_pressed(egui::Key::Enter));
-            if ui
+            if (ui
                 .add_sized([120.0, 32.0], egui::Button::new("Log In"))
                 .clicked()
-                || enter_pressed
+                || enter_pressed)
+                && !self.profile_name.is_empty()
+                && !self.username.is_empty()
+                && !self.password.is_empty()
+                && !self.employee_id.is_empty()
             {
-                if !self.username.is_empty()
-                    && !self.password.is_empty()
-                    && !self.employee_id.is_empty()
-                {
-                    self.is_logged_in = true;
-
-                    self.config = AppConfig {
-                        ebms_url: self.ebms_url.clone(),
-                        ebms_username: self.username.clone(),
-                        ebms_password: self.password.clone(),
-                        employee_id: self.employee_id.clone(),
-                        gpt_api_key: self.gpt_api_key.clone(),
-                    };
-                    save_config(&self.config);
-                }
+                self.is_logged_in = true;
+
+                self.config.upsert_profile(ConnectionProfile {
+                    name: self.profile_name.clone(),
+                    ebms_url: self.ebms_url.clone(),
+                    ebms_username: self.username.clone(),
+                    ebms_password: self.password.clone(),
+                    employee_id: self.employee_id.clone(),
+                    production: self.production,
+                    // Settings the login form doesn't show
+                    ..self
+                        .config
+                        .find_profile(&self.profile_name)
+                        .cloned()
+                        .unwrap_or_default()
+                });
+                self.config.current_profile = Some(self.profile_name.clone());
+                self.config.gpt_api_key = self.gpt_api_key.clone();
+                save_config(&self.config);
             }
         });
     }
 
+    fn load_profile_fields(&mut self, profile: ConnectionProfile) {
+        self.profile_name = profile.name;
+        self.production = profile.production;
+        self.ebms_url = profile.ebms_url;
+        self.username = profile.ebms_username;
+        self.password = profile.ebms_password;
+        self.employee_id = profile.employee_id;
+    }
+
     fn draw_main_ui(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if !std::mem::replace(&mut self.session_requested, true) {
+            self.load_session(ctx);
+        }
+        if let Some((profile_name, employee_id, name)) = self.loaded_name.lock().unwrap().take()
+            && let Some(profile) = self
+                .config
+                .profiles
+                .iter_mut()
+                .find(|p| p.name == profile_name && p.employee_id == employee_id)
+        {
+            profile.employee_name = name;
+            save_config(&self.config);
+        }
+        egui::TopBottomPanel::top("timesheet").show(ctx, |ui| {
+            self.draw_timesheet(ctx, ui);
+        });
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            let status = self.usage_status.lock().unwrap().clone();
+            ui.label(RichText::new(status).small().weak());
+        });
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
                 ui.add_space(10.0); // Adds a bit of margin to the bottom
@@ -175,7 +550,7 @@ impl AgentApp {
                         || enter_pressed
                     {
                         ctx.memory_mut(|mem| mem.request_focus(id));
-                        self.button_clicked();
+                        self.button_clicked(ctx);
                     }
                 });
                 if *self.is_working.lock().unwrap() {
@@ -202,20 +577,892 @@ impl AgentApp {
                 {
                     self.log_out();
                 }
+                if ui.button("Import Calendar").clicked() {
+                    self.show_import = true;
+                }
+                if ui.button("Context").clicked() {
+                    self.show_context = !self.show_context;
+                }
+                if ui.button("Logs").clicked() {
+                    self.show_logs = !self.show_logs;
+                }
+                if ui.button("Settings").clicked() && self.settings.is_none() {
+                    self.settings = Some(SettingsState::new(&self.config));
+                    self.connection_test.lock().unwrap().take();
+                }
+                if let Some(profile) = self.config.profile() {
+                    let label = if profile.production {
+                        RichText::new(format!("{} (PRODUCTION)", profile.name))
+                            .strong()
+                            .color(egui::Color32::RED)
+                    } else {
+                        RichText::new(&profile.name)
+                    };
+                    ui.label(label);
+                }
+            });
+        });
+        if self.show_import {
+            self.draw_import_window(ctx);
+        }
+        if self.settings.is_some() {
+            self.draw_settings_window(ctx);
+        }
+        if self.show_context {
+            self.draw_context_window(ctx);
+        }
+        if self.show_logs {
+            self.draw_logs_window(ctx);
+        }
+    }
+
+    fn draw_logs_window(&mut self, ctx: &egui::Context) {
+        let lines = logging::recent();
+        egui::Window::new("Logs")
+            .open(&mut self.show_logs)
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                if let Some(dir) = logging::log_dir(&self.config) {
+                    ui.label(format!(
+                        "Recent messages at {} level and up. Older ones are in {}",
+                        self.config.log_level(),
+                        dir.display()
+                    ));
+                }
+                ui.separator();
+                if lines.is_empty() {
+                    ui.label(RichText::new("Nothing logged yet").weak());
+                }
+                egui::ScrollArea::both()
+                    .max_height(400.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in &lines {
+                            ui.label(RichText::new(line).monospace());
+                        }
+                    });
+            });
+    }
+
+    fn draw_context_window(&mut self, ctx: &egui::Context) {
+        let conversation = self.current_conversation.lock().unwrap().clone();
+        let removed = *self.context_removed.lock().unwrap();
+        let budget = self.config.context_token_budget();
+        egui::Window::new("Context Window")
+            .open(&mut self.show_context)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "About {} of {} tokens resent with the next prompt ({:?} policy)",
+                    context::total_tokens(&conversation),
+                    budget,
+                    self.config.context_policy
+                ));
+                if removed > 0 {
+                    ui.label(format!("{} earlier messages left out so far", removed));
+                }
+                ui.separator();
+                if conversation.is_empty() {
+                    ui.label(RichText::new("The conversation is empty").weak());
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("context_grid")
+                            .num_columns(3)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for message in &conversation {
+                                    let role = match message.role {
+                                        Role::User => "user",
+                                        Role::Assistant => "assistant",
+                                        Role::System
+                                            if message
+                                                .content
+                                                .starts_with(context::SUMMARY_PREFIX) =>
+                                        {
+                                            "summary"
+                                        }
+                                        Role::System => "system",
+                                        Role::Function => "function",
+                                    };
+                                    ui.label(RichText::new(role).strong());
+                                    ui.label(format!("{}", context::estimate_tokens(message)));
+                                    let text = match &message.function_call {
+                                        Some(call) if message.content.is_empty() => {
+                                            format!("{}({})", call.name, call.arguments)
+                                        }
+                                        _ => message.content.clone(),
+                                    };
+                                    ui.add(egui::Label::new(text).wrap());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+    }
+
+    fn draw_settings_window(&mut self, ctx: &egui::Context) {
+        let Some(mut settings) = self.settings.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut save = false;
+        let mut test = false;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .default_width(480.0)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                ui.heading("Model");
+                egui::Grid::new("settings_model")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("API Key:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut settings.config.gpt_api_key)
+                                .password(true),
+                        );
+                        ui.end_row();
+                        ui.label("API URL:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut settings.config.gpt_api_url)
+                                .hint_text("OpenAI"),
+                        );
+                        ui.end_row();
+                        ui.label("Model:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut settings.config.gpt_model)
+                                .hint_text("gpt-4"),
+                        );
+                        ui.end_row();
+                        ui.label("System prompt:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut settings.system_prompt_path)
+                                .hint_text("system_prompt.txt in the config folder"),
+                        )
+                        .on_hover_text(format!(
+                            "A template file; it can use {}",
+                            prompt::VARIABLES
+                                .map(|v| format!("{{{{{}}}}}", v))
+                                .join(", ")
+                        ));
+                        ui.end_row();
+                        ui.label("Timeout (seconds, 0 for 120):");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.config.gpt_timeout_secs)
+                                .range(0..=600),
+                        );
+                        ui.end_row();
+                        ui.label("Context budget (tokens, 0 for 2000):");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.config.context_token_budget)
+                                .range(0..=100_000),
+                        );
+                        ui.end_row();
+                        ui.label("Function calls:");
+                        ui.horizontal(|ui| {
+                            let mode = &mut settings.config.tool_calling;
+                            ui.radio_value(mode, ToolCalling::Functions, "Native");
+                            ui.radio_value(mode, ToolCalling::JsonSchema, "JSON schema");
+                            ui.radio_value(mode, ToolCalling::JsonPrompt, "JSON prompt");
+                        })
+                        .response
+                        .on_hover_text(
+                            "For models without function calling, ask for a JSON plan instead",
+                        );
+                        ui.end_row();
+                        ui.label("Offline rules:");
+                        ui.horizontal(|ui| {
+                            let mode = &mut settings.config.offline_rules;
+                            ui.radio_value(mode, OfflineRules::Fallback, "When the model fails");
+                            ui.radio_value(mode, OfflineRules::Always, "Always");
+                            ui.radio_value(mode, OfflineRules::Never, "Never");
+                        })
+                        .response
+                        .on_hover_text(
+                            "Understand simple prompts like \"sick today\" without the model",
+                        );
+                        ui.end_row();
+                        ui.label("Older messages:");
+                        ui.horizontal(|ui| {
+                            let policy = &mut settings.config.context_policy;
+                            ui.radio_value(policy, ContextPolicy::Trim, "Drop");
+                            ui.radio_value(policy, ContextPolicy::Summarize, "Summarize");
+                        });
+                        ui.end_row();
+                        ui.label("Monthly budget ($, 0 for none):");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.config.monthly_budget_usd)
+                                .range(0.0..=100_000.0)
+                                .speed(1.0),
+                        );
+                        ui.end_row();
+                        ui.label("Rates ($ per million tokens):");
+                        ui.add(
+                            egui::TextEdit::multiline(&mut settings.model_rates)
+                                .hint_text("gpt-4o: 2.50 / 10.00")
+                                .desired_rows(2),
+                        )
+                        .on_hover_text("Prompt / completion rates, for models not priced already");
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.heading(format!("EBMS: {}", settings.profile.name));
+                egui::Grid::new("settings_ebms")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        let profile = &mut settings.profile;
+                        ui.label("API URL:");
+                        ui.text_edit_singleline(&mut profile.ebms_url);
+                        ui.end_row();
+                        ui.label("Username:");
+                        ui.text_edit_singleline(&mut profile.ebms_username);
+                        ui.end_row();
+                        ui.label("Password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut profile.ebms_password).password(true),
+                        );
+                        ui.end_row();
+                        ui.label("Employee ID:");
+                        ui.text_edit_singleline(&mut profile.employee_id);
+                        ui.end_row();
+                        ui.label("Production:");
+                        ui.checkbox(&mut profile.production, "This is a live company");
+                        ui.end_row();
+                        ui.label("Timeout (seconds, 0 for 30):");
+                        ui.add(egui::DragValue::new(&mut profile.timeout_secs).range(0..=600));
+                        ui.end_row();
+                        for pay_type in PayType::iter() {
+                            ui.label(format!("{} pay code:", pay_type));
+                            let default =
+                                agent::config::ConnectionProfile::default().pay_code(&pay_type);
+                            ui.add(
+                                egui::TextEdit::singleline(
+                                    profile.pay_codes.entry(pay_type.to_string()).or_default(),
+                                )
+                                .hint_text(default),
+                            );
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Test Connection").clicked() {
+                        test = true;
+                    }
+                    match self.connection_test.lock().unwrap().as_ref() {
+                        Some(Ok(msg)) => {
+                            ui.label(RichText::new(msg).color(egui::Color32::DARK_GREEN));
+                        }
+                        Some(Err(e)) => {
+                            ui.label(RichText::new(e).color(egui::Color32::RED));
+                        }
+                        None => {}
+                    }
+                });
+
+                ui.separator();
+                ui.heading("Calendar");
+                egui::Grid::new("settings_calendar")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("Timezone:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut settings.config.company_timezone)
+                                .hint_text("Local time, or e.g. America/Chicago"),
+                        );
+                        ui.end_row();
+                        ui.label("Week starts on:");
+                        let week_start = &mut settings.config.week_start;
+                        egui::ComboBox::from_id_salt("week_start")
+                            .selected_text(if week_start.is_empty() {
+                                "Sunday"
+                            } else {
+                                week_start.as_str()
+                            })
+                            .show_ui(ui, |ui| {
+                                for day in WEEK {
+                                    let name = day_name(day);
+                                    let selected = week_start.parse() == Ok(day)
+                                        || (week_start.is_empty() && day == Weekday::Sun);
+                                    if ui.selectable_label(selected, name).clicked() {
+                                        *week_start = name.to_string();
+                                    }
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Work days:");
+                        ui.horizontal(|ui| {
+                            for day in WEEK {
+                                let mut on = settings.work_days.contains(&day);
+                                if ui.checkbox(&mut on, day.to_string()).changed() {
+                                    settings.work_days.retain(|d| *d != day);
+                                    if on {
+                                        settings.work_days.push(day);
+                                    }
+                                }
+                            }
+                        });
+                        ui.end_row();
+                        ui.label("Other schedules:");
+                        ui.add(
+                            egui::TextEdit::multiline(&mut settings.employee_work_days)
+                                .hint_text("E100: Mon, Tue, Wed")
+                                .desired_rows(2),
+                        );
+                        ui.end_row();
+                        ui.label("Holidays:");
+                        ui.add(
+                            egui::TextEdit::multiline(&mut settings.company_holidays)
+                                .hint_text("2025-12-25 Christmas Day")
+                                .desired_rows(3),
+                        );
+                        ui.end_row();
+                        ui.label("Holiday calendar:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut settings.holiday_calendar_path)
+                                .hint_text("holidays.ics in the config folder"),
+                        );
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.heading("Import and Audit");
+                egui::Grid::new("settings_other")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("Import rules:");
+                        ui.add(
+                            egui::TextEdit::multiline(&mut settings.import_rules)
+                                .hint_text("Sick = Sick\nPTO = Vacation")
+                                .desired_rows(3),
+                        );
+                        ui.end_row();
+                        ui.label("Audit log:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut settings.audit_log_path)
+                                .hint_text("audit.jsonl in the config folder"),
+                        );
+                        ui.end_row();
+                        ui.label("Log level:");
+                        egui::ComboBox::from_id_salt("settings_log_level")
+                            .selected_text(settings.config.log_level().to_string())
+                            .show_ui(ui, |ui| {
+                                for level in ["error", "warn", "info", "debug", "trace"] {
+                                    ui.selectable_value(
+                                        &mut settings.config.log_level,
+                                        level.to_string(),
+                                        level,
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text("Takes effect the next time the app starts");
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                for problem in &settings.problems {
+                    ui.label(RichText::new(problem).color(egui::Color32::RED));
+                }
+                if ui.button("Save").clicked() {
+                    save = true;
+                }
+            });
+
+        if test {
+            self.test_connection(ctx, &settings);
+        }
+        let saved = save
+            && match settings.to_config() {
+                Ok(config) => {
+                    self.config = config;
+                    save_config(&self.config);
+                    if let Some(profile) = self.config.profile().cloned() {
+                        self.load_profile_fields(profile);
+                    }
+                    self.gpt_api_key = self.config.gpt_api_key.clone();
+                    *self.usage_status.lock().unwrap() =
+                        usage_status(&self.config, self.session_start);
+                    *self.timesheet_stale.lock().unwrap() = true;
+                    true
+                }
+                Err(problems) => {
+                    settings.problems = problems;
+                    false
+                }
+            };
+        if open && !saved {
+            self.settings = Some(settings);
+        }
+    }
+
+    fn test_connection(&mut self, ctx: &egui::Context, settings: &SettingsState) {
+        let mut config = settings.config.clone();
+        config.upsert_profile(settings.profile.clone());
+        let result = self.connection_test.clone();
+        let ctx = ctx.clone();
+        *result.lock().unwrap() = Some(Ok("Connecting...".to_string()));
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let outcome = rt
+                .block_on(agent::test_connection(&config))
+                .map(|employee| format!("Connected as {}", employee))
+                .map_err(|e| e.to_string());
+            *result.lock().unwrap() = Some(outcome);
+            ctx.request_repaint();
+        });
+    }
+
+    fn draw_import_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_import;
+        let working = *self.is_working.lock().unwrap();
+        egui::Window::new("Import Calendar")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("ICS or CSV file:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.import_path)
+                            .hint_text("/path/to/calendar.ics")
+                            .desired_width(260.0),
+                    );
+                    if ui
+                        .add_enabled(!working, egui::Button::new("Preview"))
+                        .clicked()
+                    {
+                        self.preview_import();
+                    }
+                });
+                // Spreadsheet rows name their own employee and dates
+                ui.horizontal(|ui| {
+                    ui.label("Calendar days from:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.import_from)
+                            .hint_text("YYYY-MM-DD")
+                            .desired_width(90.0),
+                    );
+                    ui.label("to:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.import_to)
+                            .hint_text("YYYY-MM-DD")
+                            .desired_width(90.0),
+                    );
+                });
+                let team = self.team.lock().unwrap().clone();
+                if team.len() > 1 {
+                    ui.horizontal(|ui| {
+                        ui.label("Calendar of:");
+                        let selected_text = team
+                            .iter()
+                            .find(|e| Some(&e.id) == self.import_employee.as_ref())
+                            .map_or("Me".to_string(), |e| e.name.clone());
+                        egui::ComboBox::from_id_salt("import_employee")
+                            .selected_text(selected_text)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.import_employee, None, "Me");
+                                for employee in team.iter().skip(1) {
+                                    ui.selectable_value(
+                                        &mut self.import_employee,
+                                        Some(employee.id.clone()),
+                                        employee.to_string(),
+                                    );
+                                }
+                            });
+                    });
+                }
+
+                let today = Clock::System.today(&self.config);
+                let mut can_apply = false;
+                if let Some(state) = self.import_preview.lock().unwrap().as_ref() {
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for preview in &state.previews {
+                                ui.label(RichText::new(&preview.item.source).strong());
+                                match &preview.changes {
+                                    Ok(changes) => {
+                                        for change in changes {
+                                            ui.label(change.describe(today));
+                                        }
+                                    }
+                                    Err(e) => {
+                                        ui.label(RichText::new(e).color(egui::Color32::RED));
+                                    }
+                                }
+                            }
+                            for skipped in &state.skipped {
+                                ui.label(RichText::new(format!("Skipped {}", skipped)).weak());
+                            }
+                        });
+                    can_apply = state.previews.iter().any(ImportPreview::has_changes);
+                }
+                if ui
+                    .add_enabled(can_apply && !working, egui::Button::new("Apply"))
+                    .clicked()
+                {
+                    self.apply_import();
+                }
             });
+        self.show_import = open;
+    }
+
+    fn preview_import(&mut self) {
+        let config = self.config.clone();
+        let path = self.import_path.trim().to_string();
+        let (from, to) = match (import_date(&self.import_from), import_date(&self.import_to)) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => {
+                self.output.lock().unwrap().push(RichText::new(e));
+                return;
+            }
+        };
+        let options = IcsImportOptions {
+            employee: self.import_employee.clone(),
+            from,
+            to,
+        };
+        *self.is_working.lock().unwrap() = true;
+        let is_working = self.is_working.clone();
+        let output = self.output.clone();
+        let import_preview = self.import_preview.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            // Spreadsheets from HR, anything else is treated as a calendar
+            let planned = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))
+                .and_then(|text| {
+                    if path.to_lowercase().ends_with(".csv") {
+                        rt.block_on(import::plan_csv_import(&config, &text))
+                            .map(|csv| (csv.plan, csv.rows))
+                    } else {
+                        Ok((
+                            import::plan_ics_import(&config, &text, &options),
+                            Vec::new(),
+                        ))
+                    }
+                });
+            match planned {
+                Ok((plan, rows)) => {
+                    let previews = rt.block_on(import::preview_import(&config, &plan));
+                    *import_preview.lock().unwrap() = Some(ImportState {
+                        skipped: plan.skipped,
+                        previews,
+                        rows,
+                    });
+                }
+                Err(e) => output.lock().unwrap().push(RichText::new(e)),
+            }
+            *is_working.lock().unwrap() = false;
+        });
+    }
+
+    fn apply_import(&mut self) {
+        let Some(state) = self.import_preview.lock().unwrap().take() else {
+            return;
+        };
+        let config = self.config.clone();
+        *self.is_working.lock().unwrap() = true;
+        self.show_import = false;
+        let is_working = self.is_working.clone();
+        let output = self.output.clone();
+        let timesheet_stale = self.timesheet_stale.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let results = rt.block_on(import::apply_import(&config, &state.previews));
+            let today = Clock::System.today(&config);
+            let mut output_lock = output.lock().unwrap();
+            for result in &results {
+                output_lock.push(RichText::new(format!(">> Import {}", result.item.source)));
+                match &result.changes {
+                    Ok(changes) => {
+                        for change in changes {
+                            output_lock.push(RichText::new(change.describe(today)).strong());
+                        }
+                    }
+                    Err(e) => output_lock.push(RichText::new(e.as_str())),
+                }
+            }
+            for (line, result) in import::row_results(&state.rows, &results) {
+                if let Err(e) = result {
+                    output_lock.push(RichText::new(format!("Line {} failed: {}", line, e)));
+                }
+            }
+            drop(output_lock);
+            *timesheet_stale.lock().unwrap() = true;
+            *is_working.lock().unwrap() = false;
+        });
+    }
+
+    fn draw_timesheet(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        if std::mem::take(&mut *self.timesheet_stale.lock().unwrap()) {
+            self.refresh_timesheet(ctx);
+        }
+        let week: Vec<NaiveDate> = (0..7).map(|n| self.grid_week + Duration::days(n)).collect();
+
+        ui.horizontal(|ui| {
+            if ui.button("<").clicked() {
+                self.grid_week -= Duration::days(7);
+                self.refresh_timesheet(ctx);
+            }
+            ui.label(
+                RichText::new(format!("Week of {}", self.grid_week.format("%B %-d, %Y"))).strong(),
+            );
+            if ui.button(">").clicked() {
+                self.grid_week += Duration::days(7);
+                self.refresh_timesheet(ctx);
+            }
+            if ui.button("This Week").clicked() {
+                self.grid_week = dates::week_start_of(
+                    Clock::System.today(&self.config),
+                    self.config.week_start(),
+                );
+                self.refresh_timesheet(ctx);
+            }
+            if ui.button("Refresh").clicked() {
+                self.refresh_timesheet(ctx);
+            }
+
+            let team = self.team.lock().unwrap().clone();
+            if team.len() > 1 {
+                let mut selected = self.grid_employee.clone();
+                let selected_text = team
+                    .iter()
+                    .find(|e| Some(&e.id) == selected.as_ref())
+                    .map_or("Me".to_string(), |e| e.name.clone());
+                egui::ComboBox::from_id_salt("grid_employee")
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut selected, None, "Me");
+                        for employee in team.iter().skip(1) {
+                            ui.selectable_value(
+                                &mut selected,
+                                Some(employee.id.clone()),
+                                employee.to_string(),
+                            );
+                        }
+                    });
+                if selected != self.grid_employee {
+                    self.grid_employee = selected;
+                    self.refresh_timesheet(ctx);
+                }
+            }
+        });
+
+        let today = Clock::System.today(&self.config);
+        let (timesheet, holidays) = {
+            let state = self.timesheet.lock().unwrap();
+            let holidays: Vec<bool> = week.iter().map(|d| state.holidays.is_holiday(*d)).collect();
+            (state.entries.clone(), holidays)
+        };
+        let mut change: Option<(NaiveDate, PayType)> = None;
+        egui::Grid::new("timesheet_grid")
+            .num_columns(7)
+            .min_col_width(90.0)
+            .show(ui, |ui| {
+                for date in &week {
+                    let label = RichText::new(date.format("%a %-m/%-d").to_string());
+                    ui.label(if *date == today {
+                        label.strong()
+                    } else {
+                        label
+                    });
+                }
+                ui.end_row();
+
+                for (date, holiday) in week.iter().zip(&holidays) {
+                    ui.vertical(|ui| {
+                        let entries: Vec<&TimeEntry> = match &timesheet {
+                            Some(Ok(entries)) => {
+                                entries.iter().filter(|e| e.date == *date).collect()
+                            }
+                            _ => Vec::new(),
+                        };
+                        if entries.is_empty() {
+                            ui.label(RichText::new("-").weak());
+                        } else {
+                            let codes: Vec<&str> =
+                                entries.iter().map(|e| e.pay_code.as_str()).collect();
+                            let text = RichText::new(codes.join(", "))
+                                .color(egui::Color32::BLACK)
+                                .background_color(pay_type_color(entries[0].pay_type.as_ref()));
+                            ui.menu_button(text, |ui| {
+                                for pay_type in PayType::iter() {
+                                    if ui.button(pay_type.to_string()).clicked() {
+                                        change = Some((*date, pay_type));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        }
+                        if *holiday {
+                            ui.label(RichText::new("Holiday").small().weak());
+                        }
+                    });
+                }
+                ui.end_row();
+            });
+        match &timesheet {
+            Some(Err(e)) => {
+                ui.label(RichText::new(e).color(egui::Color32::RED));
+            }
+            None => {
+                ui.label(RichText::new("Loading...").weak());
+            }
+            Some(Ok(_)) => {}
+        }
+
+        if let Some((date, pay_type)) = change {
+            self.change_day(ctx, date, pay_type);
+        }
+    }
+
+    fn refresh_timesheet(&mut self, ctx: &egui::Context) {
+        let config = self.config.clone();
+        let employee = self.grid_employee.clone();
+        let start = self.grid_week;
+        let timesheet = self.timesheet.clone();
+        let ctx = ctx.clone();
+        let key = (start, employee.clone());
+        *timesheet.lock().unwrap() = TimesheetState {
+            requested: Some(key.clone()),
+            entries: None,
+            holidays: HolidayCalendar::load(&config),
+        };
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let entries = rt.block_on(agent::get_time_entries(
+                &config,
+                employee.as_deref(),
+                start,
+                start + Duration::days(6),
+            ));
+            let mut timesheet = timesheet.lock().unwrap();
+            if timesheet.requested.as_ref() == Some(&key) {
+                timesheet.entries = Some(entries.map_err(|e| e.to_string()));
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    // Once per login: the user's name, so prompts don't each ask EBMS for it, and the people
+    // they manage, for the timesheet
+    fn load_session(&mut self, ctx: &egui::Context) {
+        let config = self.config.clone();
+        let Some(mut profile) = config.profile().cloned() else {
+            return;
+        };
+        let loaded_name = self.loaded_name.clone();
+        let team = self.team.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            if profile.employee_name.trim().is_empty() {
+                match rt.block_on(agent::load_employee_name(&mut profile)) {
+                    Ok(()) if !profile.employee_name.is_empty() => {
+                        *loaded_name.lock().unwrap() =
+                            Some((profile.name, profile.employee_id, profile.employee_name));
+                    }
+                    Ok(()) => {}
+                    Err(e) => tracing::warn!("Failed to look up {}: {}", profile.employee_id, e),
+                }
+            }
+            match rt.block_on(agent::get_team(&config)) {
+                Ok(members) => *team.lock().unwrap() = members,
+                Err(e) => tracing::warn!("Failed to look up who you manage: {}", e),
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    // Clicking a day sets it directly, without the model
+    fn change_day(&mut self, ctx: &egui::Context, date: NaiveDate, pay_type: PayType) {
+        let mut arguments = serde_json::json!({
+            "dates": [date.format("%Y-%m-%d").to_string()],
+            "pay_type": pay_type.to_string(),
+        });
+        if let Some(employee) = &self.grid_employee {
+            arguments["employee"] = serde_json::json!(employee);
+        }
+        let function_call = FunctionCall {
+            name: "set_pay_type".to_string(),
+            arguments: arguments.to_string(),
+            prompt_version: None,
+            usage: None,
+        };
+        let config = self.config.clone();
+        let output = self.output.clone();
+        let timesheet_stale = self.timesheet_stale.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let result = rt.block_on(agent::execute_function_call(
+                &config,
+                &function_call,
+                &ExecutionOptions::default(),
+            ));
+            let today = Clock::System.today(&config);
+            let mut output_lock = output.lock().unwrap();
+            match result {
+                Ok(agent::ExecutionResult::Success(changes))
+                | Ok(agent::ExecutionResult::Planned(_, changes)) => {
+                    for change in changes {
+                        output_lock.push(RichText::new(change.describe(today)).strong());
+                    }
+                }
+                Ok(agent::ExecutionResult::Message(msg)) => output_lock.push(RichText::new(msg)),
+                Err(e) => output_lock.push(RichText::new(e.to_string())),
+            }
+            drop(output_lock);
+            *timesheet_stale.lock().unwrap() = true;
+            ctx.request_repaint();
         });
     }
 
     fn log_out(&mut self) {
         self.prompt.clear();
+        self.show_import = false;
+        self.import_preview.lock().unwrap().take();
+        self.import_employee = None;
+        self.settings = None;
+        self.grid_employee = None;
+        *self.timesheet.lock().unwrap() = TimesheetState::default();
+        *self.timesheet_stale.lock().unwrap() = true;
         self.output.lock().unwrap().clear();
         self.current_conversation.lock().unwrap().clear();
+        *self.context_removed.lock().unwrap() = 0;
         self.is_logged_in = false;
-        self.config = AppConfig::empty();
+        self.session_requested = false;
+        self.team.lock().unwrap().clear();
+        self.config.current_profile = None;
         save_config(&self.config);
     }
 
-    fn button_clicked(&mut self) {
+    fn button_clicked(&mut self, ctx: &egui::Context) {
         let prompt = self.prompt.clone();
         // Spawn the async task in a background thread
         let config: AppConfig = self.config.clone();
@@ -226,17 +1473,29 @@ impl AgentApp {
         let is_working_clone = self.is_working.clone();
         let output_ref_clone = self.output.clone();
         let conversation_clone = self.current_conversation.clone();
+        let context_removed = self.context_removed.clone();
+        let progress = self.progress.clone();
+        let usage_status_ref = self.usage_status.clone();
+        let session_start = self.session_start;
+        let timesheet_stale = self.timesheet_stale.clone();
+        let ctx = ctx.clone();
         std::thread::spawn(move || {
             let rt = tokio::runtime::Builder::new_current_thread()
                 .enable_all()
                 .build()
                 .unwrap();
             rt.block_on(execute_prompt(
-                config,
+                ctx,
+                config.clone(),
                 prompt,
                 output_ref_clone,
                 conversation_clone,
+                context_removed,
+                progress.clone(),
             ));
+            *progress.lock().unwrap() = None;
+            *usage_status_ref.lock().unwrap() = usage_status(&config, session_start);
+            *timesheet_stale.lock().unwrap() = true;
             let mut is_working = is_working_clone.lock().unwrap();
             *is_working = false;
         });
@@ -250,15 +1509,71 @@ impl AgentApp {
             1 => "..",
             _ => "...",
         };
-        ui.label(RichText::new(dots).size(40.0));
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(dots).size(40.0));
+            if let Some(step) = self.progress.lock().unwrap().as_ref() {
+                ui.label(RichText::new(step).weak());
+            }
+        });
+    }
+}
+
+const WEEK: [Weekday; 7] = [
+    Weekday::Sun,
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+];
+
+fn day_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Sun => "Sunday",
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+    }
+}
+
+fn usage_status(config: &AppConfig, session_start: DateTime<Local>) -> String {
+    let records = usage::load(config);
+    let today = Clock::System.today(config);
+    let mut status = format!(
+        "Model use - session: {} | today: {} | this month: {}",
+        usage::totals_since(&records, session_start),
+        usage::totals_between(config, &records, usage::day_of(today)),
+        usage::totals_between(config, &records, usage::month_of(today)),
+    );
+    if config.monthly_budget_usd > 0.0 {
+        status.push_str(&format!(" of ${:.2}", config.monthly_budget_usd));
+    }
+    status
+}
+
+fn pay_type_color(pay_type: Option<&PayType>) -> egui::Color32 {
+    match pay_type {
+        Some(PayType::Salary) => egui::Color32::from_rgb(220, 220, 220),
+        Some(PayType::Sick) => egui::Color32::from_rgb(255, 190, 190),
+        Some(PayType::Vacation) => egui::Color32::from_rgb(180, 210, 255),
+        Some(PayType::Holiday) => egui::Color32::from_rgb(190, 235, 190),
+        Some(PayType::Parental) => egui::Color32::from_rgb(225, 200, 255),
+        None => egui::Color32::from_rgb(255, 225, 170),
     }
 }
 
 async fn execute_prompt(
+    ctx: egui::Context,
     config: AppConfig,
     prompt: String,
     output: Arc<Mutex<Vec<RichText>>>,
     current_conversation: Arc<Mutex<Vec<ConversationMessage>>>,
+    context_removed: Arc<Mutex<usize>>,
+    progress: Arc<Mutex<Option<String>>>,
 ) {
     // Add prompt to output (lock only for this)
     {
@@ -267,69 +1582,83 @@ async fn execute_prompt(
     }
 
     // Clone conversation for use in async call (lock only for this)
-    let conversation: Vec<ConversationMessage>;
-    {
-        conversation = current_conversation.lock().unwrap().clone()
-    }; // lock released here
+    let conversation: Vec<ConversationMessage> = current_conversation.lock().unwrap().clone(); // lock released here
 
-    let result = agent::execute_prompt(&config, &prompt, &conversation).await;
+    // The reply is shown as it streams in, on one line that's filled in piece by piece
+    let streamed: Arc<Mutex<Option<(usize, String)>>> = Arc::new(Mutex::new(None));
+    let on_text = {
+        let output = output.clone();
+        let streamed = streamed.clone();
+        let ctx = ctx.clone();
+        TextSink(Arc::new(move |text: &str| {
+            let mut output_lock = output.lock().unwrap();
+            let mut streamed_lock = streamed.lock().unwrap();
+            let (index, so_far) = streamed_lock.get_or_insert_with(|| {
+                output_lock.push(RichText::new("Agent: "));
+                (output_lock.len() - 1, String::new())
+            });
+            so_far.push_str(text);
+            if let Some(line) = output_lock.get_mut(*index) {
+                *line = RichText::new(format!("Agent: {}", so_far));
+            }
+            ctx.request_repaint();
+        }))
+    };
+    let on_progress = {
+        let output = output.clone();
+        let ctx = ctx.clone();
+        ProgressSink(Arc::new(move |event: &ProgressEvent| {
+            // Kept in the output, so it's clear the model didn't read this prompt
+            if let ProgressEvent::OfflineRules { .. } = event {
+                output
+                    .lock()
+                    .unwrap()
+                    .push(RichText::new(event.to_string()).italics());
+            }
+            *progress.lock().unwrap() = Some(event.to_string());
+            ctx.request_repaint();
+        }))
+    };
+    let options = ExecutionOptions {
+        on_text: Some(on_text),
+        on_progress: Some(on_progress),
+        ..Default::default()
+    };
+    let result = agent::execute_prompt(&config, &prompt, &conversation, &options).await;
 
-    let conversation_update: Option<Vec<ConversationMessage>>;
+    let today = Clock::System.today(&config);
     let mut output_messages: Vec<RichText> = Vec::new();
-    match result {
-        Ok(success) => {
-            match success {
-                agent::ExecutionResult::Message(msg) => {
-                    output_messages.push(RichText::new(format!("Agent: {}", msg)));
-
-                    // On error, add user prompt to conversation
-                    let mut new_conversation = {
-                        let lock = current_conversation.lock().unwrap();
-                        let mut cloned = lock.clone();
-                        cloned.push(ConversationMessage::new_content(Role::User, prompt.clone()));
-                        cloned
-                    };
-                    new_conversation.push(ConversationMessage::new_content(
-                        Role::Assistant,
-                        msg.clone(),
-                    ));
-                    conversation_update = Some(new_conversation);
-                }
-                agent::ExecutionResult::Success(changes) => {
-                    let mut new_conversation: Vec<ConversationMessage> = vec![];
-                    for change in changes {
-                        let change_text = change.to_string();
-                        output_messages.push(RichText::new(change_text.clone()).strong());
-                        if let Some(function_call) = change.function_call {
-                            new_conversation.push(ConversationMessage::new_function_call(
-                                function_call,
-                                change_text,
-                            ));
-                        }
-                    }
-
-                    // On success, clear conversation restart with what actually happened - this allows the agent to know how to undo
-                    conversation_update = Some(new_conversation);
-                }
+    match &result {
+        Ok(agent::ExecutionResult::Message(msg)) => {
+            let line = RichText::new(format!("Agent: {}", msg));
+            let streamed_line = streamed.lock().unwrap().take();
+            let mut output_lock = output.lock().unwrap();
+            match streamed_line.and_then(|(index, _)| output_lock.get_mut(index)) {
+                Some(streamed_line) => *streamed_line = line,
+                None => output_messages.push(line),
             }
         }
-        Err(e) => {
-            conversation_update = None; // No conversation update on error
-            match e {
-                ExecutionError::AgentError(msg) => {
-                    output_messages.push(RichText::new(format!("Agent Error: {}", msg)));
-                }
-                ExecutionError::EbmsError(msg) => {
-                    output_messages.push(RichText::new(format!("EBMS: {}", msg)));
-                }
+        Ok(agent::ExecutionResult::Success(changes))
+        | Ok(agent::ExecutionResult::Planned(_, changes)) => {
+            for change in changes {
+                output_messages.push(RichText::new(change.describe(today)).strong());
             }
         }
+        Err(e) => {
+            output_messages.push(RichText::new(e.to_string()));
+        }
     }
 
+    // On success the conversation restarts with what actually happened - this allows the agent to know how to undo
+    let conversation_update =
+        agent::next_conversation_window(&config, &options.clock, &conversation, &prompt, &result)
+            .await;
+
     // Apply updates (lock only for this)
-    if let Some(new_conversation) = conversation_update {
+    if let Some(window) = conversation_update {
+        *context_removed.lock().unwrap() += window.removed;
         let mut conversation_lock = current_conversation.lock().unwrap();
-        *conversation_lock = new_conversation;
+        *conversation_lock = window.messages;
     }
 
     if !output_messages.is_empty() {
@@ -339,4 +1668,5 @@ async fn execute_prompt(
             output_lock.push(new_output);
         }
     }
+    ctx.request_repaint();
 }
@@ -0,0 +1,144 @@
+//! Understands common prompts without the model, e.g. "sick today", "vacation next week" or
+//! "PTO on 7/3/2025", for when the model can't be reached or isn't wanted. Only the user's own
+//! time can be changed this way.
+
+use crate::{
+    PayType, clock::Clock, config::AppConfig, conversation_message::FunctionCall, dates,
+    holidays::HolidayCalendar,
+};
+use strum::IntoEnumIterator;
+
+/// Recorded as the prompt version of calls the offline rules made.
+pub const VERSION: &str = "offline-rules-1";
+
+// Words that name a pay type, besides the pay types' own names
+const SYNONYMS: [(&str, PayType); 11] = [
+    ("ill", PayType::Sick),
+    ("unwell", PayType::Sick),
+    ("pto", PayType::Vacation),
+    ("vacay", PayType::Vacation),
+    ("holidays", PayType::Holiday),
+    ("maternity", PayType::Parental),
+    ("paternity", PayType::Parental),
+    ("worked", PayType::Salary),
+    ("working", PayType::Salary),
+    ("work", PayType::Salary),
+    ("regular", PayType::Salary),
+];
+
+/// The `set_pay_type` call the prompt asks for, or why it couldn't be worked out.
+pub fn parse(config: &AppConfig, clock: &Clock, prompt: &str) -> Result<FunctionCall, String> {
+    let words: Vec<String> = prompt
+        .split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    let mut pay_types: Vec<PayType> = Vec::new();
+    let mut rest: Vec<&str> = Vec::new();
+    for word in &words {
+        match pay_type(word) {
+            Some(found) => {
+                if !pay_types.contains(&found) {
+                    pay_types.push(found);
+                }
+            }
+            None => rest.push(word),
+        }
+    }
+    // "may" is only the month when a day follows it, as in "may 5"; "I may be sick" isn't May
+    let rest: Vec<&str> = rest
+        .iter()
+        .enumerate()
+        .filter(|(i, word)| {
+            **word != "may"
+                || rest
+                    .get(i + 1)
+                    .is_some_and(|next| next.starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .map(|(_, word)| *word)
+        .collect();
+    let pay_type = match pay_types.as_slice() {
+        [pay_type] => pay_type.clone(),
+        [] => {
+            return Err(format!(
+                "Say which pay type, e.g. {}",
+                PayType::iter()
+                    .map(|pt| pt.to_string().to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        _ => {
+            return Err(format!(
+                "It mentions more than one pay type: {}",
+                pay_types
+                    .iter()
+                    .map(|pt| pt.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    };
+
+    let context = dates::DateContext {
+        today: clock.today(config),
+        week_start: config.week_start(),
+        holidays: HolidayCalendar::load(config),
+    };
+    let dates = find_dates(&rest, &context)
+        .ok_or_else(|| "Say which days, e.g. today, friday or next week".to_string())?;
+
+    // A run of days is passed as a range, so weekends and holidays in it are skipped
+    let contiguous = dates
+        .windows(2)
+        .all(|pair| pair[0].succ_opt() == Some(pair[1]));
+    let format = |date: &chrono::NaiveDate| date.format("%Y-%m-%d").to_string();
+    let arguments = match (dates.first(), dates.last()) {
+        (Some(start), Some(end)) if dates.len() > 1 && contiguous => serde_json::json!({
+            "start": format(start),
+            "end": format(end),
+            "pay_type": pay_type.to_string(),
+        }),
+        _ => serde_json::json!({
+            "dates": dates.iter().map(format).collect::<Vec<_>>(),
+            "pay_type": pay_type.to_string(),
+        }),
+    };
+    Ok(FunctionCall {
+        name: "set_pay_type".to_string(),
+        arguments: arguments.to_string(),
+        prompt_version: Some(VERSION.to_string()),
+        usage: None,
+    })
+}
+
+fn pay_type(word: &str) -> Option<PayType> {
+    PayType::iter()
+        .find(|pt| pt.to_string().eq_ignore_ascii_case(word))
+        .or_else(|| {
+            SYNONYMS
+                .iter()
+                .find(|(synonym, _)| *synonym == word)
+                .map(|(_, pt)| pt.clone())
+        })
+}
+
+// The longest run of words that's a date expression, earliest first among runs as long.
+// "I was out monday to wednesday" tries the whole sentence, then shorter runs down to
+// "monday to wednesday"
+fn find_dates(words: &[&str], context: &dates::DateContext) -> Option<Vec<chrono::NaiveDate>> {
+    for len in (1..=words.len()).rev() {
+        for window in words.windows(len) {
+            if let Ok(dates) = dates::resolve(&window.join(" "), context)
+                && !dates.is_empty()
+            {
+                return Some(dates);
+            }
+        }
+    }
+    None
+}
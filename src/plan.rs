@@ -0,0 +1,130 @@
+//! Function calls for models without function calling. The model is told about the functions
+//! in the system prompt and replies with a JSON plan, either an answer for the user or a
+//! function to call, which is read back into the same [`AgentResponse`] function calling gives.
+
+use crate::{
+    AgentResponse,
+    config::{AppConfig, ToolCalling},
+    conversation_message::{ConversationMessage, FunctionCall, Role},
+    gpt,
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// Starts the message that hands a function's result back to the model.
+pub const RESULT_PREFIX: &str = "Result of ";
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Plan {
+    Reply {
+        message: String,
+    },
+    Call {
+        function: String,
+        #[serde(default)]
+        arguments: Value,
+    },
+}
+
+/// Whether the config asks for JSON plans instead of function calls.
+pub fn enabled(config: &AppConfig) -> bool {
+    config.tool_calling != ToolCalling::Functions
+}
+
+/// The JSON schema a plan must match, for `response_format`.
+pub fn schema(config: &AppConfig) -> Value {
+    let names: Vec<Value> = gpt::get_functions_metadata(config)
+        .into_iter()
+        .map(|f| f["name"].clone())
+        .collect();
+    json!({
+        "type": "object",
+        "properties": {
+            "action": { "type": "string", "enum": ["reply", "call"] },
+            "message": { "type": "string" },
+            "function": { "type": "string", "enum": names },
+            "arguments": { "type": "object" }
+        },
+        "required": ["action"],
+        "additionalProperties": false
+    })
+}
+
+/// What's added to the system prompt: the plan format and the functions a plan may call.
+pub fn instructions(config: &AppConfig) -> String {
+    let functions: Vec<String> = gpt::get_functions_metadata(config)
+        .iter()
+        .map(|f| {
+            format!(
+                "- {}: {} Arguments, as JSON schema: {}",
+                f["name"].as_str().unwrap_or_default(),
+                f["description"].as_str().unwrap_or_default(),
+                f["parameters"]
+            )
+        })
+        .collect();
+    format!(
+        "You can't call functions directly. Reply with a single JSON object and nothing else: \
+         {{\"action\": \"reply\", \"message\": \"...\"}} to answer the user, or \
+         {{\"action\": \"call\", \"function\": \"...\", \"arguments\": {{...}}}} to call one of \
+         these functions:\n{}\nA function's result comes back in a message starting with \
+         \"{}<function>:\".",
+        functions.join("\n"),
+        RESULT_PREFIX
+    )
+}
+
+/// The message as a model without function calling can read it: a call becomes the plan
+/// that asked for it and a function's result becomes a user message.
+pub fn to_plain_message(message: &ConversationMessage) -> ConversationMessage {
+    match (&message.role, &message.function_call) {
+        (_, Some(call)) => {
+            let arguments: Value = serde_json::from_str(&call.arguments).unwrap_or(json!({}));
+            ConversationMessage::new_content(
+                Role::Assistant,
+                json!({ "action": "call", "function": call.name, "arguments": arguments })
+                    .to_string(),
+            )
+        }
+        (Role::Function, None) => ConversationMessage::new_content(
+            Role::User,
+            format!(
+                "{}{}: {}",
+                RESULT_PREFIX,
+                message.name.as_deref().unwrap_or("the function"),
+                message.content
+            ),
+        ),
+        _ => message.clone(),
+    }
+}
+
+/// Reads a plan from the model's reply. Code fences and text around the JSON are ignored;
+/// a reply with no plan in it at all is taken as an answer for the user.
+pub fn parse(reply: &str) -> Result<AgentResponse, String> {
+    let json = match (reply.find('{'), reply.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => {
+            tracing::warn!("The model's reply has no JSON plan, showing it as it is");
+            return Ok(AgentResponse::Message(reply.trim().to_string()));
+        }
+    };
+    let plan: Plan = serde_json::from_str(json)
+        .map_err(|e| format!("The model's plan isn't one the agent understands: {}", e))?;
+    Ok(match plan {
+        Plan::Reply { message } => AgentResponse::Message(message),
+        Plan::Call {
+            function,
+            arguments,
+        } => AgentResponse::FunctionCall(FunctionCall {
+            name: function,
+            arguments: match arguments {
+                Value::Null => "{}".to_string(),
+                arguments => arguments.to_string(),
+            },
+            prompt_version: None,
+            usage: None,
+        }),
+    })
+}
@@ -0,0 +1,84 @@
+//! What a prompt is doing while it runs, so front ends can show more than a spinner.
+//! Events are passed to [`ExecutionOptions::on_progress`](crate::ExecutionOptions) as they happen.
+
+use serde::Serialize;
+use std::{fmt::Display, sync::Arc};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// The model is being asked, `step` counting from 1 as lookups send it back again
+    ModelRequest { step: usize },
+    /// The model replied, with the function it called if it didn't answer in words
+    ModelResponse { function: Option<String> },
+    /// The model's arguments for a function didn't fit its schema, so it's asked to fix them
+    Repairing { function: String, problem: String },
+    /// The prompt is read by the offline rules instead of the model, with why if the model failed
+    OfflineRules { reason: Option<String> },
+    /// Reading an employee's time entries for the days to change
+    EbmsRead { employee: String, days: usize },
+    /// How many of the entries read need a new pay code
+    Planned { changes: usize },
+    /// Writing the new pay code to this many entries
+    EbmsWrite { rows: usize },
+    /// Read the entries back and found this many with the new pay code
+    Verified { rows: usize },
+}
+
+impl Display for ProgressEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgressEvent::ModelRequest { step: 1 } => write!(f, "Asking the model..."),
+            ProgressEvent::ModelRequest { .. } => write!(f, "Asking the model again..."),
+            ProgressEvent::ModelResponse { function: None } => write!(f, "The model replied"),
+            ProgressEvent::ModelResponse {
+                function: Some(function),
+            } => write!(f, "The model called {}", function),
+            ProgressEvent::Repairing { function, .. } => {
+                write!(f, "Asking the model to fix its {} arguments...", function)
+            }
+            ProgressEvent::OfflineRules { reason: None } => write!(f, "Using the offline rules"),
+            ProgressEvent::OfflineRules { reason: Some(_) } => {
+                write!(
+                    f,
+                    "The model isn't available, using the offline rules instead"
+                )
+            }
+            ProgressEvent::EbmsRead { employee, days } => {
+                write!(
+                    f,
+                    "Fetching {} for {}...",
+                    plural(*days, "day", "days"),
+                    employee
+                )
+            }
+            ProgressEvent::Planned { changes } => {
+                write!(f, "{} to change", plural(*changes, "entry", "entries"))
+            }
+            ProgressEvent::EbmsWrite { rows } => {
+                write!(f, "Updating {}...", plural(*rows, "row", "rows"))
+            }
+            ProgressEvent::Verified { rows } => {
+                write!(
+                    f,
+                    "Checked {} in EBMS",
+                    plural(*rows, "updated row", "updated rows")
+                )
+            }
+        }
+    }
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+/// Receives progress events as a prompt runs.
+#[derive(Clone)]
+pub struct ProgressSink(pub Arc<dyn Fn(&ProgressEvent) + Send + Sync>);
+
+impl std::fmt::Debug for ProgressSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressSink")
+    }
+}
@@ -0,0 +1,209 @@
+//! The system prompt the model is given, as a template with `{{variable}}` placeholders.
+//! The built-in template can be replaced with `system_prompt.txt` in the config directory.
+//! Every template has a version that's recorded with the changes the model asks for.
+
+use crate::{
+    PayType,
+    config::{AppConfig, config_dir},
+    holidays::HolidayCalendar,
+};
+use chrono::{Datelike, NaiveDate, Weekday};
+use std::path::PathBuf;
+use strum::IntoEnumIterator;
+
+const PROMPT_FILE: &str = "system_prompt.txt";
+
+/// Bump whenever the built-in template's wording changes.
+pub const BUILT_IN_VERSION: &str = "builtin-3";
+
+const BUILT_IN_TEMPLATE: &str = "\
+You are a helpful assistant that can set pay types for employees. \
+You are talking to {{employee_name}}. \
+If no pay type is specified, use Salary by default. \
+The pay types and the company's pay codes for them are: {{pay_codes}}. \
+Managers may ask you to change time for someone else, e.g. \"mark Jane's Friday as sick\"; pass that person as the employee. \
+Today's date is {{today}}, the week begins on {{week_start}}. \
+Don't work out dates yourself: call resolve_dates with the user's wording, e.g. \"next friday\" or \"the week after thanksgiving\", and use the dates it returns. \
+For a run of days such as a week of vacation, pass start and end to set_pay_type instead of listing every date; weekends and company holidays are skipped for you. \
+This year's company holidays are: {{holidays}}. \
+Call list_holidays to answer questions about holidays in other years, or when asked to mark the holidays in a year, then set those dates to Holiday. \
+If the user asks you to undo a change and the record shows that you made a change, you should set it back to what you originally said it was.";
+
+/// The variables a template can use.
+pub const VARIABLES: [&str; 5] = [
+    "today",
+    "week_start",
+    "employee_name",
+    "pay_codes",
+    "holidays",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    /// `BUILT_IN_VERSION`, or for a custom file the version it declares on a
+    /// `# version: ...` first line, else "custom-" and a hash of its text
+    pub version: String,
+    pub text: String,
+}
+
+impl PromptTemplate {
+    pub fn built_in() -> Self {
+        PromptTemplate {
+            version: BUILT_IN_VERSION.to_string(),
+            text: BUILT_IN_TEMPLATE.to_string(),
+        }
+    }
+
+    /// A template read from a file, with its optional `# version: ...` line taken off.
+    pub fn custom(contents: &str) -> Self {
+        let declared = contents.lines().next().and_then(|line| {
+            let version = line
+                .trim()
+                .strip_prefix('#')?
+                .trim()
+                .strip_prefix("version:")?;
+            Some(version.trim().to_string()).filter(|v| !v.is_empty())
+        });
+        match declared {
+            Some(version) => PromptTemplate {
+                version,
+                text: contents
+                    .split_once('\n')
+                    .map(|(_, rest)| rest)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            },
+            None => PromptTemplate {
+                version: format!("custom-{:08x}", fnv1a(contents.trim().as_bytes()) as u32),
+                text: contents.trim().to_string(),
+            },
+        }
+    }
+
+    /// The configured template file if there is one and it can be read, else the built-in template.
+    pub fn load(config: &AppConfig) -> Self {
+        let Some(path) = template_path(config) else {
+            return Self::built_in();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) if !contents.trim().is_empty() => Self::custom(&contents),
+            Ok(_) => {
+                tracing::warn!("{} is empty, using the built-in prompt", path.display());
+                Self::built_in()
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to read the prompt from {}, using the built-in one: {}",
+                    path.display(),
+                    e
+                );
+                Self::built_in()
+            }
+        }
+    }
+
+    /// The text with each `{{variable}}` filled in. Unknown variables are left as they are.
+    pub fn render(&self, variables: &PromptVariables) -> String {
+        let mut rendered = String::new();
+        let mut rest = self.text.as_str();
+        while let Some(open) = rest.find("{{") {
+            rendered.push_str(&rest[..open]);
+            let after = &rest[open + 2..];
+            let Some(close) = after.find("}}") else {
+                rendered.push_str(&rest[open..]);
+                rest = "";
+                break;
+            };
+            let name = after[..close].trim();
+            match variables.get(name) {
+                Some(value) => rendered.push_str(&value),
+                None => {
+                    tracing::warn!("Unknown prompt variable '{}'", name);
+                    rendered.push_str(&rest[open..open + 2 + close + 2]);
+                }
+            }
+            rest = &after[close + 2..];
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+/// What the template's variables stand for in one conversation.
+#[derive(Debug, Clone)]
+pub struct PromptVariables {
+    pub today: NaiveDate,
+    pub week_start: Weekday,
+    pub employee_name: String,
+    /// Each pay type with the code it's written as
+    pub pay_codes: Vec<(PayType, String)>,
+    /// This year's company holidays, as date and name
+    pub holidays: Vec<(NaiveDate, String)>,
+}
+
+impl PromptVariables {
+    pub fn new(config: &AppConfig, today: NaiveDate, employee_name: &str) -> Self {
+        let profile = config.profile().cloned().unwrap_or_default();
+        PromptVariables {
+            today,
+            week_start: config.week_start(),
+            employee_name: employee_name.to_string(),
+            pay_codes: PayType::iter()
+                .map(|pt| {
+                    let code = profile.pay_code(&pt);
+                    (pt, code)
+                })
+                .collect(),
+            holidays: HolidayCalendar::load(config)
+                .in_year(today.year())
+                .into_iter()
+                .map(|h| (h.date, h.name))
+                .collect(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        Some(match name {
+            // e.g. "Monday, 2025-05-26", so the model knows what "wednesday" means
+            "today" => self.today.format("%A, %Y-%m-%d").to_string(),
+            "week_start" => self.week_start.to_string(),
+            "employee_name" => self.employee_name.clone(),
+            "pay_codes" => self
+                .pay_codes
+                .iter()
+                .map(|(pt, code)| format!("{} is {}", pt, code))
+                .collect::<Vec<_>>()
+                .join(", "),
+            "holidays" if self.holidays.is_empty() => "none configured".to_string(),
+            "holidays" => self
+                .holidays
+                .iter()
+                .map(|(date, name)| {
+                    format!("{} {}", date.format("%Y-%m-%d"), name)
+                        .trim_end()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => return None,
+        })
+    }
+}
+
+/// The configured template file, or `system_prompt.txt` in the config directory if that exists.
+pub fn template_path(config: &AppConfig) -> Option<PathBuf> {
+    match &config.system_prompt_path {
+        Some(path) => Some(path.clone()),
+        None => config_dir()
+            .map(|dir| dir.join(PROMPT_FILE))
+            .filter(|path| path.exists()),
+    }
+}
+
+// A stable hash, so the same file always gets the same version
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
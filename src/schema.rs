@@ -0,0 +1,170 @@
+//! Checks a function call's arguments against the JSON schema the function is declared with,
+//! so a malformed call can be handed back to the model to fix. Covers the parts of JSON
+//! Schema the declarations use.
+
+use crate::{config::AppConfig, conversation_message::FunctionCall, gpt};
+use serde_json::Value;
+
+/// The call's arguments, parsed, if they fit the schema of the function it names.
+/// Otherwise every problem found, worded so the model can correct them.
+pub fn check_call(config: &AppConfig, call: &FunctionCall) -> Result<Value, String> {
+    let definitions = gpt::get_functions_metadata(config);
+    let definition = definitions
+        .iter()
+        .find(|f| f["name"] == call.name.as_str())
+        .ok_or_else(|| format!("There is no function named {}", call.name))?;
+    let arguments: Value = serde_json::from_str(&call.arguments)
+        .map_err(|e| format!("The arguments aren't valid JSON: {}", e))?;
+    let problems = validate(&arguments, &definition["parameters"]);
+    if problems.is_empty() {
+        Ok(arguments)
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+/// Where and how `value` doesn't match `schema`, e.g. "dates[1]: expected a date in
+/// YYYY-MM-DD format". Empty if it matches.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut problems = Vec::new();
+    check(value, schema, "arguments", &mut problems);
+    problems
+}
+
+fn check(value: &Value, schema: &Value, path: &str, problems: &mut Vec<String>) {
+    if let Some(expected) = schema["type"].as_str()
+        && !has_type(value, expected)
+    {
+        problems.push(format!("{}: expected {}, got {}", path, a(expected), value));
+        return;
+    }
+    if let Some(allowed) = schema["enum"].as_array()
+        && !allowed.contains(value)
+    {
+        let allowed: Vec<String> = allowed.iter().map(|v| v.to_string()).collect();
+        problems.push(format!(
+            "{}: {} isn't one of {}",
+            path,
+            value,
+            allowed.join(", ")
+        ));
+    }
+    if schema["format"] == "date"
+        && let Some(text) = value.as_str()
+        && chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").is_err()
+    {
+        problems.push(format!(
+            "{}: expected a date in YYYY-MM-DD format, got {}",
+            path, value
+        ));
+    }
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema["minimum"].as_f64()
+            && number < minimum
+        {
+            problems.push(format!("{}: must be at least {}", path, minimum));
+        }
+        if let Some(maximum) = schema["maximum"].as_f64()
+            && number > maximum
+        {
+            problems.push(format!("{}: must be at most {}", path, maximum));
+        }
+    }
+    match value {
+        Value::Array(items) => check_array(items, schema, path, problems),
+        Value::Object(fields) => check_object(fields, schema, path, problems),
+        _ => {}
+    }
+}
+
+fn check_array(items: &[Value], schema: &Value, path: &str, problems: &mut Vec<String>) {
+    if let Some(min) = schema["minItems"].as_u64()
+        && (items.len() as u64) < min
+    {
+        problems.push(format!("{}: needs at least {} item(s)", path, min));
+    }
+    if let Some(max) = schema["maxItems"].as_u64()
+        && items.len() as u64 > max
+    {
+        problems.push(format!("{}: allows at most {} item(s)", path, max));
+    }
+    if schema["items"].is_object() {
+        for (i, item) in items.iter().enumerate() {
+            check(
+                item,
+                &schema["items"],
+                &format!("{}[{}]", path, i),
+                problems,
+            );
+        }
+    }
+}
+
+fn check_object(
+    fields: &serde_json::Map<String, Value>,
+    schema: &Value,
+    path: &str,
+    problems: &mut Vec<String>,
+) {
+    let field_path = |name: &str| match path {
+        "arguments" => name.to_string(),
+        _ => format!("{}.{}", path, name),
+    };
+    for name in schema["required"].as_array().into_iter().flatten() {
+        if let Some(name) = name.as_str()
+            && !fields.contains_key(name)
+        {
+            problems.push(format!("{}: is required", field_path(name)));
+        }
+    }
+    // `{"start": ["end"]}`: a field that's only allowed together with others
+    if let Some(dependencies) = schema["dependentRequired"].as_object() {
+        for (name, needed) in dependencies {
+            if !fields.contains_key(name) {
+                continue;
+            }
+            for other in needed.as_array().into_iter().flatten() {
+                if let Some(other) = other.as_str()
+                    && !fields.contains_key(other)
+                {
+                    problems.push(format!(
+                        "{}: is required when {} is given",
+                        field_path(other),
+                        name
+                    ));
+                }
+            }
+        }
+    }
+    let properties = schema["properties"].as_object();
+    for (name, value) in fields {
+        match properties.and_then(|p| p.get(name)) {
+            Some(property) => check(value, property, &field_path(name), problems),
+            None if schema["additionalProperties"] == false => {
+                problems.push(format!("{}: isn't a known argument", field_path(name)));
+            }
+            None => {}
+        }
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+// "an object", "a string"
+fn a(type_name: &str) -> String {
+    match type_name.chars().next() {
+        Some('a' | 'e' | 'i' | 'o' | 'u') => format!("an {}", type_name),
+        _ => format!("a {}", type_name),
+    }
+}
@@ -0,0 +1,205 @@
+//! Token usage and cost of model calls. Every call the API reports usage for is appended to
+//! `usage.jsonl` next to the audit log, which is what daily and monthly totals and the
+//! optional monthly budget are worked out from.
+
+use crate::{
+    audit,
+    clock::{self, Clock},
+    config::{AppConfig, config_dir},
+};
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::{ops::Range, path::PathBuf};
+
+const USAGE_FILE: &str = "usage.jsonl";
+
+/// Tokens as reported in a chat completion's `usage` block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl Usage {
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// What a model costs, in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelRate {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+/// List prices for the models the agent is usually run with, used for any model the config's
+/// rate table doesn't list.
+pub fn default_rates() -> Vec<(&'static str, ModelRate)> {
+    [
+        ("gpt-4", 30.0, 60.0),
+        ("gpt-4-turbo", 10.0, 30.0),
+        ("gpt-4o", 2.5, 10.0),
+        ("gpt-4o-mini", 0.15, 0.6),
+    ]
+    .into_iter()
+    .map(|(model, prompt, completion)| {
+        (
+            model,
+            ModelRate {
+                prompt_per_million: prompt,
+                completion_per_million: completion,
+            },
+        )
+    })
+    .collect()
+}
+
+pub fn rate(config: &AppConfig, model: &str) -> Option<ModelRate> {
+    config.model_rates.get(model).copied().or_else(|| {
+        default_rates()
+            .into_iter()
+            .find(|(name, _)| *name == model)
+            .map(|(_, rate)| rate)
+    })
+}
+
+/// The cost in US dollars, if the model has a rate.
+pub fn cost(config: &AppConfig, model: &str, usage: Usage) -> Option<f64> {
+    let rate = rate(config, model)?;
+    Some(
+        (usage.prompt_tokens as f64 * rate.prompt_per_million
+            + usage.completion_tokens as f64 * rate.completion_per_million)
+            / 1_000_000.0,
+    )
+}
+
+/// One model call, as a line of `usage.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub timestamp: DateTime<Local>,
+    pub profile: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub usage: Usage,
+    /// `None` if the model had no rate at the time
+    pub cost: Option<f64>,
+}
+
+fn usage_path(config: &AppConfig) -> Option<PathBuf> {
+    match &config.audit_log_path {
+        Some(path) => Some(path.with_file_name(USAGE_FILE)),
+        None => config_dir().map(|dir| dir.join(USAGE_FILE)),
+    }
+}
+
+pub fn record(config: &AppConfig, clock: &Clock, model: &str, usage: Usage) {
+    let record = UsageRecord {
+        timestamp: clock.instant(config),
+        profile: config.current_profile.clone().unwrap_or_default(),
+        model: model.to_string(),
+        usage,
+        cost: cost(config, model, usage),
+    };
+    let result = usage_path(config)
+        .ok_or_else(|| "No config directory".into())
+        .and_then(|path| audit::append_json(&path, &record));
+    if let Err(e) = result {
+        tracing::error!("Failed to write usage log: {}", e);
+    }
+}
+
+/// Every recorded call, oldest first. Unreadable lines are skipped.
+pub fn load(config: &AppConfig) -> Vec<UsageRecord> {
+    usage_path(config)
+        .map(|path| audit::load_json(&path))
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UsageTotals {
+    pub calls: usize,
+    pub usage: Usage,
+    /// Cost of the calls that had a rate
+    pub cost: f64,
+    /// Calls to models without a rate, so left out of `cost`
+    pub unpriced_calls: usize,
+}
+
+impl std::fmt::Display for UsageTotals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} tokens, ${:.2}", self.usage.total(), self.cost)?;
+        if self.unpriced_calls > 0 {
+            write!(f, " (+{} unpriced)", self.unpriced_calls)?;
+        }
+        Ok(())
+    }
+}
+
+/// Totals of the records at or after `since`.
+pub fn totals_since(records: &[UsageRecord], since: DateTime<Local>) -> UsageTotals {
+    totals(records.iter().filter(|r| r.timestamp >= since))
+}
+
+/// Totals of the records made on the `days` in the company's calendar, e.g. today's or
+/// this month's.
+pub fn totals_between(
+    config: &AppConfig,
+    records: &[UsageRecord],
+    days: Range<NaiveDate>,
+) -> UsageTotals {
+    totals(
+        records
+            .iter()
+            .filter(|r| days.contains(&clock::company_date(config, &r.timestamp))),
+    )
+}
+
+fn totals<'a>(records: impl Iterator<Item = &'a UsageRecord>) -> UsageTotals {
+    let mut totals = UsageTotals::default();
+    for record in records {
+        totals.calls += 1;
+        totals.usage += record.usage;
+        match record.cost {
+            Some(cost) => totals.cost += cost,
+            None => totals.unpriced_calls += 1,
+        }
+    }
+    totals
+}
+
+/// The day `date` is on, as a range for `totals_between`.
+pub fn day_of(date: NaiveDate) -> Range<NaiveDate> {
+    date..date + Days::new(1)
+}
+
+/// The days of the month `date` is in.
+pub fn month_of(date: NaiveDate) -> Range<NaiveDate> {
+    let first = date.with_day(1).unwrap_or(date);
+    first..first + Months::new(1)
+}
+
+/// An error once this month's spending has reached the configured budget.
+/// The month is the one `clock` is in, in the company's timezone.
+pub fn check_budget(config: &AppConfig, clock: &Clock) -> Result<(), String> {
+    if config.monthly_budget_usd <= 0.0 {
+        return Ok(());
+    }
+    let month = month_of(clock.today(config));
+    let spent = totals_between(config, &load(config), month).cost;
+    if spent >= config.monthly_budget_usd {
+        return Err(format!(
+            "This month's model budget of ${:.2} has been used up (${:.2} spent). \
+             Raise it in the settings to keep going.",
+            config.monthly_budget_usd, spent
+        ));
+    }
+    Ok(())
+}
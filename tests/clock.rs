@@ -0,0 +1,56 @@
+use agent::{Employee, PayType, PayTypeChange, clock::Clock, config::AppConfig};
+
+fn in_timezone(name: &str) -> AppConfig {
+    AppConfig {
+        company_timezone: name.to_string(),
+        ..AppConfig::empty()
+    }
+}
+
+#[test]
+fn company_timezone_decides_today() {
+    // UTC+14 and UTC-11 are always on different calendar days: one apart, or two from
+    // 10:00 to 10:59 UTC
+    let ahead = Clock::System.today(&in_timezone("Pacific/Kiritimati"));
+    let behind = Clock::System.today(&in_timezone("Pacific/Pago_Pago"));
+
+    assert!(ahead > behind, "{} should be after {}", ahead, behind);
+    assert!(ahead - behind <= chrono::Duration::days(2));
+}
+
+#[test]
+fn fixed_clock_ignores_timezone() {
+    let date = "2025-06-04".parse().unwrap();
+    let clock = Clock::fixed_date(date);
+
+    assert_eq!(clock.today(&in_timezone("Pacific/Kiritimati")), date);
+    assert_eq!(clock.today(&AppConfig::empty()), date);
+}
+
+#[test]
+fn describe_leaves_out_the_current_year() {
+    let change = PayTypeChange {
+        employee: Employee {
+            id: "E100".to_string(),
+            name: "Pat Smith".to_string(),
+        },
+        date: "2025-06-06".parse().unwrap(),
+        old_pay_type: "Salary".to_string(),
+        pay_type: PayType::Vacation,
+        pay_code: "Vac-SAL".to_string(),
+        function_call: None,
+    };
+
+    assert_eq!(
+        change.describe("2025-01-01".parse().unwrap()),
+        "Pat Smith (E100): set pay type for Fri June 06 from Salary to Vac-SAL"
+    );
+    assert_eq!(
+        change.describe("2026-01-01".parse().unwrap()),
+        "Pat Smith (E100): set pay type for Fri June 06, 2025 from Salary to Vac-SAL"
+    );
+    assert_eq!(
+        change.to_string(),
+        change.describe("2026-01-01".parse().unwrap())
+    );
+}
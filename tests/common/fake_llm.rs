@@ -0,0 +1,152 @@
+//! A stand-in for the chat completions API that replays scripted replies.
+//! Point `AppConfig::gpt_api_url` at [`FakeLlm::url`].
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde_json::{Value, json};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Default)]
+struct Script {
+    replies: VecDeque<Value>,
+    requests: Vec<Value>,
+}
+
+pub struct FakeLlm {
+    pub url: String,
+    script: Arc<Mutex<Script>>,
+}
+
+impl FakeLlm {
+    /// Starts serving on a free local port. Each request is answered with the next reply,
+    /// an assistant message such as [`text_reply`] or [`function_call_reply`]. Requests asking
+    /// for a stream get the reply as server-sent events, a few characters at a time.
+    pub async fn start(replies: Vec<Value>) -> std::io::Result<Self> {
+        let script = Arc::new(Mutex::new(Script {
+            replies: replies.into(),
+            requests: Vec::new(),
+        }));
+        let app = Router::new()
+            .route("/v1/chat/completions", post(chat_completions))
+            .with_state(script.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/v1/chat/completions", listener.local_addr()?);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Ok(FakeLlm { url, script })
+    }
+
+    /// The request bodies received so far, oldest first.
+    pub fn requests(&self) -> Vec<Value> {
+        self.script.lock().unwrap().requests.clone()
+    }
+}
+
+pub fn text_reply(content: &str) -> Value {
+    json!({ "role": "assistant", "content": content })
+}
+
+pub fn function_call_reply(name: &str, arguments: &Value) -> Value {
+    json!({
+        "role": "assistant",
+        "content": null,
+        "function_call": { "name": name, "arguments": arguments.to_string() },
+    })
+}
+
+/// The reply, with the API reporting it used these tokens.
+pub fn with_usage(mut reply: Value, prompt_tokens: u64, completion_tokens: u64) -> Value {
+    reply["usage"] = json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+    });
+    reply
+}
+
+async fn chat_completions(
+    State(script): State<Arc<Mutex<Script>>>,
+    Json(body): Json<Value>,
+) -> Response {
+    let mut script = script.lock().unwrap();
+    let stream = body["stream"].as_bool().unwrap_or_default();
+    script.requests.push(body);
+    match script.replies.pop_front() {
+        Some(mut message) => {
+            // Usage belongs to the response, not the message
+            let usage = message
+                .as_object_mut()
+                .and_then(|m| m.remove("usage"))
+                .unwrap_or(Value::Null);
+            if stream {
+                return (
+                    [(header::CONTENT_TYPE, "text/event-stream")],
+                    event_stream(&message, usage),
+                )
+                    .into_response();
+            }
+            Json(json!({
+                "object": "chat.completion",
+                "choices": [{ "index": 0, "message": message, "finish_reason": "stop" }],
+                "usage": usage,
+            }))
+            .into_response()
+        }
+        None => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "The fake model has no more scripted replies",
+        )
+            .into_response(),
+    }
+}
+
+// The reply as chat.completion.chunk events: text and function arguments in small pieces,
+// then the usage on a chunk of its own
+fn event_stream(message: &Value, usage: Value) -> String {
+    let mut deltas = vec![json!({ "role": "assistant" })];
+    if let Some(content) = message["content"].as_str() {
+        deltas.extend(pieces(content).map(|piece| json!({ "content": piece })));
+    }
+    if let Some(call) = message.get("function_call").filter(|c| !c.is_null()) {
+        deltas.push(json!({ "function_call": { "name": call["name"], "arguments": "" } }));
+        let arguments = call["arguments"].as_str().unwrap_or_default();
+        deltas.extend(
+            pieces(arguments).map(|piece| json!({ "function_call": { "arguments": piece } })),
+        );
+    }
+
+    let mut events: Vec<Value> = deltas
+        .into_iter()
+        .map(|delta| {
+            json!({
+                "object": "chat.completion.chunk",
+                "choices": [{ "index": 0, "delta": delta, "finish_reason": null }],
+            })
+        })
+        .collect();
+    if !usage.is_null() {
+        events.push(json!({ "object": "chat.completion.chunk", "choices": [], "usage": usage }));
+    }
+    let mut body: String = events
+        .iter()
+        .map(|event| format!("data: {}\n\n", event))
+        .collect();
+    body.push_str("data: [DONE]\n\n");
+    body
+}
+
+fn pieces(text: &str) -> impl Iterator<Item = String> + '_ {
+    let chars: Vec<char> = text.chars().collect();
+    (0..chars.len())
+        .step_by(8)
+        .map(move |i| chars[i..(i + 8).min(chars.len())].iter().collect())
+}
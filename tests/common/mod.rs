@@ -0,0 +1,353 @@
+//! An in-process stand-in for the EBMS OData API, holding time details and employees in memory.
+
+#![allow(dead_code)] // each test binary uses a different part of the mock
+
+pub mod fake_llm;
+
+use agent::config::{AppConfig, ConnectionProfile};
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use base64::Engine;
+use chrono::NaiveDate;
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+pub const USERNAME: &str = "api-user";
+pub const PASSWORD: &str = "api-password";
+pub const PROFILE_NAME: &str = "Mock";
+const MODIFY_PATH: &str =
+    "/TimeDetailManager(c2e90ee5-3e20-473c-9b2c-979a6a2ce6e2)/Model.Entities.ModifyTimeEntries";
+
+#[derive(Debug, Clone)]
+pub struct MockEntry {
+    pub autoid: String,
+    pub employee_id: String,
+    pub date: NaiveDate,
+    pub pay_level: String,
+}
+
+#[derive(Default)]
+struct MockState {
+    entries: Vec<MockEntry>,
+    employees: Vec<(String, String, String)>,
+    // Employee ID to their supervisor's
+    supervisors: HashMap<String, String>,
+    modify_requests: Vec<Value>,
+    query_failure: Option<(StatusCode, String)>,
+    modify_failure: Option<(StatusCode, String)>,
+    ignore_modifications: bool,
+    employee_queries: usize,
+}
+
+pub struct MockEbms {
+    pub url: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockEbms {
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let app = Router::new()
+            .route("/PYTMDET", get(query_pytmdet))
+            .route("/PREMPLOY", get(query_premploy))
+            .route(MODIFY_PATH, post(modify_time_entries))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        MockEbms { url, state }
+    }
+
+    pub fn add_employee(&self, id: &str, first_name: &str, last_name: &str) {
+        self.state.lock().unwrap().employees.push((
+            id.to_string(),
+            first_name.to_string(),
+            last_name.to_string(),
+        ));
+    }
+
+    /// Makes `supervisor` the employee's supervisor, so they may change the employee's time.
+    pub fn set_supervisor(&self, employee_id: &str, supervisor: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .supervisors
+            .insert(employee_id.to_string(), supervisor.to_string());
+    }
+
+    /// Adds a time detail line and returns its AUTOID.
+    pub fn add_entry(&self, employee_id: &str, date: &str, pay_level: &str) -> String {
+        let mut state = self.state.lock().unwrap();
+        let autoid = format!("AUTO{:04}", state.entries.len() + 1);
+        state.entries.push(MockEntry {
+            autoid: autoid.clone(),
+            employee_id: employee_id.to_string(),
+            date: date.parse().unwrap(),
+            pay_level: pay_level.to_string(),
+        });
+        autoid
+    }
+
+    pub fn pay_level(&self, employee_id: &str, date: &str) -> Option<String> {
+        let date: NaiveDate = date.parse().unwrap();
+        self.state
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .find(|e| e.employee_id == employee_id && e.date == date)
+            .map(|e| e.pay_level.clone())
+    }
+
+    /// Bodies of every ModifyTimeEntries call received so far.
+    pub fn modify_requests(&self) -> Vec<Value> {
+        self.state.lock().unwrap().modify_requests.clone()
+    }
+
+    pub fn fail_queries(&self, status: StatusCode, body: &str) {
+        self.state.lock().unwrap().query_failure = Some((status, body.to_string()));
+    }
+
+    pub fn fail_modifications(&self, status: StatusCode, body: &str) {
+        self.state.lock().unwrap().modify_failure = Some((status, body.to_string()));
+    }
+
+    /// Answers ModifyTimeEntries with success but leaves the entries as they were.
+    pub fn ignore_modifications(&self) {
+        self.state.lock().unwrap().ignore_modifications = true;
+    }
+
+    /// How many PREMPLOY queries have been made so far.
+    pub fn employee_queries(&self) -> usize {
+        self.state.lock().unwrap().employee_queries
+    }
+
+    /// A profile for the employee, with their name if they've been added already, as it would
+    /// be after logging in.
+    pub fn profile(&self, employee_id: &str) -> ConnectionProfile {
+        let employee_name = self
+            .state
+            .lock()
+            .unwrap()
+            .employees
+            .iter()
+            .find(|(id, _, _)| id == employee_id)
+            .map(|(_, first, last)| format!("{} {}", first, last))
+            .unwrap_or_default();
+        ConnectionProfile {
+            name: PROFILE_NAME.to_string(),
+            ebms_url: self.url.clone(),
+            ebms_username: USERNAME.to_string(),
+            ebms_password: PASSWORD.to_string(),
+            employee_id: employee_id.to_string(),
+            employee_name,
+            ..Default::default()
+        }
+    }
+
+    /// A config logged in to this mock as the employee, auditing into `dir`.
+    pub fn config(&self, employee_id: &str, dir: &Path) -> AppConfig {
+        AppConfig {
+            profiles: vec![self.profile(employee_id)],
+            current_profile: Some(PROFILE_NAME.to_string()),
+            audit_log_path: Some(dir.join("audit.jsonl")),
+            ..AppConfig::empty()
+        }
+    }
+}
+
+fn authorized(headers: &HeaderMap) -> bool {
+    let expected =
+        base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", USERNAME, PASSWORD));
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == format!("Basic {}", expected))
+}
+
+fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, "Authorization has been denied").into_response()
+}
+
+// Pulls every `ID eq '...'` value out of an OData filter
+fn filter_ids(filter: &str) -> Vec<String> {
+    filter_values(filter, "ID")
+}
+
+// Pulls every `<column> eq '...'` value out of an OData filter
+fn filter_values(filter: &str, column: &str) -> Vec<String> {
+    filter
+        .split(&format!("{} eq '", column))
+        .skip(1)
+        .filter_map(|rest| rest.split('\'').next())
+        .map(str::to_string)
+        .collect()
+}
+
+// Pulls every `DATE <op> 2025-01-01T00:00:00Z` comparison out of an OData filter
+fn filter_dates(filter: &str) -> Vec<(String, NaiveDate)> {
+    let tokens: Vec<&str> = filter
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|t| !t.is_empty())
+        .collect();
+    tokens
+        .windows(3)
+        .filter(|w| w[0] == "DATE")
+        .filter_map(|w| Some((w[1].to_string(), w[2].get(..10)?.parse().ok()?)))
+        .collect()
+}
+
+fn select(params: &HashMap<String, String>, row: Value) -> Value {
+    let Some(fields) = params.get("$select") else {
+        return row;
+    };
+    let fields: Vec<&str> = fields.split(',').map(str::trim).collect();
+    match row {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(k, _)| fields.contains(&k.as_str()))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+async fn query_pytmdet(
+    State(state): State<Arc<Mutex<MockState>>>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    if !authorized(&headers) {
+        return unauthorized();
+    }
+    let state = state.lock().unwrap();
+    if let Some((status, body)) = &state.query_failure {
+        return (*status, body.clone()).into_response();
+    }
+
+    let filter = params.get("$filter").cloned().unwrap_or_default();
+    let ids = filter_ids(&filter);
+    let dates = filter_dates(&filter);
+    let equal: Vec<NaiveDate> = dates
+        .iter()
+        .filter(|(op, _)| op == "eq")
+        .map(|(_, d)| *d)
+        .collect();
+
+    let rows: Vec<Value> = state
+        .entries
+        .iter()
+        .filter(|e| ids.is_empty() || ids.contains(&e.employee_id))
+        .filter(|e| equal.is_empty() || equal.contains(&e.date))
+        .filter(|e| {
+            dates.iter().all(|(op, d)| match op.as_str() {
+                "ge" => e.date >= *d,
+                "le" => e.date <= *d,
+                _ => true,
+            })
+        })
+        .map(|e| {
+            select(
+                &params,
+                json!({
+                    "AUTOID": e.autoid,
+                    "ID": e.employee_id,
+                    "DATE": format!("{}T00:00:00Z", e.date.format("%Y-%m-%d")),
+                    "PAY_LEVEL": e.pay_level,
+                }),
+            )
+        })
+        .collect();
+    Json(json!({ "value": rows })).into_response()
+}
+
+async fn query_premploy(
+    State(state): State<Arc<Mutex<MockState>>>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    if !authorized(&headers) {
+        return unauthorized();
+    }
+    let mut state = state.lock().unwrap();
+    state.employee_queries += 1;
+    if let Some((status, body)) = &state.query_failure {
+        return (*status, body.clone()).into_response();
+    }
+
+    let filter = params.get("$filter").cloned().unwrap_or_default();
+    let ids = filter_ids(&filter);
+    let supervisors = filter_values(&filter, "SUPERVISOR");
+    let rows: Vec<Value> = state
+        .employees
+        .iter()
+        .filter(|(id, _, _)| {
+            (ids.is_empty() && supervisors.is_empty())
+                || ids.contains(id)
+                || state
+                    .supervisors
+                    .get(id)
+                    .is_some_and(|s| supervisors.contains(s))
+        })
+        .map(|(id, first, last)| {
+            select(
+                &params,
+                json!({ "ID": id, "F_NAME": first, "L_NAME": last }),
+            )
+        })
+        .collect();
+    Json(json!({ "value": rows })).into_response()
+}
+
+async fn modify_time_entries(
+    State(state): State<Arc<Mutex<MockState>>>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if !authorized(&headers) {
+        return unauthorized();
+    }
+    let mut state = state.lock().unwrap();
+    state.modify_requests.push(body.clone());
+    if let Some((status, body)) = &state.modify_failure {
+        return (*status, body.clone()).into_response();
+    }
+
+    let Some(modifications) = body["ModifyEntries"].as_array() else {
+        return (StatusCode::BAD_REQUEST, "ModifyEntries is required").into_response();
+    };
+    // Validate everything first so a bad row leaves the state untouched, like a transaction
+    let mut updates = Vec::new();
+    for modification in modifications {
+        let autoid = modification["AUTOID"].as_str().unwrap_or_default();
+        let Some(index) = state.entries.iter().position(|e| e.autoid == autoid) else {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Time entry {} does not exist", autoid),
+            )
+                .into_response();
+        };
+        let Some(pay_type) = modification["PayType"].as_str() else {
+            return (StatusCode::BAD_REQUEST, "PayType is required").into_response();
+        };
+        updates.push((index, pay_type.to_string()));
+    }
+    if state.ignore_modifications {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    for (index, pay_type) in updates {
+        state.entries[index].pay_level = pay_type;
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
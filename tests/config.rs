@@ -0,0 +1,107 @@
+use agent::{
+    PayType,
+    config::{AppConfig, ConnectionProfile},
+};
+
+fn logged_in(profile: ConnectionProfile) -> AppConfig {
+    AppConfig {
+        current_profile: Some(profile.name.clone()),
+        profiles: vec![profile],
+        ..AppConfig::empty()
+    }
+}
+
+fn valid_profile() -> ConnectionProfile {
+    ConnectionProfile {
+        name: "Test".to_string(),
+        ebms_url: "https://ecc1.servicebus.windows.net/MyEbms/ECC/OData".to_string(),
+        employee_id: "E100".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn defaults_are_valid() {
+    let config = logged_in(valid_profile());
+
+    assert!(config.validate().is_empty(), "{:?}", config.validate());
+    assert_eq!(config.gpt_model(), "gpt-4");
+    assert_eq!(config.gpt_timeout().as_secs(), 120);
+    assert_eq!(config.profile().unwrap().timeout().as_secs(), 30);
+}
+
+#[test]
+fn validation_reports_each_problem() {
+    let mut profile = valid_profile();
+    profile.ebms_url = "ecc1.servicebus.windows.net".to_string();
+    profile
+        .pay_codes
+        .insert("Sick".to_string(), "vac-sal".to_string());
+    profile
+        .pay_codes
+        .insert("Jury".to_string(), "JURY".to_string());
+    let config = AppConfig {
+        company_timezone: "Central".to_string(),
+        week_start: "Funday".to_string(),
+        work_days: vec!["Mon".to_string(), "Friyay".to_string()],
+        ..logged_in(profile)
+    };
+
+    let problems = config.validate();
+
+    assert_eq!(problems.len(), 6, "{:?}", problems);
+    for expected in [
+        "timezone 'Central'",
+        "'Funday'",
+        "'Friyay'",
+        "should start with https://",
+        "Sick and Vacation both use pay code",
+        "'Jury' isn't a pay type",
+    ] {
+        assert!(
+            problems.iter().any(|p| p.contains(expected)),
+            "no '{}' in {:?}",
+            expected,
+            problems
+        );
+    }
+}
+
+#[test]
+fn pay_codes_fall_back_to_the_built_in_ones() {
+    let mut profile = valid_profile();
+    profile
+        .pay_codes
+        .insert("Vacation".to_string(), "VAC".to_string());
+    profile
+        .pay_codes
+        .insert("Sick".to_string(), " ".to_string());
+
+    assert_eq!(profile.pay_code(&PayType::Vacation), "VAC");
+    assert_eq!(profile.pay_code(&PayType::Sick), "Sick-Sal");
+    assert_eq!(profile.pay_type_for_code("vac"), Some(PayType::Vacation));
+    assert_eq!(profile.pay_type_for_code("Vac-SAL"), None);
+}
+
+#[test]
+fn an_unreadable_config_file_is_kept_before_starting_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    let broken = "gpt_api_key = \"sk-test\"\nprofiles = [oops\n";
+    std::fs::write(&path, broken).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_agent-cli"))
+        .arg("usage")
+        .env(agent::config::CONFIG_ENV, &path)
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to read the config file"),
+        "{}",
+        stderr
+    );
+    let backup = dir.path().join("config.toml.bak");
+    assert_eq!(std::fs::read_to_string(backup).unwrap(), broken);
+}
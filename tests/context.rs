@@ -0,0 +1,111 @@
+mod common;
+
+use agent::{
+    clock::Clock,
+    config::{AppConfig, ContextPolicy},
+    context::{self, SUMMARY_PREFIX},
+    conversation_message::{ConversationMessage, FunctionCall, Role},
+};
+use common::fake_llm::{FakeLlm, text_reply};
+
+// Each of these is 4 + 40 / 4 = 14 tokens
+fn message(role: Role, n: usize) -> ConversationMessage {
+    ConversationMessage::new_content(role, format!("{:<40}", format!("message {}", n)))
+}
+
+fn chat(turns: usize) -> Vec<ConversationMessage> {
+    (0..turns)
+        .flat_map(|n| {
+            [
+                message(Role::User, 2 * n),
+                message(Role::Assistant, 2 * n + 1),
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn trim_keeps_the_newest_messages_within_budget() {
+    let conversation = chat(5);
+    assert_eq!(context::estimate_tokens(&conversation[0]), 14);
+
+    let window = context::trim(&conversation, 50);
+
+    assert_eq!(window.removed, 7);
+    assert_eq!(window.messages.len(), 3);
+    assert!(window.tokens() <= 50);
+    assert!(window.messages[0].content.starts_with("message 7"));
+
+    // The newest message stays even when it's over budget on its own
+    assert_eq!(context::trim(&conversation, 5).messages.len(), 1);
+}
+
+#[test]
+fn trim_never_keeps_a_result_without_its_call() {
+    let mut conversation = chat(2);
+    conversation.push(ConversationMessage::new_function_call(
+        FunctionCall {
+            name: "resolve_dates".to_string(),
+            arguments: r#"{"expression":"next week"}"#.to_string(),
+            prompt_version: None,
+            usage: None,
+        },
+        String::new(),
+    ));
+    conversation.push(ConversationMessage::new_function_result(
+        "resolve_dates",
+        "x".repeat(40),
+    ));
+    conversation.push(message(Role::Assistant, 9));
+
+    // Room for the result and the reply, but not the call before them
+    let window = context::trim(&conversation, 40);
+
+    assert_eq!(window.messages.len(), 1);
+    assert!(window.messages[0].content.starts_with("message 9"));
+}
+
+#[tokio::test]
+async fn summarize_replaces_older_messages_with_the_models_summary() {
+    let fake = FakeLlm::start(vec![text_reply("Pat asked about sick days in June.")])
+        .await
+        .unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        context_token_budget: 60,
+        context_policy: ContextPolicy::Summarize,
+        ..AppConfig::empty()
+    };
+
+    let window = context::fit(&config, &Clock::System, &chat(5)).await;
+
+    assert!(window.summarized);
+    assert_eq!(
+        window.messages[0].content,
+        format!("{}Pat asked about sick days in June.", SUMMARY_PREFIX)
+    );
+    assert!(window.tokens() <= 60, "{}", window.tokens());
+    assert_eq!(window.removed + window.messages.len() - 1, 10);
+    let transcript = fake.requests()[0]["messages"][1]["content"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(transcript.starts_with("User: message 0"), "{}", transcript);
+}
+
+#[tokio::test]
+async fn a_failed_summary_falls_back_to_trimming() {
+    // No scripted replies, so the model call fails
+    let fake = FakeLlm::start(vec![]).await.unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        context_token_budget: 50,
+        context_policy: ContextPolicy::Summarize,
+        ..AppConfig::empty()
+    };
+
+    let window = context::fit(&config, &Clock::System, &chat(5)).await;
+
+    assert!(!window.summarized);
+    assert_eq!(window.messages.len(), 3);
+}
@@ -0,0 +1,117 @@
+use agent::{
+    dates::{self, DateContext},
+    holidays::{Holiday, HolidayCalendar},
+};
+use chrono::{NaiveDate, Weekday};
+
+// A Wednesday
+fn context(week_start: Weekday) -> DateContext {
+    DateContext {
+        today: "2025-06-04".parse().unwrap(),
+        week_start,
+        holidays: HolidayCalendar::default(),
+    }
+}
+
+fn resolve(expression: &str, week_start: Weekday) -> Vec<String> {
+    dates::resolve(expression, &context(week_start))
+        .unwrap()
+        .iter()
+        .map(NaiveDate::to_string)
+        .collect()
+}
+
+#[test]
+fn relative_days_and_weekdays() {
+    assert_eq!(resolve("yesterday", Weekday::Sun), ["2025-06-03"]);
+    assert_eq!(resolve("this Friday", Weekday::Sun), ["2025-06-06"]);
+    assert_eq!(resolve("next friday", Weekday::Sun), ["2025-06-13"]);
+    assert_eq!(resolve("last Friday", Weekday::Sun), ["2025-05-30"]);
+    assert_eq!(resolve("monday", Weekday::Sun), ["2025-06-02"]);
+}
+
+#[test]
+fn week_start_decides_which_week() {
+    assert_eq!(resolve("sunday", Weekday::Sun), ["2025-06-01"]);
+    assert_eq!(resolve("sunday", Weekday::Mon), ["2025-06-08"]);
+    assert_eq!(
+        resolve("next week", Weekday::Mon),
+        [
+            "2025-06-09",
+            "2025-06-10",
+            "2025-06-11",
+            "2025-06-12",
+            "2025-06-13",
+            "2025-06-14",
+            "2025-06-15"
+        ]
+    );
+}
+
+#[test]
+fn holidays_ranges_and_lists() {
+    assert_eq!(
+        resolve("the week after Thanksgiving", Weekday::Sun)
+            .first()
+            .unwrap(),
+        "2025-11-30"
+    );
+    assert_eq!(resolve("memorial day 2026", Weekday::Sun), ["2026-05-25"]);
+    assert_eq!(resolve("good friday", Weekday::Sun), ["2025-04-18"]);
+    assert_eq!(
+        resolve("June 30th through July 2nd", Weekday::Sun),
+        ["2025-06-30", "2025-07-01", "2025-07-02"]
+    );
+    assert_eq!(
+        resolve("6/5, 6/9 and the day after tomorrow", Weekday::Sun),
+        ["2025-06-05", "2025-06-06", "2025-06-09"]
+    );
+}
+
+#[test]
+fn weekday_sets() {
+    assert_eq!(
+        resolve("every friday in june", Weekday::Sun),
+        ["2025-06-06", "2025-06-13", "2025-06-20", "2025-06-27"]
+    );
+    assert_eq!(
+        resolve("mondays and wednesdays next week", Weekday::Sun),
+        ["2025-06-09", "2025-06-11"]
+    );
+}
+
+#[test]
+fn nonsense_is_an_error() {
+    assert!(dates::resolve("whenever", &context(Weekday::Sun)).is_err());
+    assert!(dates::resolve("june 9 to june 2", &context(Weekday::Sun)).is_err());
+}
+
+#[test]
+fn company_holidays_are_found_by_name_first() {
+    let holiday = |date: &str, name: &str| Holiday {
+        date: date.parse().unwrap(),
+        name: name.to_string(),
+    };
+    let context = DateContext {
+        holidays: HolidayCalendar::new(vec![
+            holiday("2025-09-12", "Founders' Day"),
+            // Observed on the Thursday
+            holiday("2025-07-03", "Independence Day"),
+        ]),
+        ..context(Weekday::Sun)
+    };
+    let resolve = |expression| {
+        dates::resolve(expression, &context)
+            .unwrap()
+            .iter()
+            .map(NaiveDate::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(resolve("founders day"), ["2025-09-12"]);
+    assert_eq!(resolve("the week after Founders' Day")[0], "2025-09-14");
+    assert_eq!(resolve("july 4th"), ["2025-07-04"]);
+    assert_eq!(resolve("independence day"), ["2025-07-03"]);
+    assert_eq!(resolve("thanksgiving"), ["2025-11-27"]);
+    assert!(dates::resolve("founders day 2026", &context).is_err());
+}
@@ -0,0 +1,513 @@
+mod common;
+
+use agent::{
+    ExecutionError, ExecutionOptions, ExecutionResult, PayTypeChange, audit,
+    conversation_message::FunctionCall, holidays::Holiday,
+};
+use axum::http::StatusCode;
+use common::MockEbms;
+use serde_json::json;
+
+fn set_pay_type_call(arguments: serde_json::Value) -> FunctionCall {
+    FunctionCall {
+        name: "set_pay_type".to_string(),
+        arguments: arguments.to_string(),
+        prompt_version: None,
+        usage: None,
+    }
+}
+
+async fn set_pay_type(
+    mock: &MockEbms,
+    dir: &tempfile::TempDir,
+    arguments: serde_json::Value,
+) -> Result<ExecutionResult, ExecutionError> {
+    let config = mock.config("E100", dir.path());
+    agent::execute_function_call(
+        &config,
+        &set_pay_type_call(arguments),
+        &ExecutionOptions::default(),
+    )
+    .await
+}
+
+fn applied(result: Result<ExecutionResult, ExecutionError>) -> Vec<PayTypeChange> {
+    match result {
+        Ok(ExecutionResult::Success(changes)) => changes,
+        Ok(ExecutionResult::Planned(..)) => panic!("expected applied changes, got a plan"),
+        Ok(ExecutionResult::Message(msg)) => panic!("expected applied changes, got: {}", msg),
+        Err(e) => panic!("expected applied changes, got: {}", e),
+    }
+}
+
+fn invalid_request(result: Result<ExecutionResult, ExecutionError>) -> String {
+    match result {
+        Err(ExecutionError::InvalidRequest(msg)) => msg,
+        Err(e) => panic!("expected an invalid request, got: {}", e),
+        Ok(_) => panic!("expected an invalid request"),
+    }
+}
+
+fn ebms_error(result: Result<ExecutionResult, ExecutionError>) -> String {
+    match result {
+        Err(ExecutionError::EbmsError(msg)) => msg,
+        Err(e) => panic!("expected an EBMS error, got: {}", e),
+        Ok(_) => panic!("expected an EBMS error"),
+    }
+}
+
+async fn mock_with_week() -> MockEbms {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    for date in ["2025-06-02", "2025-06-03", "2025-06-04"] {
+        mock.add_entry("E100", date, "Salary");
+    }
+    mock
+}
+
+#[tokio::test]
+async fn sets_pay_type_and_records_audit() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let changes = applied(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "dates": ["2025-06-02", "2025-06-03"], "pay_type": "Vacation" }),
+        )
+        .await,
+    );
+
+    assert_eq!(changes.len(), 2);
+    assert!(changes.iter().all(|c| c.old_pay_type == "Salary"));
+    assert!(changes.iter().all(|c| c.employee.name == "Pat Smith"));
+    assert_eq!(mock.pay_level("E100", "2025-06-02").unwrap(), "Vac-SAL");
+    assert_eq!(mock.pay_level("E100", "2025-06-03").unwrap(), "Vac-SAL");
+    assert_eq!(mock.pay_level("E100", "2025-06-04").unwrap(), "Salary");
+
+    let requests = mock.modify_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["ModifyEntries"].as_array().unwrap().len(), 2);
+
+    let records = audit::load(&mock.config("E100", dir.path()));
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].changes.len(), 2);
+}
+
+#[tokio::test]
+async fn already_set_makes_no_modification() {
+    let mock = mock_with_week().await;
+    mock.add_entry("E100", "2025-06-05", "Sick-Sal");
+    let dir = tempfile::tempdir().unwrap();
+
+    let changes = applied(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "dates": ["2025-06-05"], "pay_type": "Sick" }),
+        )
+        .await,
+    );
+
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].get_function_call().is_none());
+    assert!(changes[0].to_string().contains("already set to Sick-Sal"));
+    assert!(mock.modify_requests().is_empty());
+}
+
+#[tokio::test]
+async fn only_changes_the_lines_that_differ() {
+    let mock = mock_with_week().await;
+    mock.add_entry("E100", "2025-06-05", "Vac-SAL");
+    let dir = tempfile::tempdir().unwrap();
+
+    let changes = applied(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "dates": ["2025-06-04", "2025-06-05"], "pay_type": "Vacation" }),
+        )
+        .await,
+    );
+
+    assert_eq!(changes.len(), 2);
+    let requests = mock.modify_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0]["ModifyEntries"],
+        json!([{ "AUTOID": "AUTO0003", "PayType": "Vac-SAL" }])
+    );
+}
+
+#[tokio::test]
+async fn missing_dates_are_an_error() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let error = ebms_error(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "dates": ["2025-07-04"], "pay_type": "Holiday" }),
+        )
+        .await,
+    );
+
+    assert!(error.contains("No time details found"), "{}", error);
+    assert!(mock.modify_requests().is_empty());
+}
+
+#[tokio::test]
+async fn missing_dates_are_left_out_of_a_partial_change() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let changes = applied(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "dates": ["2025-06-04", "2025-06-07"], "pay_type": "Sick" }),
+        )
+        .await,
+    );
+
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].date.to_string(), "2025-06-04");
+}
+
+#[tokio::test]
+async fn modify_errors_are_reported() {
+    let mock = mock_with_week().await;
+    mock.fail_modifications(StatusCode::INTERNAL_SERVER_ERROR, "Payroll is locked");
+    let dir = tempfile::tempdir().unwrap();
+
+    let error = ebms_error(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "dates": ["2025-06-02"], "pay_type": "Vacation" }),
+        )
+        .await,
+    );
+
+    assert!(error.contains("Payroll is locked"), "{}", error);
+    assert_eq!(mock.pay_level("E100", "2025-06-02").unwrap(), "Salary");
+    assert!(audit::load(&mock.config("E100", dir.path())).is_empty());
+}
+
+#[tokio::test]
+async fn query_errors_are_reported() {
+    let mock = mock_with_week().await;
+    mock.fail_queries(StatusCode::SERVICE_UNAVAILABLE, "EBMS is offline");
+    let dir = tempfile::tempdir().unwrap();
+
+    let error = ebms_error(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "dates": ["2025-06-02"], "pay_type": "Vacation" }),
+        )
+        .await,
+    );
+
+    assert!(error.contains("EBMS is offline"), "{}", error);
+}
+
+#[tokio::test]
+async fn bad_credentials_are_reported() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.profiles[0].ebms_password = "wrong".to_string();
+
+    let result = agent::execute_function_call(
+        &config,
+        &set_pay_type_call(json!({ "dates": ["2025-06-02"], "pay_type": "Vacation" })),
+        &ExecutionOptions::default(),
+    )
+    .await;
+
+    let error = ebms_error(result);
+    assert!(error.contains("Authorization has been denied"), "{}", error);
+}
+
+#[tokio::test]
+async fn dry_run_plans_without_writing() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = mock.config("E100", dir.path());
+
+    let result = agent::execute_function_call(
+        &config,
+        &set_pay_type_call(json!({ "dates": ["2025-06-02"], "pay_type": "Vacation" })),
+        &ExecutionOptions {
+            dry_run: true,
+            ..Default::default()
+        },
+    )
+    .await;
+
+    match result {
+        Ok(ExecutionResult::Planned(_, changes)) => assert_eq!(changes.len(), 1),
+        _ => panic!("expected a plan"),
+    }
+    assert!(mock.modify_requests().is_empty());
+    assert!(audit::load(&config).is_empty());
+}
+
+#[tokio::test]
+async fn undo_restores_the_previous_pay_type() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = mock.config("E100", dir.path());
+
+    applied(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "dates": ["2025-06-02"], "pay_type": "Sick" }),
+        )
+        .await,
+    );
+    let undone = applied(agent::undo_last(&config).await);
+
+    assert_eq!(undone.len(), 1);
+    assert_eq!(mock.pay_level("E100", "2025-06-02").unwrap(), "Salary");
+    assert!(matches!(
+        agent::undo_last(&config).await,
+        Ok(ExecutionResult::Message(_))
+    ));
+}
+
+#[tokio::test]
+async fn managers_can_only_change_their_reports() {
+    let mock = mock_with_week().await;
+    mock.add_employee("E200", "Jane", "Doe");
+    mock.add_employee("E300", "Sam", "Other");
+    mock.add_entry("E200", "2025-06-06", "Salary");
+    mock.add_entry("E300", "2025-06-06", "Salary");
+    mock.set_supervisor("E200", "E100");
+    let dir = tempfile::tempdir().unwrap();
+    let config = mock.config("E100", dir.path());
+
+    let call = |employee: &str| {
+        set_pay_type_call(
+            json!({ "dates": ["2025-06-06"], "pay_type": "Sick", "employee": employee }),
+        )
+    };
+
+    let changes = applied(
+        agent::execute_function_call(&config, &call("Jane"), &ExecutionOptions::default()).await,
+    );
+    assert_eq!(changes[0].employee.id, "E200");
+    assert_eq!(mock.pay_level("E200", "2025-06-06").unwrap(), "Sick-Sal");
+
+    match agent::execute_function_call(&config, &call("Sam"), &ExecutionOptions::default()).await {
+        Err(ExecutionError::PermissionDenied(e)) => assert!(e.contains("permission"), "{}", e),
+        _ => panic!("expected the change to be refused"),
+    }
+    assert_eq!(mock.pay_level("E300", "2025-06-06").unwrap(), "Salary");
+}
+
+#[tokio::test]
+async fn own_time_is_changed_without_reading_the_employee_directory() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = mock.config("E100", dir.path());
+
+    let changes = applied(
+        agent::execute_function_call(
+            &config,
+            &set_pay_type_call(json!({ "dates": ["2025-06-03"], "pay_type": "Sick" })),
+            &ExecutionOptions::default(),
+        )
+        .await,
+    );
+
+    assert_eq!(changes[0].employee.name, "Pat Smith");
+    assert_eq!(mock.employee_queries(), 0);
+}
+
+#[tokio::test]
+async fn lists_time_entries_in_a_range() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = mock.config("E100", dir.path());
+
+    let entries = agent::get_time_entries(
+        &config,
+        None,
+        "2025-06-03".parse().unwrap(),
+        "2025-06-10".parse().unwrap(),
+    )
+    .await
+    .unwrap_or_else(|e| panic!("{}", e));
+
+    let dates: Vec<String> = entries.iter().map(|e| e.date.to_string()).collect();
+    assert_eq!(dates, ["2025-06-03", "2025-06-04"]);
+}
+
+#[tokio::test]
+async fn ranges_expand_to_work_days_and_skip_holidays() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    // Sunday through Saturday
+    for day in 1..=7 {
+        mock.add_entry("E100", &format!("2025-06-{:02}", day), "Salary");
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.company_holidays = vec![Holiday {
+        date: "2025-06-04".parse().unwrap(),
+        name: "Founders' Day".to_string(),
+    }];
+
+    let result = agent::execute_function_call(
+        &config,
+        &set_pay_type_call(
+            json!({ "start": "2025-06-01", "end": "2025-06-07", "pay_type": "Vacation" }),
+        ),
+        &ExecutionOptions::default(),
+    )
+    .await;
+
+    let dates: Vec<String> = applied(result).iter().map(|c| c.date.to_string()).collect();
+    assert_eq!(
+        dates,
+        ["2025-06-02", "2025-06-03", "2025-06-05", "2025-06-06"]
+    );
+    assert_eq!(mock.pay_level("E100", "2025-06-04").unwrap(), "Salary");
+    assert_eq!(mock.pay_level("E100", "2025-06-07").unwrap(), "Salary");
+}
+
+#[tokio::test]
+async fn ranges_longer_than_the_maximum_are_refused() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let error = invalid_request(
+        set_pay_type(
+            &mock,
+            &dir,
+            json!({ "start": "2025-01-01", "end": "2025-12-31", "pay_type": "Vacation" }),
+        )
+        .await,
+    );
+
+    assert!(error.contains("spans 365 days"), "{}", error);
+    assert!(error.contains("at most 31 days"), "{}", error);
+    assert!(mock.modify_requests().is_empty());
+
+    let mut config = mock.config("E100", dir.path());
+    config.max_range_days = 2;
+    let refused = agent::execute_function_call(
+        &config,
+        &set_pay_type_call(
+            json!({ "start": "2025-06-02", "end": "2025-06-04", "pay_type": "Sick" }),
+        ),
+        &ExecutionOptions::default(),
+    )
+    .await;
+    assert!(invalid_request(refused).contains("at most 2 days"));
+    assert!(mock.modify_requests().is_empty());
+}
+
+#[tokio::test]
+async fn ranges_follow_the_employee_schedule() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    for day in 1..=7 {
+        mock.add_entry("E100", &format!("2025-06-{:02}", day), "Salary");
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.employee_work_days.insert(
+        "E100".to_string(),
+        vec!["Sat".to_string(), "Sun".to_string()],
+    );
+
+    let result = agent::execute_function_call(
+        &config,
+        &set_pay_type_call(
+            json!({ "start": "2025-06-01", "end": "2025-06-07", "pay_type": "Sick" }),
+        ),
+        &ExecutionOptions::default(),
+    )
+    .await;
+
+    let dates: Vec<String> = applied(result).iter().map(|c| c.date.to_string()).collect();
+    assert_eq!(dates, ["2025-06-01", "2025-06-07"]);
+}
+
+#[tokio::test]
+async fn company_pay_codes_replace_the_built_in_ones() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-02", "Salary");
+    mock.add_entry("E100", "2025-06-03", "VAC");
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.profiles[0]
+        .pay_codes
+        .insert("Vacation".to_string(), "VAC".to_string());
+
+    let changes = applied(
+        agent::execute_function_call(
+            &config,
+            &set_pay_type_call(
+                json!({ "dates": ["2025-06-02", "2025-06-03"], "pay_type": "Vacation" }),
+            ),
+            &ExecutionOptions::default(),
+        )
+        .await,
+    );
+
+    assert_eq!(mock.pay_level("E100", "2025-06-02").unwrap(), "VAC");
+    assert_eq!(mock.modify_requests().len(), 1);
+    assert!(changes[0].to_string().ends_with("from Salary to VAC"));
+    assert!(changes[1].get_function_call().is_none());
+
+    let entries = agent::get_time_entries(
+        &config,
+        None,
+        "2025-06-02".parse().unwrap(),
+        "2025-06-03".parse().unwrap(),
+    )
+    .await
+    .unwrap_or_else(|e| panic!("{}", e));
+    assert!(
+        entries
+            .iter()
+            .all(|e| e.pay_type == Some(agent::PayType::Vacation))
+    );
+
+    applied(agent::undo_last(&config).await);
+    assert_eq!(mock.pay_level("E100", "2025-06-02").unwrap(), "Salary");
+}
+
+#[tokio::test]
+async fn test_connection_finds_the_logged_in_employee() {
+    let mock = mock_with_week().await;
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+
+    let employee = agent::test_connection(&config)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(employee.name, "Pat Smith");
+
+    config.profiles[0].employee_id = "E999".to_string();
+    let error = agent::test_connection(&config)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("no employee E999"), "{}", error);
+
+    config.profiles[0].ebms_password = "wrong".to_string();
+    let error = agent::test_connection(&config)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Authorization has been denied"), "{}", error);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use agent::{
+    Employee, ExecutionOptions, PayType, TimeEntry, audit, conversation_message::FunctionCall,
+    export, ics,
+};
+use common::MockEbms;
+use serde_json::json;
+
+fn entry(name: &str, date: &str, pay_code: &str, pay_type: Option<PayType>) -> TimeEntry {
+    TimeEntry {
+        employee: Employee {
+            id: "E100".to_string(),
+            name: name.to_string(),
+        },
+        date: date.parse().unwrap(),
+        pay_code: pay_code.to_string(),
+        pay_type,
+    }
+}
+
+#[test]
+fn entries_csv_quotes_awkward_fields() {
+    let csv = export::entries_csv(&[entry(
+        "Smith, Pat \"PJ\"",
+        "2025-06-06",
+        "Vac-SAL",
+        Some(PayType::Vacation),
+    )]);
+
+    assert_eq!(
+        csv,
+        "employee_id,employee_name,date,weekday,pay_code,pay_type\r\n\
+         E100,\"Smith, Pat \"\"PJ\"\"\",2025-06-06,Friday,Vac-SAL,Vacation\r\n"
+    );
+}
+
+#[test]
+fn days_off_become_one_event_per_run() {
+    let entries = [
+        entry("Pat Smith", "2025-06-04", "Salary", Some(PayType::Salary)),
+        entry(
+            "Pat Smith",
+            "2025-06-05",
+            "Vac-SAL",
+            Some(PayType::Vacation),
+        ),
+        entry(
+            "Pat Smith",
+            "2025-06-06",
+            "Vac-SAL",
+            Some(PayType::Vacation),
+        ),
+        entry("Pat Smith", "2025-06-09", "Sick-Sal", Some(PayType::Sick)),
+        entry("Pat Smith", "2025-06-10", "OT", None),
+    ];
+
+    let text = export::days_off_ics(&entries, chrono::Utc::now());
+    let events = ics::parse(&text, None);
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].summary, "Pat Smith - Vacation");
+    assert_eq!(events[0].start.to_string(), "2025-06-05");
+    assert_eq!(events[0].end.to_string(), "2025-06-06");
+    assert_eq!(events[1].summary, "Pat Smith - Sick");
+    assert_eq!(events[1].start, events[1].end);
+}
+
+#[tokio::test]
+async fn audit_csv_lists_applied_changes() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-02", "Salary");
+    mock.add_entry("E100", "2025-06-03", "Vac-SAL");
+    let dir = tempfile::tempdir().unwrap();
+    let config = mock.config("E100", dir.path());
+
+    agent::execute_function_call(
+        &config,
+        &FunctionCall {
+            name: "set_pay_type".to_string(),
+            arguments: json!({ "dates": ["2025-06-02", "2025-06-03"], "pay_type": "Vacation" })
+                .to_string(),
+            prompt_version: Some("builtin-3".to_string()),
+            usage: None,
+        },
+        &ExecutionOptions::default(),
+    )
+    .await
+    .unwrap_or_else(|e| panic!("{}", e));
+
+    let csv = export::audit_csv(&audit::load(&config));
+    let lines: Vec<&str> = csv.lines().collect();
+    // The day that was already vacation isn't a change
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].ends_with(",Mock,E100,Pat Smith,2025-06-02,Salary,Vacation,,builtin-3"));
+}
@@ -0,0 +1,328 @@
+mod common;
+
+use agent::{
+    ExecutionOptions, ExecutionResult,
+    clock::Clock,
+    config::AppConfig,
+    eval::{self, EvalCase, EvalOutcome, Expectation},
+    holidays::Holiday,
+};
+use common::MockEbms;
+use common::fake_llm::{FakeLlm, function_call_reply, text_reply};
+use serde_json::json;
+
+fn corpus() -> Vec<EvalCase> {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("evals/golden_prompts.json");
+    eval::load_corpus(&path).unwrap()
+}
+
+// Answers the case with a fake model replaying the expected reply, which checks the harness
+// and request plumbing without a real model
+async fn run_fake_case(case: &EvalCase) -> EvalOutcome {
+    let reply = match &case.expect {
+        Expectation::FunctionCall(call) => function_call_reply(&call.name, &call.arguments),
+        Expectation::Message => text_reply("Could you tell me more?"),
+    };
+    let fake = FakeLlm::start(vec![reply]).await.unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        ..AppConfig::empty()
+    };
+
+    let mut outcome = eval::run_case(&config, case).await;
+    // The pinned date must reach the model, or relative dates can't be resolved
+    let today = case.today.format("%Y-%m-%d").to_string();
+    if outcome.passed
+        && !fake
+            .requests()
+            .iter()
+            .any(|r| r.to_string().contains(&today))
+    {
+        outcome.passed = false;
+        outcome.detail = format!("Request to the model did not mention today ({})", today);
+    }
+    outcome
+}
+
+#[tokio::test]
+async fn golden_corpus_passes_against_the_fake_model() {
+    let mut outcomes = Vec::new();
+    for case in corpus() {
+        outcomes.push(run_fake_case(&case).await);
+    }
+
+    assert!(
+        outcomes.iter().all(|o| o.passed),
+        "{}",
+        eval::report(&outcomes)
+    );
+}
+
+#[tokio::test]
+async fn wrong_dates_fail_the_case() {
+    let case = corpus()
+        .into_iter()
+        .find(|c| c.name == "vacation next week")
+        .unwrap();
+    let fake = FakeLlm::start(vec![function_call_reply(
+        "set_pay_type",
+        &json!({ "dates": ["2025-06-08", "2025-06-09"], "pay_type": "Vacation" }),
+    )])
+    .await
+    .unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        ..AppConfig::empty()
+    };
+
+    let outcome = eval::run_case(&config, &case).await;
+
+    assert!(!outcome.passed);
+    assert!(outcome.detail.contains("dates"), "{}", outcome.detail);
+}
+
+#[tokio::test]
+async fn prompt_flows_from_fake_model_to_ebms() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-03", "Salary");
+    let fake = FakeLlm::start(vec![
+        text_reply("Which day were you sick?"),
+        function_call_reply(
+            "set_pay_type",
+            &json!({ "dates": ["2025-06-03"], "pay_type": "Sick" }),
+        ),
+    ])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+    let options = ExecutionOptions {
+        clock: Clock::fixed_date("2025-06-04".parse().unwrap()),
+        ..Default::default()
+    };
+
+    let first = agent::execute_prompt(&config, "I was sick", &[], &options).await;
+    let conversation = agent::next_conversation(&[], "I was sick", &first).unwrap();
+    let second = agent::execute_prompt(&config, "yesterday", &conversation, &options).await;
+
+    assert!(matches!(second, Ok(ExecutionResult::Success(ref c)) if c.len() == 1));
+    assert_eq!(mock.pay_level("E100", "2025-06-03").unwrap(), "Sick-Sal");
+
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 2);
+    // The clarification turn is sent back with the follow-up, and today is pinned
+    let messages = requests[1]["messages"].to_string();
+    assert!(messages.contains("Which day were you sick?"));
+    assert!(messages.contains("2025-06-04"));
+}
+
+#[tokio::test]
+async fn resolved_dates_are_sent_back_to_the_model() {
+    let fake = FakeLlm::start(vec![
+        function_call_reply(
+            "resolve_dates",
+            &json!({ "expression": "next week", "workdays_only": true }),
+        ),
+        function_call_reply(
+            "set_pay_type",
+            &json!({ "dates": ["2025-06-09"], "pay_type": "Vacation" }),
+        ),
+    ])
+    .await
+    .unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        ..AppConfig::empty()
+    };
+    let options = ExecutionOptions {
+        clock: Clock::fixed_date("2025-06-04".parse().unwrap()),
+        dry_run: true,
+        ..Default::default()
+    };
+
+    let response = agent::agent_response(&config, "vacation next week", &[], &options).await;
+
+    assert!(
+        matches!(response, Ok(agent::AgentResponse::FunctionCall(ref c)) if c.name == "set_pay_type")
+    );
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 2);
+    let result = requests[1]["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["role"] == "function")
+        .unwrap();
+    assert_eq!(result["name"], "resolve_dates");
+    let dates: serde_json::Value =
+        serde_json::from_str(result["content"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        dates["dates"],
+        json!([
+            "2025-06-09",
+            "2025-06-10",
+            "2025-06-11",
+            "2025-06-12",
+            "2025-06-13"
+        ])
+    );
+}
+
+#[tokio::test]
+async fn a_prompt_may_make_exactly_max_tool_steps_lookups() {
+    let lookup = || function_call_reply("resolve_dates", &json!({ "expression": "friday" }));
+    let decide = function_call_reply(
+        "set_pay_type",
+        &json!({ "dates": ["2025-06-06"], "pay_type": "Vacation" }),
+    );
+    let config = |fake: &FakeLlm| AppConfig {
+        gpt_api_url: fake.url.clone(),
+        ..AppConfig::empty()
+    };
+    let options = ExecutionOptions {
+        clock: Clock::fixed_date("2025-06-04".parse().unwrap()),
+        ..Default::default()
+    };
+
+    let mut replies: Vec<_> = (0..agent::MAX_TOOL_STEPS).map(|_| lookup()).collect();
+    replies.push(decide.clone());
+    let fake = FakeLlm::start(replies).await.unwrap();
+    let response = agent::agent_response(&config(&fake), "vacation friday", &[], &options).await;
+    assert!(
+        matches!(response, Ok(agent::AgentResponse::FunctionCall(ref c)) if c.name == "set_pay_type")
+    );
+    assert_eq!(fake.requests().len(), agent::MAX_TOOL_STEPS + 1);
+
+    let mut replies: Vec<_> = (0..=agent::MAX_TOOL_STEPS).map(|_| lookup()).collect();
+    replies.push(decide);
+    let fake = FakeLlm::start(replies).await.unwrap();
+    let response = agent::agent_response(&config(&fake), "vacation friday", &[], &options).await;
+    assert!(
+        matches!(response, Err(agent::ExecutionError::AgentError(ref e)) if e.contains("kept looking things up"))
+    );
+    assert_eq!(fake.requests().len(), agent::MAX_TOOL_STEPS + 1);
+}
+
+#[tokio::test]
+async fn custom_system_prompt_is_rendered_and_recorded() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-03", "Salary");
+    let fake = FakeLlm::start(vec![function_call_reply(
+        "set_pay_type",
+        &json!({ "dates": ["2025-06-03"], "pay_type": "Sick" }),
+    )])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let template = dir.path().join("system_prompt.txt");
+    std::fs::write(
+        &template,
+        "# version: ops-7\nHelp {{employee_name}}. Today is {{today}}. Codes: {{pay_codes}}.",
+    )
+    .unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+    config.system_prompt_path = Some(template);
+    let options = ExecutionOptions {
+        clock: Clock::fixed_date("2025-06-04".parse().unwrap()),
+        ..Default::default()
+    };
+
+    let result = agent::execute_prompt(&config, "sick yesterday", &[], &options).await;
+
+    assert!(matches!(result, Ok(ExecutionResult::Success(_))));
+    let system = &fake.requests()[0]["messages"][0];
+    assert_eq!(system["role"], "system");
+    let content = system["content"].as_str().unwrap();
+    assert!(
+        content.starts_with(
+            "Help Pat Smith. Today is Wednesday, 2025-06-04. Codes: Sick is Sick-Sal,"
+        ),
+        "{}",
+        content
+    );
+    let records = agent::audit::load(&config);
+    let call = records[0].function_call.as_ref().unwrap();
+    assert_eq!(call.prompt_version.as_deref(), Some("ops-7"));
+}
+
+#[tokio::test]
+async fn the_user_is_named_from_the_profile_without_asking_ebms() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    let fake = FakeLlm::start(vec![text_reply("Which days?"), text_reply("Which days?")])
+        .await
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+    config.profiles[0].employee_name.clear();
+
+    // Looked up once per session, not for each prompt
+    agent::load_employee_name(&mut config.profiles[0])
+        .await
+        .unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(config.profiles[0].employee_name, "Pat Smith");
+    for _ in 0..2 {
+        agent::execute_prompt(&config, "I was out", &[], &ExecutionOptions::default())
+            .await
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    assert_eq!(mock.employee_queries(), 1);
+    let system = fake.requests()[1]["messages"][0]["content"].clone();
+    assert!(system.as_str().unwrap().contains("Pat Smith"), "{}", system);
+}
+
+#[tokio::test]
+async fn ranges_count_holidays_only_for_holiday_pay() {
+    let case = |pay_type: &str, dates: &[&str]| -> EvalCase {
+        serde_json::from_value(json!({
+            "name": pay_type,
+            "prompt": "the week of july 4th",
+            "today": "2025-06-04",
+            "expect": { "function_call": {
+                "name": "set_pay_type",
+                "arguments": { "dates": dates, "pay_type": pay_type }
+            } }
+        }))
+        .unwrap()
+    };
+    let week = ["2025-06-30", "2025-07-01", "2025-07-02", "2025-07-03"];
+    let cases = [
+        (case("Vacation", &week), "Vacation"),
+        (
+            case("Holiday", &[&week[..], &["2025-07-04"]].concat()),
+            "Holiday",
+        ),
+    ];
+    let fake = FakeLlm::start(
+        cases
+            .iter()
+            .map(|(_, pay_type)| {
+                function_call_reply(
+                    "set_pay_type",
+                    &json!({ "start": "2025-06-30", "end": "2025-07-06", "pay_type": pay_type }),
+                )
+            })
+            .collect(),
+    )
+    .await
+    .unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        company_holidays: vec![Holiday {
+            date: "2025-07-04".parse().unwrap(),
+            name: "Independence Day".to_string(),
+        }],
+        ..AppConfig::empty()
+    };
+
+    for (case, _) in &cases {
+        let outcome = eval::run_case(&config, case).await;
+        assert!(outcome.passed, "{}: {}", case.name, outcome.detail);
+    }
+}
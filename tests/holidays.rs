@@ -0,0 +1,270 @@
+mod common;
+
+use agent::{
+    ExecutionOptions, ExecutionResult,
+    clock::Clock,
+    config::AppConfig,
+    holidays::{Holiday, HolidayCalendar},
+    ics,
+};
+use common::MockEbms;
+use common::fake_llm::{FakeLlm, function_call_reply};
+use serde_json::json;
+
+const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20251225\r
+DTEND;VALUE=DATE:20251227\r
+SUMMARY:Christmas\\, and the day after\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;TZID=America/New_York:20250704T090000\r
+DTEND;TZID=America/New_York:20250704T170000\r
+SUMMARY:Independence\r
+  Day\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+fn calendar_config(dir: &tempfile::TempDir) -> AppConfig {
+    let path = dir.path().join("holidays.ics");
+    std::fs::write(&path, CALENDAR).unwrap();
+    AppConfig {
+        holiday_calendar_path: Some(path),
+        company_timezone: "America/New_York".to_string(),
+        company_holidays: vec![Holiday {
+            date: "2025-11-28".parse().unwrap(),
+            name: "Day after Thanksgiving".to_string(),
+        }],
+        ..AppConfig::empty()
+    }
+}
+
+#[test]
+fn parses_all_day_and_timed_events() {
+    let events = ics::parse(CALENDAR, Some(chrono_tz::America::New_York));
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].summary, "Christmas, and the day after");
+    assert_eq!(
+        events[0].dates().map(|d| d.to_string()).collect::<Vec<_>>(),
+        ["2025-12-25", "2025-12-26"]
+    );
+    assert_eq!(events[1].summary, "Independence Day");
+    assert_eq!(events[1].start, events[1].end);
+}
+
+#[test]
+fn timed_events_fall_on_the_day_they_are_in_the_company_timezone() {
+    let calendar = "BEGIN:VEVENT
+DTSTART;TZID=America/Los_Angeles:20250704T220000
+SUMMARY:Late in California
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20250705T030000Z
+SUMMARY:Early in UTC
+END:VEVENT
+BEGIN:VEVENT
+DTSTART;TZID=Nowhere/Special:20250704T220000
+SUMMARY:Floating
+END:VEVENT
+";
+    let days = |zone| -> Vec<String> {
+        ics::parse(calendar, Some(zone))
+            .iter()
+            .map(|e| e.start.to_string())
+            .collect()
+    };
+
+    assert_eq!(
+        days(chrono_tz::UTC),
+        ["2025-07-05", "2025-07-05", "2025-07-04"]
+    );
+    assert_eq!(
+        days(chrono_tz::America::Chicago),
+        ["2025-07-05", "2025-07-04", "2025-07-04"]
+    );
+}
+
+#[test]
+fn events_ending_at_midnight_end_on_the_day_before() {
+    let calendar = "BEGIN:VEVENT
+DTSTART:20250605T090000
+DTEND:20250607T000000
+SUMMARY:Conference
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20250610T090000
+DTEND:20250611T000100
+SUMMARY:Late night
+END:VEVENT
+";
+    let events = ics::parse(calendar, Some(chrono_tz::UTC));
+
+    assert_eq!(events[0].start.to_string(), "2025-06-05");
+    assert_eq!(events[0].end.to_string(), "2025-06-06");
+    assert_eq!(events[1].end.to_string(), "2025-06-11");
+}
+
+#[test]
+fn repeating_events_become_one_event_per_occurrence() {
+    let calendar = "BEGIN:VEVENT
+DTSTART;VALUE=DATE:20201225
+DTEND;VALUE=DATE:20201227
+RRULE:FREQ=YEARLY;BYMONTH=12;BYMONTHDAY=25
+SUMMARY:Christmas break
+END:VEVENT
+BEGIN:VEVENT
+DTSTART;VALUE=DATE:20250131
+RRULE:FREQ=MONTHLY;UNTIL=20250630
+SUMMARY:Month end
+END:VEVENT
+BEGIN:VEVENT
+DTSTART;VALUE=DATE:20250606
+RRULE:FREQ=WEEKLY;INTERVAL=2;COUNT=3
+EXDATE;VALUE=DATE:20250620
+SUMMARY:Every other Friday
+END:VEVENT
+BEGIN:VEVENT
+DTSTART;VALUE=DATE:20251127
+RRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=4TH
+SUMMARY:Thanksgiving
+END:VEVENT
+";
+    let events = ics::parse(calendar, None);
+    let starts = |summary: &str| -> Vec<String> {
+        events
+            .iter()
+            .filter(|e| e.summary == summary)
+            .map(|e| e.start.to_string())
+            .collect()
+    };
+
+    assert_eq!(
+        starts("Christmas break")[..6],
+        [
+            "2020-12-25",
+            "2021-12-25",
+            "2022-12-25",
+            "2023-12-25",
+            "2024-12-25",
+            "2025-12-25"
+        ]
+    );
+    assert!(
+        events
+            .iter()
+            .filter(|e| e.summary == "Christmas break")
+            .all(|e| e.dates().count() == 2)
+    );
+    // Months without a 31st are skipped
+    assert_eq!(
+        starts("Month end"),
+        ["2025-01-31", "2025-03-31", "2025-05-31"]
+    );
+    // The third occurrence was cancelled
+    assert_eq!(starts("Every other Friday"), ["2025-06-06", "2025-07-04"]);
+    // A rule it doesn't understand keeps just the first day
+    assert_eq!(starts("Thanksgiving"), ["2025-11-27"]);
+}
+
+#[test]
+fn escapes_are_read_in_one_pass() {
+    let calendar = r"BEGIN:VEVENT
+DTSTART;VALUE=DATE:20250704
+SUMMARY:C:\\new\, improved\nplan\;
+END:VEVENT
+";
+
+    let events = ics::parse(calendar, None);
+
+    assert_eq!(events[0].summary, "C:\\new, improved plan;");
+}
+
+#[test]
+fn written_events_have_unique_uids() {
+    let day: chrono::NaiveDate = "2025-06-09".parse().unwrap();
+    let event = ics::IcsEvent {
+        summary: "Pat Smith - Sick".to_string(),
+        start: day,
+        end: day,
+    };
+    let text = ics::write(
+        &[
+            event.clone(),
+            event.clone(),
+            ics::IcsEvent {
+                summary: "Pat Smith / Sick".to_string(),
+                ..event
+            },
+        ],
+        chrono::Utc::now(),
+    );
+
+    let uids: Vec<&str> = text.lines().filter(|l| l.starts_with("UID:")).collect();
+    let unique: std::collections::HashSet<&str> = uids.iter().copied().collect();
+    assert_eq!(uids.len(), 3);
+    assert_eq!(unique.len(), 3, "{:?}", uids);
+    assert_eq!(ics::parse(&text, None).len(), 3);
+}
+
+#[test]
+fn calendar_combines_config_and_ics() {
+    let dir = tempfile::tempdir().unwrap();
+    let calendar = HolidayCalendar::load(&calendar_config(&dir));
+
+    let dates: Vec<String> = calendar
+        .in_year(2025)
+        .iter()
+        .map(|h| h.date.to_string())
+        .collect();
+    assert_eq!(
+        dates,
+        ["2025-07-04", "2025-11-28", "2025-12-25", "2025-12-26"]
+    );
+    assert!(calendar.is_holiday("2025-12-26".parse().unwrap()));
+    assert!(calendar.in_year(2026).is_empty());
+}
+
+#[tokio::test]
+async fn marks_the_years_holidays_in_one_prompt() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    for date in ["2025-07-04", "2025-11-28", "2025-12-25", "2025-12-26"] {
+        mock.add_entry("E100", date, "Salary");
+    }
+    let fake = FakeLlm::start(vec![
+        function_call_reply("list_holidays", &json!({})),
+        function_call_reply(
+            "set_pay_type",
+            &json!({
+                "dates": ["2025-07-04", "2025-11-28", "2025-12-25", "2025-12-26"],
+                "pay_type": "Holiday"
+            }),
+        ),
+    ])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    let calendar = calendar_config(&dir);
+    config.holiday_calendar_path = calendar.holiday_calendar_path;
+    config.company_holidays = calendar.company_holidays;
+    config.company_timezone = calendar.company_timezone;
+    config.gpt_api_url = fake.url.clone();
+    let options = ExecutionOptions {
+        clock: Clock::fixed_date("2025-06-04".parse().unwrap()),
+        ..Default::default()
+    };
+
+    let result =
+        agent::execute_prompt(&config, "mark all of this year's holidays", &[], &options).await;
+
+    assert!(matches!(result, Ok(ExecutionResult::Success(ref c)) if c.len() == 4));
+    assert_eq!(mock.pay_level("E100", "2025-12-26").unwrap(), "Hol-SAL");
+    // The model was shown the calendar before choosing dates
+    let messages = fake.requests()[1]["messages"].to_string();
+    assert!(messages.contains("Day after Thanksgiving"));
+    assert!(messages.contains("2025-12-26"));
+}
@@ -0,0 +1,237 @@
+mod common;
+
+use agent::{
+    PayType,
+    import::{self, IcsImportOptions, ImportRule},
+};
+use common::MockEbms;
+
+const PTO_CALENDAR: &str = "BEGIN:VCALENDAR
+BEGIN:VEVENT
+DTSTART;VALUE=DATE:20250605
+DTEND;VALUE=DATE:20250610
+SUMMARY:Pat - Vacation
+END:VEVENT
+BEGIN:VEVENT
+DTSTART;VALUE=DATE:20250602
+SUMMARY:Pat sick
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20250603T150000Z
+SUMMARY:Dentist
+END:VEVENT
+END:VCALENDAR
+";
+
+async fn mock_with_entries() -> MockEbms {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    for day in 1..=9 {
+        mock.add_entry("E100", &format!("2025-06-{:02}", day), "Salary");
+    }
+    mock
+}
+
+#[tokio::test]
+async fn previews_matching_events_without_writing() {
+    let mock = mock_with_entries().await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = mock.config("E100", dir.path());
+
+    let plan = import::plan_ics_import(&config, PTO_CALENDAR, &IcsImportOptions::default());
+    let previews = import::preview_import(&config, &plan).await;
+
+    assert_eq!(plan.skipped.len(), 1);
+    assert!(plan.skipped[0].starts_with("Dentist"));
+    let planned: Vec<Vec<String>> = previews
+        .iter()
+        .map(|p| {
+            p.changes
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|c| c.date.to_string())
+                .collect()
+        })
+        .collect();
+    // The weekend of the 7th and 8th is left alone
+    assert_eq!(
+        planned,
+        [
+            vec!["2025-06-05", "2025-06-06", "2025-06-09"],
+            vec!["2025-06-02"]
+        ]
+    );
+    assert!(mock.modify_requests().is_empty());
+}
+
+#[tokio::test]
+async fn applies_with_configured_rules_and_window() {
+    let mock = mock_with_entries().await;
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.import_rules = vec![ImportRule {
+        pattern: "dentist".to_string(),
+        pay_type: PayType::Sick,
+    }];
+    let options = IcsImportOptions {
+        to: Some("2025-06-04".parse().unwrap()),
+        ..Default::default()
+    };
+
+    let plan = import::plan_ics_import(&config, PTO_CALENDAR, &options);
+    let previews = import::preview_import(&config, &plan).await;
+    let results = import::apply_import(&config, &previews).await;
+
+    assert_eq!(plan.items.len(), 1);
+    assert_eq!(results.len(), 1);
+    assert_eq!(mock.pay_level("E100", "2025-06-03").unwrap(), "Sick-Sal");
+    assert_eq!(mock.pay_level("E100", "2025-06-02").unwrap(), "Salary");
+    assert_eq!(mock.pay_level("E100", "2025-06-05").unwrap(), "Salary");
+}
+
+#[tokio::test]
+async fn long_events_are_split_into_ranges_that_fit() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    // 12 weeks of parental leave from Monday, June 2
+    let leave: Vec<chrono::NaiveDate> = "2025-06-02"
+        .parse::<chrono::NaiveDate>()
+        .unwrap()
+        .iter_days()
+        .take(84)
+        .collect();
+    for date in &leave {
+        mock.add_entry("E100", &date.to_string(), "Salary");
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.import_rules = vec![ImportRule {
+        pattern: "parental".to_string(),
+        pay_type: PayType::Parental,
+    }];
+    let calendar = "BEGIN:VCALENDAR
+BEGIN:VEVENT
+DTSTART;VALUE=DATE:20250602
+DTEND;VALUE=DATE:20250825
+SUMMARY:Parental leave
+END:VEVENT
+END:VCALENDAR
+";
+
+    let plan = import::plan_ics_import(&config, calendar, &IcsImportOptions::default());
+    let previews = import::preview_import(&config, &plan).await;
+    let results = import::apply_import(&config, &previews).await;
+
+    assert_eq!(plan.items.len(), 3);
+    assert!(plan.items[2].source.ends_with("part 3 of 3"));
+    assert!(
+        results.iter().all(|r| r.changes.is_ok()),
+        "{:?}",
+        results.iter().map(|r| &r.changes).collect::<Vec<_>>()
+    );
+    let changed: usize = results
+        .iter()
+        .map(|r| r.changes.as_ref().unwrap().len())
+        .sum();
+    // 12 weeks of work days
+    assert_eq!(changed, 60);
+    assert_eq!(mock.pay_level("E100", "2025-08-22").unwrap(), "Par-SAL");
+}
+
+const HR_SPREADSHEET: &str = "Employee,Date,Pay Type,Hours\r
+Pat Smith,2025-06-02,Vacation,8\r
+E200,6/3/2025,Sick-Sal,\r
+E100,2025-06-03,vacation,\r
+\"Smith, Pat\",2025-06-02,Sick,\r
+Pat,2025-06-31,Vacation,\r
+Pat,2025-06-04,Overtime,\r
+Pat,2025-06-04,Vacation,30\r
+Jordan Lee,2025-06-04,Vacation,\r
+E100,2025-06-20,Vacation,\r
+";
+
+async fn team_mock(dir: &tempfile::TempDir) -> (MockEbms, agent::config::AppConfig) {
+    let mock = mock_with_entries().await;
+    mock.add_employee("E200", "Sam", "Jones");
+    mock.add_employee("E300", "Jordan", "Lee");
+    mock.add_entry("E200", "2025-06-03", "Salary");
+    mock.set_supervisor("E200", "E100");
+    let config = mock.config("E100", dir.path());
+    (mock, config)
+}
+
+#[tokio::test]
+async fn csv_rows_are_validated_and_grouped() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_mock, config) = team_mock(&dir).await;
+
+    let csv = import::plan_csv_import(&config, HR_SPREADSHEET)
+        .await
+        .unwrap();
+
+    let valid: Vec<usize> = csv.rows.iter().map(|r| r.line).collect();
+    assert_eq!(valid, [2, 3, 4, 10]);
+    assert_eq!(csv.rows[0].hours, Some(8.0));
+    let skipped: Vec<&str> = csv
+        .plan
+        .skipped
+        .iter()
+        .map(|s| s.split(':').next().unwrap())
+        .collect();
+    assert_eq!(skipped, ["line 5", "line 6", "line 7", "line 8", "line 9"]);
+    assert!(
+        csv.plan.skipped[0].contains("conflicts with line 2"),
+        "{:?}",
+        csv.plan.skipped
+    );
+    // Pat's vacation is one batch, Sam's sick day another
+    assert_eq!(csv.plan.items.len(), 2);
+    assert_eq!(csv.plan.items[0].lines, [2, 4, 10]);
+}
+
+#[tokio::test]
+async fn csv_rows_for_part_of_a_day_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let (mock, mut config) = team_mock(&dir).await;
+    let csv = "employee,date,pay_type,hours\nE100,2025-06-02,Sick,4\nE100,2025-06-03,Sick,7.5\n";
+
+    let import = import::plan_csv_import(&config, csv).await.unwrap();
+
+    assert!(import.rows.is_empty());
+    assert!(
+        import.plan.skipped[0].contains("4 hours is less than a full day (8)"),
+        "{:?}",
+        import.plan.skipped
+    );
+    assert!(mock.modify_requests().is_empty());
+
+    config.full_day_hours = 7.5;
+    let import = import::plan_csv_import(&config, csv).await.unwrap();
+    let valid: Vec<usize> = import.rows.iter().map(|r| r.line).collect();
+    assert_eq!(valid, [3]);
+}
+
+#[tokio::test]
+async fn csv_import_applies_in_batches_with_row_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let (mock, config) = team_mock(&dir).await;
+
+    let csv = import::plan_csv_import(&config, HR_SPREADSHEET)
+        .await
+        .unwrap();
+    let previews = import::preview_import(&config, &csv.plan).await;
+    let applied = import::apply_import(&config, &previews).await;
+    let rows = import::row_results(&csv.rows, &applied);
+
+    assert_eq!(mock.modify_requests().len(), 2);
+    assert_eq!(mock.pay_level("E100", "2025-06-03").unwrap(), "Vac-SAL");
+    assert_eq!(mock.pay_level("E200", "2025-06-03").unwrap(), "Sick-Sal");
+    let failed: Vec<usize> = rows
+        .iter()
+        .filter(|(_, r)| r.is_err())
+        .map(|(line, _)| *line)
+        .collect();
+    // There's no time entry on the 20th
+    assert_eq!(failed, [10]);
+}
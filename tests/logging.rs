@@ -0,0 +1,84 @@
+use agent::{
+    config::{AppConfig, ConnectionProfile},
+    logging,
+};
+
+#[test]
+fn credentials_are_masked_wherever_they_appear() {
+    let cases = [
+        (
+            "Authorization: Bearer abc.def-123456",
+            "Authorization: Bearer [REDACTED]",
+        ),
+        (
+            "header basic YXBpOnBhc3N3b3Jk sent",
+            "header basic [REDACTED] sent",
+        ),
+        (
+            r#"{"ebms_password": "hunter2!", "employee_id": "E100"}"#,
+            r#"{"ebms_password": "[REDACTED]", "employee_id": "E100"}"#,
+        ),
+        (
+            "GET /odata?api_key=12345&$top=1",
+            "GET /odata?api_key=[REDACTED]&$top=1",
+        ),
+        (
+            "key sk-proj1234567890abcdef in use",
+            "key sk-[REDACTED] in use",
+        ),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(logging::redact(text), expected);
+    }
+
+    // Look-alikes are left alone
+    for text in [
+        r#"{"prompt_tokens": 300, "completion_tokens": 20}"#,
+        "Set basic pay for the week",
+        "task-list-for-the-whole-week",
+    ] {
+        assert_eq!(logging::redact(text), text);
+    }
+}
+
+#[test]
+fn secrets_from_the_config_are_masked_and_logs_are_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        gpt_api_key: "plain-key-without-prefix".to_string(),
+        profiles: vec![ConnectionProfile {
+            name: "Test".to_string(),
+            ebms_password: "Tr0ub4dor&3".to_string(),
+            ..Default::default()
+        }],
+        audit_log_path: Some(dir.path().join("audit.jsonl")),
+        log_level: "debug".to_string(),
+        ..AppConfig::empty()
+    };
+    logging::init(&config);
+
+    tracing::debug!("Logged in with Tr0ub4dor&3 and plain-key-without-prefix");
+
+    let recent = logging::recent();
+    let line = recent
+        .iter()
+        .find(|line| line.contains("Logged in with"))
+        .expect("the line is kept for the app");
+    assert!(
+        line.ends_with("Logged in with [REDACTED] and [REDACTED]"),
+        "{}",
+        line
+    );
+
+    let log_dir = logging::log_dir(&config).unwrap();
+    assert_eq!(log_dir, dir.path().join("logs"));
+    let file = std::fs::read_dir(&log_dir)
+        .unwrap()
+        .next()
+        .expect("a log file")
+        .unwrap()
+        .path();
+    let contents = std::fs::read_to_string(file).unwrap();
+    assert!(contents.contains("Logged in with [REDACTED] and [REDACTED]"));
+    assert!(!contents.contains("Tr0ub4dor"));
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use agent::{config::AppConfig, holidays::Holiday};
+use common::MockEbms;
+use serde_json::{Value, json};
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+/// The MCP server binary, talking newline-delimited JSON-RPC over its stdin and stdout.
+struct McpServer {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl McpServer {
+    fn start(config: &AppConfig, dir: &std::path::Path) -> Self {
+        let path = dir.join("config.toml");
+        std::fs::write(&path, toml::to_string(config).unwrap()).unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_agent-mcp"))
+            .env(agent::config::CONFIG_ENV, &path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        McpServer {
+            child,
+            stdin,
+            stdout,
+            next_id: 1,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        writeln!(self.stdin, "{}", message).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).unwrap();
+        let response: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(response["id"], json!(id), "{}", response);
+        response
+    }
+
+    fn call_tool(&mut self, name: &str, arguments: Value) -> Value {
+        let response = self.request(
+            "tools/call",
+            json!({ "name": name, "arguments": arguments }),
+        );
+        response["result"].clone()
+    }
+}
+
+impl Drop for McpServer {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+async fn setup(dir: &tempfile::TempDir) -> (MockEbms, McpServer) {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-09", "Salary");
+    mock.add_entry("E100", "2025-06-10", "Salary");
+    let config = AppConfig {
+        company_holidays: vec![Holiday {
+            date: "2025-07-04".parse().unwrap(),
+            name: "Independence Day".to_string(),
+        }],
+        ..mock.config("E100", dir.path())
+    };
+    let server = McpServer::start(&config, dir.path());
+    (mock, server)
+}
+
+// The server's stdin and stdout are blocking, so the mock EBMS needs a thread of its own
+#[tokio::test(flavor = "multi_thread")]
+async fn initialize_and_list_the_tools() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_mock, mut server) = setup(&dir).await;
+
+    let initialized = server.request(
+        "initialize",
+        json!({ "protocolVersion": "2025-06-18", "capabilities": {} }),
+    );
+    assert_eq!(initialized["result"]["protocolVersion"], "2025-06-18");
+    assert!(initialized["result"]["capabilities"]["tools"].is_object());
+    // Notifications get no response, so the next line answers the next request
+    server.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }));
+
+    let listed = server.request("tools/list", json!({}));
+    let names: Vec<&str> = listed["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "get_time_entries",
+            "set_pay_type",
+            "resolve_dates",
+            "list_holidays",
+            "undo_last_change"
+        ]
+    );
+    assert!(
+        listed["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|tool| tool["inputSchema"]["type"] == "object")
+    );
+
+    let unknown = server.request("resources/list", json!({}));
+    assert_eq!(unknown["error"]["code"], -32601);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn time_entries_are_read_changed_and_undone() {
+    let dir = tempfile::tempdir().unwrap();
+    let (mock, mut server) = setup(&dir).await;
+
+    let entries = server.call_tool(
+        "get_time_entries",
+        json!({ "start": "2025-06-09", "end": "2025-06-15" }),
+    );
+    assert_eq!(entries["isError"], false, "{}", entries);
+    assert_eq!(
+        entries["structuredContent"]["entries"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(
+        entries["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("Pat Smith (E100) Mon 2025-06-09: Salary")
+    );
+
+    let set = server.call_tool(
+        "set_pay_type",
+        json!({ "start": "2025-06-09", "end": "2025-06-10", "pay_type": "Sick" }),
+    );
+    assert_eq!(set["isError"], false, "{}", set);
+    assert_eq!(set["structuredContent"]["status"], "applied");
+    assert_eq!(mock.pay_level("E100", "2025-06-10").unwrap(), "Sick-Sal");
+
+    let undone = server.call_tool("undo_last_change", json!({}));
+    assert_eq!(undone["isError"], false, "{}", undone);
+    assert_eq!(mock.pay_level("E100", "2025-06-09").unwrap(), "Salary");
+    assert_eq!(mock.pay_level("E100", "2025-06-10").unwrap(), "Salary");
+
+    let invalid = server.call_tool(
+        "set_pay_type",
+        json!({ "dates": ["2025-06-09"], "pay_type": "Bonus" }),
+    );
+    assert_eq!(invalid["isError"], true);
+    assert_eq!(mock.pay_level("E100", "2025-06-09").unwrap(), "Salary");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lookups_answer_without_ebms() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_mock, mut server) = setup(&dir).await;
+
+    let dates = server.call_tool(
+        "resolve_dates",
+        json!({ "expression": "2025-06-09 to 2025-06-11" }),
+    );
+    assert_eq!(dates["isError"], false, "{}", dates);
+    assert_eq!(
+        dates["structuredContent"]["dates"],
+        json!(["2025-06-09", "2025-06-10", "2025-06-11"])
+    );
+
+    let holidays = server.call_tool("list_holidays", json!({ "year": 2025 }));
+    assert_eq!(holidays["isError"], false, "{}", holidays);
+    assert!(
+        holidays["structuredContent"]["holidays"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|h| h["date"] == "2025-07-04" && h["name"] == "Independence Day")
+    );
+
+    let unknown = server.call_tool("delete_everything", json!({}));
+    assert_eq!(unknown["isError"], true);
+}
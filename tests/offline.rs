@@ -0,0 +1,134 @@
+mod common;
+
+use agent::{
+    ExecutionOptions, ExecutionResult, audit,
+    clock::Clock,
+    config::{AppConfig, OfflineRules},
+    offline,
+    progress::{ProgressEvent, ProgressSink},
+};
+use common::MockEbms;
+use common::fake_llm::FakeLlm;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+// A Wednesday
+fn clock() -> Clock {
+    Clock::fixed_date("2025-06-04".parse().unwrap())
+}
+
+fn arguments(prompt: &str) -> Result<Value, String> {
+    let call = offline::parse(&AppConfig::empty(), &clock(), prompt)?;
+    assert_eq!(call.name, "set_pay_type");
+    assert_eq!(call.prompt_version.as_deref(), Some(offline::VERSION));
+    Ok(serde_json::from_str(&call.arguments).unwrap())
+}
+
+#[test]
+fn common_phrasings_become_set_pay_type_calls() {
+    assert_eq!(
+        arguments("Sick today").unwrap(),
+        json!({ "dates": ["2025-06-04"], "pay_type": "Sick" })
+    );
+    assert_eq!(
+        arguments("I may be out ill tomorrow.").unwrap(),
+        json!({ "dates": ["2025-06-05"], "pay_type": "Sick" })
+    );
+    assert_eq!(
+        arguments("PTO monday to wednesday").unwrap(),
+        json!({ "start": "2025-06-02", "end": "2025-06-04", "pay_type": "Vacation" })
+    );
+    assert_eq!(
+        arguments("vacation on 7/3/2025 and 7/7/2025").unwrap(),
+        json!({ "dates": ["2025-07-03", "2025-07-07"], "pay_type": "Vacation" })
+    );
+    assert_eq!(
+        arguments("parental leave next week").unwrap()["pay_type"],
+        json!("Parental")
+    );
+
+    assert!(arguments("friday").unwrap_err().contains("pay type"));
+    assert!(arguments("sick or vacation friday").is_err());
+    assert!(
+        arguments("sick sometime")
+            .unwrap_err()
+            .contains("which days")
+    );
+}
+
+#[tokio::test]
+async fn the_offline_rules_take_over_when_the_model_fails() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-03", "Salary");
+    // No scripted replies, so the model errors
+    let fake = FakeLlm::start(vec![]).await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let options = ExecutionOptions {
+        clock: clock(),
+        on_progress: Some({
+            let events = events.clone();
+            ProgressSink(Arc::new(move |event: &ProgressEvent| {
+                events.lock().unwrap().push(event.clone())
+            }))
+        }),
+        ..Default::default()
+    };
+
+    let result = agent::execute_prompt(&config, "sick yesterday", &[], &options).await;
+
+    match result {
+        Ok(ExecutionResult::Success(changes)) => assert_eq!(changes.len(), 1),
+        _ => panic!("expected the offline rules to make the change"),
+    }
+    assert_eq!(
+        mock.pay_level("E100", "2025-06-03").as_deref(),
+        Some("Sick-Sal")
+    );
+    assert_eq!(fake.requests().len(), 1);
+    assert!(events.lock().unwrap().iter().any(|event| matches!(
+        event,
+        ProgressEvent::OfflineRules { reason: Some(reason) } if reason.contains("no more scripted replies")
+    )));
+    let records = audit::load(&config);
+    let call = records[0].function_call.as_ref().unwrap();
+    assert_eq!(call.prompt_version.as_deref(), Some(offline::VERSION));
+
+    config.offline_rules = OfflineRules::Never;
+    assert!(
+        agent::execute_prompt(&config, "sick yesterday", &[], &options)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn the_model_is_never_asked_when_the_offline_rules_always_apply() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-06", "Salary");
+    let fake = FakeLlm::start(vec![]).await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+    config.offline_rules = OfflineRules::Always;
+    let options = ExecutionOptions {
+        clock: clock(),
+        dry_run: true,
+        ..Default::default()
+    };
+
+    let result = agent::execute_prompt(&config, "vacation friday", &[], &options).await;
+
+    match result {
+        Ok(ExecutionResult::Planned(call, changes)) => {
+            assert_eq!(call.prompt_version.as_deref(), Some(offline::VERSION));
+            assert_eq!(changes.len(), 1);
+        }
+        _ => panic!("expected a plan from the offline rules"),
+    }
+    assert!(fake.requests().is_empty());
+}
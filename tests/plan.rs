@@ -0,0 +1,108 @@
+mod common;
+
+use agent::{
+    AgentResponse, ExecutionOptions, ExecutionResult,
+    config::{AppConfig, ToolCalling},
+    plan,
+};
+use common::MockEbms;
+use common::fake_llm::{FakeLlm, text_reply};
+use serde_json::json;
+
+#[test]
+fn plans_are_read_from_the_reply() {
+    let fenced = "```json\n{\"action\": \"call\", \"function\": \"resolve_dates\", \"arguments\": {\"expression\": \"friday\"}}\n```";
+    match plan::parse(fenced) {
+        Ok(AgentResponse::FunctionCall(call)) => {
+            assert_eq!(call.name, "resolve_dates");
+            assert_eq!(call.arguments, r#"{"expression":"friday"}"#);
+        }
+        _ => panic!("expected a function call"),
+    }
+    match plan::parse("Which day were you out?") {
+        Ok(AgentResponse::Message(msg)) => assert_eq!(msg, "Which day were you out?"),
+        _ => panic!("expected the reply as it is"),
+    }
+    assert!(plan::parse(r#"{"action": "dance"}"#).is_err());
+}
+
+#[tokio::test]
+async fn a_json_plan_runs_the_same_functions() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-09", "Salary");
+    let fake = FakeLlm::start(vec![
+        text_reply(
+            &json!({
+                "action": "call",
+                "function": "resolve_dates",
+                "arguments": { "expression": "2025-06-09" }
+            })
+            .to_string(),
+        ),
+        text_reply(
+            &json!({
+                "action": "call",
+                "function": "set_pay_type",
+                "arguments": { "dates": ["2025-06-09"], "pay_type": "Sick" }
+            })
+            .to_string(),
+        ),
+    ])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+    config.tool_calling = ToolCalling::JsonSchema;
+
+    let result =
+        agent::execute_prompt(&config, "sick june 9", &[], &ExecutionOptions::default()).await;
+
+    assert!(matches!(result, Ok(ExecutionResult::Success(_))));
+    assert_eq!(mock.pay_level("E100", "2025-06-09").unwrap(), "Sick-Sal");
+
+    let requests = fake.requests();
+    assert!(requests[0].get("functions").is_none());
+    assert_eq!(requests[0]["response_format"]["type"], "json_schema");
+    let messages = requests[1]["messages"].as_array().unwrap();
+    assert!(messages.iter().all(|m| m["role"] != "function"));
+    let result = messages.last().unwrap();
+    assert_eq!(result["role"], "user");
+    assert!(
+        result["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("Result of resolve_dates: ")
+    );
+}
+
+#[tokio::test]
+async fn the_prompt_only_mode_asks_for_json_in_the_system_prompt() {
+    let fake = FakeLlm::start(vec![text_reply(
+        r#"Sure! {"action": "reply", "message": "Which day?"}"#,
+    )])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        audit_log_path: Some(dir.path().join("audit.jsonl")),
+        tool_calling: ToolCalling::JsonPrompt,
+        ..AppConfig::empty()
+    };
+
+    let result =
+        agent::execute_prompt(&config, "I was out", &[], &ExecutionOptions::default()).await;
+
+    match result {
+        Ok(ExecutionResult::Message(msg)) => assert_eq!(msg, "Which day?"),
+        _ => panic!("expected a message"),
+    }
+    let request = &fake.requests()[0];
+    assert!(request.get("response_format").is_none());
+    assert!(request.get("functions").is_none());
+    let system = request["messages"][0]["content"].as_str().unwrap();
+    assert!(system.contains("Reply with a single JSON object"));
+    assert!(system.contains("- set_pay_type: "));
+}
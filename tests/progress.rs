@@ -0,0 +1,111 @@
+mod common;
+
+use agent::{
+    ExecutionError, ExecutionOptions,
+    progress::{ProgressEvent, ProgressSink},
+};
+use common::MockEbms;
+use common::fake_llm::{FakeLlm, function_call_reply};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+fn recording_sink() -> (ProgressSink, Arc<Mutex<Vec<ProgressEvent>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let events = events.clone();
+        ProgressSink(Arc::new(move |event: &ProgressEvent| {
+            events.lock().unwrap().push(event.clone())
+        }))
+    };
+    (sink, events)
+}
+
+async fn setup(mock: &MockEbms, dir: &std::path::Path) -> (FakeLlm, agent::config::AppConfig) {
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-09", "Salary");
+    mock.add_entry("E100", "2025-06-10", "Salary");
+    mock.add_entry("E100", "2025-06-11", "Vac-SAL");
+    let fake = FakeLlm::start(vec![
+        function_call_reply("resolve_dates", &json!({ "expression": "2025-06-09" })),
+        function_call_reply(
+            "set_pay_type",
+            &json!({ "start": "2025-06-09", "end": "2025-06-11", "pay_type": "Vacation" }),
+        ),
+    ])
+    .await
+    .unwrap();
+    let mut config = mock.config("E100", dir);
+    config.gpt_api_url = fake.url.clone();
+    (fake, config)
+}
+
+#[tokio::test]
+async fn each_step_of_a_prompt_is_reported_in_order() {
+    let mock = MockEbms::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let (_fake, config) = setup(&mock, dir.path()).await;
+    let (sink, events) = recording_sink();
+    let options = ExecutionOptions {
+        on_progress: Some(sink),
+        ..Default::default()
+    };
+
+    let result = agent::execute_prompt(&config, "vacation june 9 to 11", &[], &options).await;
+
+    assert!(result.is_ok());
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            ProgressEvent::ModelRequest { step: 1 },
+            ProgressEvent::ModelResponse {
+                function: Some("resolve_dates".to_string())
+            },
+            ProgressEvent::ModelRequest { step: 2 },
+            ProgressEvent::ModelResponse {
+                function: Some("set_pay_type".to_string())
+            },
+            ProgressEvent::EbmsRead {
+                employee: "Pat Smith".to_string(),
+                days: 3
+            },
+            ProgressEvent::Planned { changes: 2 },
+            ProgressEvent::EbmsWrite { rows: 2 },
+            ProgressEvent::Verified { rows: 2 },
+        ]
+    );
+    assert_eq!(
+        ProgressEvent::EbmsWrite { rows: 2 }.to_string(),
+        "Updating 2 rows..."
+    );
+}
+
+#[tokio::test]
+async fn a_write_that_did_not_stick_is_reported_as_an_error() {
+    let mock = MockEbms::start().await;
+    mock.ignore_modifications();
+    let dir = tempfile::tempdir().unwrap();
+    let (_fake, config) = setup(&mock, dir.path()).await;
+    let (sink, events) = recording_sink();
+    let options = ExecutionOptions {
+        on_progress: Some(sink),
+        ..Default::default()
+    };
+
+    let result = agent::execute_prompt(&config, "vacation june 9 to 11", &[], &options).await;
+
+    match result {
+        Err(ExecutionError::EbmsError(e)) => {
+            assert!(
+                e.contains("2 of 2 entries still don't show Vac-SAL"),
+                "{}",
+                e
+            )
+        }
+        _ => panic!("expected the verification to fail"),
+    }
+    assert_eq!(
+        events.lock().unwrap().last(),
+        Some(&ProgressEvent::Verified { rows: 0 })
+    );
+    assert!(agent::audit::load(&config).is_empty());
+}
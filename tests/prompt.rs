@@ -0,0 +1,62 @@
+use agent::{
+    PayType,
+    prompt::{BUILT_IN_VERSION, PromptTemplate, PromptVariables},
+};
+use chrono::Weekday;
+
+fn variables() -> PromptVariables {
+    PromptVariables {
+        today: "2025-11-24".parse().unwrap(),
+        week_start: Weekday::Mon,
+        employee_name: "Pat Smith".to_string(),
+        pay_codes: vec![(PayType::Vacation, "VAC".to_string())],
+        holidays: vec![("2025-11-27".parse().unwrap(), "Thanksgiving".to_string())],
+    }
+}
+
+#[test]
+fn built_in_template_fills_every_variable() {
+    let template = PromptTemplate::built_in();
+
+    let rendered = template.render(&variables());
+
+    assert_eq!(template.version, BUILT_IN_VERSION);
+    assert!(!rendered.contains("{{"), "{}", rendered);
+    for expected in [
+        "Pat Smith",
+        "Monday, 2025-11-24",
+        "begins on Mon",
+        "Vacation is VAC",
+        "2025-11-27 Thanksgiving",
+    ] {
+        assert!(
+            rendered.contains(expected),
+            "no '{}' in {}",
+            expected,
+            rendered
+        );
+    }
+}
+
+#[test]
+fn custom_templates_are_versioned() {
+    let declared = PromptTemplate::custom("# version: 2025-06-a\nHi {{ employee_name }}\n");
+    assert_eq!(declared.version, "2025-06-a");
+    assert_eq!(declared.render(&variables()), "Hi Pat Smith");
+
+    let first = PromptTemplate::custom("Hi {{employee_name}}");
+    let edited = PromptTemplate::custom("Hello {{employee_name}}");
+    assert!(first.version.starts_with("custom-"), "{}", first.version);
+    assert_eq!(first, PromptTemplate::custom("Hi {{employee_name}}\n"));
+    assert_ne!(first.version, edited.version);
+}
+
+#[test]
+fn unknown_variables_are_left_alone() {
+    let template = PromptTemplate::custom("{{manager}} and {{today}} and {{unclosed");
+
+    assert_eq!(
+        template.render(&variables()),
+        "{{manager}} and Monday, 2025-11-24 and {{unclosed"
+    );
+}
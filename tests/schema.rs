@@ -0,0 +1,142 @@
+mod common;
+
+use agent::{
+    ExecutionError, ExecutionOptions, ExecutionResult, config::AppConfig,
+    conversation_message::FunctionCall, schema,
+};
+use common::MockEbms;
+use common::fake_llm::{FakeLlm, function_call_reply};
+use serde_json::json;
+
+fn call(arguments: serde_json::Value) -> FunctionCall {
+    FunctionCall {
+        name: "set_pay_type".to_string(),
+        arguments: arguments.to_string(),
+        prompt_version: None,
+        usage: None,
+    }
+}
+
+#[test]
+fn every_problem_with_the_arguments_is_reported() {
+    let config = AppConfig::empty();
+
+    let problems = schema::check_call(
+        &config,
+        &call(json!({
+            "dates": ["2025-06-09", "06/10/2025"],
+            "pay_type": "PTO",
+            "start": "2025-06-09",
+            "hours": 8,
+        })),
+    )
+    .unwrap_err();
+
+    assert!(
+        problems.contains("dates[1]: expected a date in YYYY-MM-DD format"),
+        "{}",
+        problems
+    );
+    assert!(
+        problems.contains("pay_type: \"PTO\" isn't one of"),
+        "{}",
+        problems
+    );
+    assert!(
+        problems.contains("end: is required when start is given"),
+        "{}",
+        problems
+    );
+    assert!(
+        problems.contains("hours: isn't a known argument"),
+        "{}",
+        problems
+    );
+
+    let valid = json!({ "start": "2025-06-09", "end": "2025-06-13", "pay_type": "Vacation" });
+    assert_eq!(schema::check_call(&config, &call(valid.clone())), Ok(valid));
+    assert!(
+        schema::check_call(
+            &config,
+            &FunctionCall {
+                arguments: "{".to_string(),
+                ..call(json!({}))
+            }
+        )
+        .unwrap_err()
+        .contains("aren't valid JSON")
+    );
+}
+
+#[tokio::test]
+async fn invalid_arguments_are_sent_back_to_the_model_to_fix() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-09", "Salary");
+    let fake = FakeLlm::start(vec![
+        function_call_reply(
+            "set_pay_type",
+            &json!({ "dates": ["06/09/2025"], "pay_type": "PTO" }),
+        ),
+        function_call_reply(
+            "set_pay_type",
+            &json!({ "dates": ["2025-06-09"], "pay_type": "Vacation" }),
+        ),
+    ])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+
+    let result =
+        agent::execute_prompt(&config, "pto june 9", &[], &ExecutionOptions::default()).await;
+
+    assert!(matches!(result, Ok(ExecutionResult::Success(_))));
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 2);
+    let feedback = requests[1]["messages"]
+        .as_array()
+        .unwrap()
+        .last()
+        .unwrap()
+        .clone();
+    assert_eq!(feedback["role"], "function");
+    let error = feedback["content"].as_str().unwrap();
+    assert!(error.contains("dates[0]: expected a date"), "{}", error);
+    assert!(
+        error.contains("pay_type: \\\"PTO\\\" isn't one of"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn repairs_are_bounded() {
+    let bad = || function_call_reply("set_pay_type", &json!({ "dates": [] }));
+    let fake = FakeLlm::start(vec![bad(), bad(), bad(), bad()])
+        .await
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        audit_log_path: Some(dir.path().join("audit.jsonl")),
+        ..AppConfig::empty()
+    };
+
+    let result = agent::execute_prompt(&config, "off", &[], &ExecutionOptions::default()).await;
+
+    match result {
+        Err(ExecutionError::AgentError(e)) => {
+            assert!(
+                e.contains("couldn't give valid arguments for set_pay_type"),
+                "{}",
+                e
+            );
+            assert!(e.contains("pay_type: is required"), "{}", e);
+        }
+        _ => panic!("expected the agent to give up"),
+    }
+    // The first try and two repairs
+    assert_eq!(fake.requests().len(), 3);
+}
@@ -0,0 +1,267 @@
+mod common;
+
+use agent::config::{AppConfig, OfflineRules};
+use common::{
+    MockEbms,
+    fake_llm::{FakeLlm, function_call_reply, text_reply},
+};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+#[test]
+fn the_server_refuses_to_start_with_an_empty_or_short_token() {
+    for token in ["", "  ", "short-token"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_agent-server"))
+            .args(["--bind", "127.0.0.1:0", "--token", token])
+            .env_remove("AGENT_SERVER_TOKEN")
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(2), "token {:?}", token);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("at least 16 characters"), "{}", stderr);
+    }
+}
+
+/// The server binary listening on a free port, with `config` as its config file.
+struct Server {
+    child: Child,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Server {
+    fn start(config: &AppConfig, dir: &std::path::Path, args: &[&str]) -> Self {
+        let path = dir.join("config.toml");
+        std::fs::write(&path, toml::to_string(config).unwrap()).unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_agent-server"))
+            .args(["--bind", "127.0.0.1:0", "--token", TOKEN])
+            .args(args)
+            .env(agent::config::CONFIG_ENV, &path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stderr = BufReader::new(child.stderr.take().unwrap());
+        let mut line = String::new();
+        let url = loop {
+            line.clear();
+            assert!(
+                stderr.read_line(&mut line).unwrap() > 0,
+                "the server exited"
+            );
+            if let Some(url) = line.trim().strip_prefix("Listening on ") {
+                break url.to_string();
+            }
+        };
+        Server {
+            child,
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn post(&self, path: &str, body: Value) -> (StatusCode, Value) {
+        let response = self
+            .client
+            .post(format!("{}{}", self.url, path))
+            .bearer_auth(TOKEN)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        (response.status(), response.json().await.unwrap())
+    }
+
+    async fn get(&self, path: &str) -> reqwest::Response {
+        self.client
+            .get(format!("{}{}", self.url, path))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+async fn setup(replies: Vec<Value>) -> (MockEbms, FakeLlm, AppConfig, tempfile::TempDir) {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_employee("E300", "Jordan", "Lee");
+    for date in ["2025-06-09", "2025-06-10"] {
+        mock.add_entry("E100", date, "Salary");
+    }
+    let fake = FakeLlm::start(replies).await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        ..mock.config("E100", dir.path())
+    };
+    (mock, fake, config, dir)
+}
+
+// The server's stderr is read blocking, so the mocks need a thread of their own
+#[tokio::test(flavor = "multi_thread")]
+async fn requests_need_the_token() {
+    let (_mock, _fake, config, dir) = setup(vec![]).await;
+    let server = Server::start(&config, dir.path(), &[]);
+    let entries = format!("{}/entries?start=2025-06-09&end=2025-06-10", server.url);
+
+    let missing = server.client.get(&entries).send().await.unwrap();
+    let wrong = server
+        .client
+        .get(&entries)
+        .bearer_auth("0123456789abcdef0123456789abcdeX")
+        .send()
+        .await
+        .unwrap();
+    let right = server.get("/entries?start=2025-06-09&end=2025-06-10").await;
+
+    assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(right.status(), StatusCode::OK);
+    let body: Value = right.json().await.unwrap();
+    assert_eq!(body["entries"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn prompts_continue_a_conversation_and_can_be_undone() {
+    let (mock, fake, config, dir) = setup(vec![
+        text_reply("Which days were you sick?"),
+        function_call_reply(
+            "set_pay_type",
+            &json!({ "dates": ["2025-06-09", "2025-06-10"], "pay_type": "Sick" }),
+        ),
+    ])
+    .await;
+    let server = Server::start(&config, dir.path(), &[]);
+
+    let (status, asked) = server
+        .post(
+            "/prompt",
+            json!({ "prompt": "I was out sick", "conversation": "c1" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(asked["status"], "message");
+
+    let (status, applied) = server
+        .post(
+            "/prompt",
+            json!({ "prompt": "June 9 and 10", "conversation": "c1" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", applied);
+    assert_eq!(applied["status"], "applied");
+    assert!(
+        applied["progress"]
+            .as_array()
+            .is_some_and(|p| !p.is_empty())
+    );
+    // The second prompt was sent with the first and the model's question
+    assert!(fake.requests()[1].to_string().contains("I was out sick"));
+    assert_eq!(mock.pay_level("E100", "2025-06-10").unwrap(), "Sick-Sal");
+
+    let csv = server
+        .get("/entries?start=2025-06-09&end=2025-06-10&format=csv")
+        .await;
+    assert!(csv.text().await.unwrap().contains("Sick-Sal"));
+
+    let (status, undone) = server.post("/undo", json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", undone);
+    assert_eq!(mock.pay_level("E100", "2025-06-09").unwrap(), "Salary");
+    assert_eq!(mock.pay_level("E100", "2025-06-10").unwrap(), "Salary");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn errors_are_reported_with_the_status_for_their_cause() {
+    let (_mock, _fake, mut config, dir) = setup(vec![
+        function_call_reply(
+            "set_pay_type",
+            &json!({ "dates": ["2025-06-09"], "pay_type": "Sick", "employee": "E300" }),
+        ),
+        function_call_reply(
+            "set_pay_type",
+            &json!({ "start": "2025-01-01", "end": "2025-12-31", "pay_type": "Vacation" }),
+        ),
+    ])
+    .await;
+    config.offline_rules = OfflineRules::Never;
+    let server = Server::start(&config, dir.path(), &[]);
+
+    let (refused, body) = server
+        .post("/prompt", json!({ "prompt": "Jordan was sick monday" }))
+        .await;
+    assert_eq!(refused, StatusCode::FORBIDDEN, "{}", body);
+    assert_eq!(body["kind"], "permission");
+
+    let (too_long, body) = server
+        .post("/prompt", json!({ "prompt": "vacation all year" }))
+        .await;
+    assert_eq!(too_long, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(body["kind"], "invalid");
+
+    // The fake model has no replies left
+    let (model_failed, body) = server
+        .post("/prompt", json!({ "prompt": "sick today" }))
+        .await;
+    assert_eq!(model_failed, StatusCode::BAD_GATEWAY, "{}", body);
+
+    let others = server
+        .get("/entries?start=2025-06-09&end=2025-06-10&employee=E300")
+        .await;
+    assert_eq!(others.status(), StatusCode::FORBIDDEN);
+    let (unknown, _) = server
+        .post(
+            "/prompt",
+            json!({ "prompt": "sick today", "profile": "Nope" }),
+        )
+        .await;
+    assert_eq!(unknown, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_least_recently_used_conversation_is_forgotten() {
+    let (_mock, fake, config, dir) = setup(vec![
+        text_reply("Which days?"),
+        text_reply("Which days?"),
+        text_reply("Which days?"),
+        text_reply("Which days?"),
+        text_reply("Which days?"),
+    ])
+    .await;
+    let server = Server::start(&config, dir.path(), &["--max-conversations", "2"]);
+
+    for (prompt, conversation) in [
+        ("first of a", "a"),
+        ("first of b", "b"),
+        ("second of b", "b"),
+        ("first of c", "c"),
+        ("second of a", "a"),
+    ] {
+        let (status, body) = server
+            .post(
+                "/prompt",
+                json!({ "prompt": prompt, "conversation": conversation }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    let requests = fake.requests();
+    assert!(requests[2].to_string().contains("first of b"));
+    // Making room for c dropped a, the conversation least recently continued
+    assert!(!requests[4].to_string().contains("first of a"));
+}
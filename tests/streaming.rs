@@ -0,0 +1,90 @@
+mod common;
+
+use agent::{ExecutionOptions, ExecutionResult, TextSink, config::AppConfig, usage};
+use common::MockEbms;
+use common::fake_llm::{FakeLlm, function_call_reply, text_reply, with_usage};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+fn collecting_sink() -> (TextSink, Arc<Mutex<Vec<String>>>) {
+    let pieces = Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let pieces = pieces.clone();
+        TextSink(Arc::new(move |text: &str| {
+            pieces.lock().unwrap().push(text.to_string())
+        }))
+    };
+    (sink, pieces)
+}
+
+#[tokio::test]
+async fn streamed_text_arrives_in_pieces_and_is_assembled() {
+    let reply = "Which days were you out, and was it sick time or vacation?";
+    let fake = FakeLlm::start(vec![with_usage(text_reply(reply), 300, 15)])
+        .await
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        audit_log_path: Some(dir.path().join("audit.jsonl")),
+        ..AppConfig::empty()
+    };
+    let (sink, pieces) = collecting_sink();
+    let options = ExecutionOptions {
+        on_text: Some(sink),
+        ..Default::default()
+    };
+
+    let result = agent::execute_prompt(&config, "I was out", &[], &options).await;
+
+    match result {
+        Ok(ExecutionResult::Message(msg)) => assert_eq!(msg, reply),
+        _ => panic!("expected a message"),
+    }
+    let pieces = pieces.lock().unwrap();
+    assert!(pieces.len() > 1);
+    assert_eq!(pieces.concat(), reply);
+    assert_eq!(fake.requests()[0]["stream"], json!(true));
+
+    let records = usage::load(&config);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].usage.total(), 315);
+}
+
+#[tokio::test]
+async fn streamed_function_arguments_are_assembled_before_the_call_runs() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-09", "Salary");
+    mock.add_entry("E100", "2025-06-10", "Salary");
+    let fake = FakeLlm::start(vec![function_call_reply(
+        "set_pay_type",
+        &json!({ "dates": ["2025-06-09", "2025-06-10"], "pay_type": "Sick" }),
+    )])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+    let (sink, pieces) = collecting_sink();
+    let options = ExecutionOptions {
+        on_text: Some(sink),
+        ..Default::default()
+    };
+
+    let result = agent::execute_prompt(&config, "sick june 9 and 10", &[], &options).await;
+
+    match result {
+        Ok(ExecutionResult::Success(changes)) => assert_eq!(changes.len(), 2),
+        _ => panic!("expected the changes to be made"),
+    }
+    assert_eq!(
+        mock.pay_level("E100", "2025-06-09").as_deref(),
+        Some("Sick-Sal")
+    );
+    assert_eq!(
+        mock.pay_level("E100", "2025-06-10").as_deref(),
+        Some("Sick-Sal")
+    );
+    assert!(pieces.lock().unwrap().is_empty());
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use agent::{
+    ExecutionError, ExecutionOptions, ExecutionResult,
+    clock::Clock,
+    config::AppConfig,
+    usage::{self, ModelRate, Usage},
+};
+use common::MockEbms;
+use common::fake_llm::{FakeLlm, function_call_reply, text_reply, with_usage};
+use serde_json::json;
+
+#[test]
+fn cost_uses_the_configured_rate_before_the_default() {
+    let usage = Usage {
+        prompt_tokens: 1_000,
+        completion_tokens: 500,
+    };
+    let mut config = AppConfig::empty();
+
+    assert_eq!(usage::cost(&config, "gpt-4", usage), Some(0.06));
+    assert_eq!(usage::cost(&config, "local-llama", usage), None);
+
+    config.model_rates.insert(
+        "gpt-4".to_string(),
+        ModelRate {
+            prompt_per_million: 1.0,
+            completion_per_million: 2.0,
+        },
+    );
+    assert_eq!(usage::cost(&config, "gpt-4", usage), Some(0.002));
+}
+
+#[tokio::test]
+async fn usage_is_logged_per_call_and_recorded_with_the_change() {
+    let mock = MockEbms::start().await;
+    mock.add_employee("E100", "Pat", "Smith");
+    mock.add_entry("E100", "2025-06-09", "Salary");
+    let fake = FakeLlm::start(vec![
+        with_usage(
+            function_call_reply("resolve_dates", &json!({ "expression": "2025-06-09" })),
+            900,
+            20,
+        ),
+        with_usage(
+            function_call_reply(
+                "set_pay_type",
+                &json!({ "dates": ["2025-06-09"], "pay_type": "Vacation" }),
+            ),
+            1_100,
+            30,
+        ),
+    ])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = mock.config("E100", dir.path());
+    config.gpt_api_url = fake.url.clone();
+
+    let result = agent::execute_prompt(
+        &config,
+        "vacation june 9",
+        &[],
+        &ExecutionOptions::default(),
+    )
+    .await;
+
+    assert!(matches!(result, Ok(ExecutionResult::Success(_))));
+    let records = usage::load(&config);
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].model, "gpt-4");
+    assert_eq!(records[0].profile, "Mock");
+    assert!(records.iter().all(|r| r.cost.is_some()));
+
+    let audit = agent::audit::load(&config);
+    let call = audit[0].function_call.as_ref().unwrap();
+    assert_eq!(
+        call.usage,
+        Some(Usage {
+            prompt_tokens: 2_000,
+            completion_tokens: 50,
+        })
+    );
+
+    let today = Clock::System.today(&config);
+    let totals = usage::totals_between(&config, &records, usage::day_of(today));
+    assert_eq!(totals.calls, 2);
+    assert_eq!(totals.usage.total(), 2_050);
+    assert_eq!(totals.to_string(), "2050 tokens, $0.06");
+}
+
+#[tokio::test]
+async fn the_monthly_budget_blocks_further_calls() {
+    let fake = FakeLlm::start(vec![
+        with_usage(text_reply("Which day?"), 100_000, 0),
+        text_reply("Which day next month?"),
+        text_reply("Which day last month?"),
+    ])
+    .await
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        gpt_api_url: fake.url.clone(),
+        audit_log_path: Some(dir.path().join("audit.jsonl")),
+        monthly_budget_usd: 1.0,
+        ..AppConfig::empty()
+    };
+    let june = "2025-06-15".parse().unwrap();
+    let options = ExecutionOptions {
+        clock: Clock::fixed_date(june),
+        ..Default::default()
+    };
+
+    let first = agent::execute_prompt(&config, "I was out", &[], &options).await;
+    assert!(matches!(first, Ok(ExecutionResult::Message(_))));
+    // Stamped with the clock's time, not the wall clock's
+    let records = usage::load(&config);
+    assert_eq!(records[0].timestamp.date_naive(), june);
+
+    let second = agent::execute_prompt(&config, "friday", &[], &options).await;
+    match second {
+        Err(ExecutionError::AgentError(e)) => assert!(e.contains("budget of $1.00"), "{}", e),
+        _ => panic!("expected the budget to stop the call"),
+    }
+    assert_eq!(fake.requests().len(), 1);
+
+    // The month is the clock's, so next month starts with nothing spent, and last month
+    // doesn't count June's spending
+    for month in ["2025-07-15", "2025-05-15"] {
+        let options = ExecutionOptions {
+            clock: Clock::fixed_date(month.parse().unwrap()),
+            ..Default::default()
+        };
+        let result = agent::execute_prompt(&config, "friday", &[], &options).await;
+        assert!(
+            matches!(result, Ok(ExecutionResult::Message(_))),
+            "{}",
+            month
+        );
+    }
+    assert_eq!(fake.requests().len(), 3);
+    assert_eq!(
+        usage::totals_between(&config, &usage::load(&config), usage::month_of(june)).calls,
+        1
+    );
+}